@@ -0,0 +1,11 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Use the bundled protoc so builds do not need one installed.
+    if std::env::var_os("PROTOC").is_none() {
+        // SAFETY: build scripts run single-threaded.
+        unsafe { std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?) };
+    }
+    tonic_build::configure()
+        .build_client(false)
+        .compile_protos(&["proto/orders.proto"], &["proto"])?;
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use common::pg_pool::PgPool;
+use std::sync::Arc;
+use tokio_postgres::error::SqlState;
+
+const KEY_PREFIX_LEN: usize = 11;
+/// Attempts at a key whose prefix is not taken yet.
+const MAX_KEY_ATTEMPTS: u32 = 5;
+
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub user_id: String,
+    /// Identifies the key the request was made with.
+    pub key_prefix: String,
+}
+
+/// API keys are shown once at creation. Postgres only keeps their SHA-256
+/// hash and a unique short prefix to identify them for revocation.
+pub struct ApiKeyStore {
+    pg: PgPool,
+}
+
+#[derive(Serialize)]
+pub struct IssuedKey {
+    pub user_id: String,
+    pub api_key: String,
+    pub key_prefix: String,
+}
+
+impl ApiKeyStore {
+    pub async fn connect(database_url: &str) -> Result<Self, tokio_postgres::Error> {
+        Ok(Self { pg: PgPool::connect(database_url).await? })
+    }
+
+    pub async fn ping(&self) -> Result<(), tokio_postgres::Error> {
+        self.pg.ping().await
+    }
+
+    pub async fn authenticate(&self, api_key: &str) -> Result<Option<AuthUser>, tokio_postgres::Error> {
+        let row = self.pg.get().await
+            .query_opt(
+                "SELECT user_id, key_prefix FROM api_keys WHERE key_hash = $1 AND revoked_at IS NULL",
+                &[&hash_key(api_key)],
+            )
+            .await?;
+        Ok(row.map(|row| AuthUser { user_id: row.get(0), key_prefix: row.get(1) }))
+    }
+
+    pub async fn create_user(&self, name: &str) -> Result<IssuedKey, tokio_postgres::Error> {
+        let user_id = uuid::Uuid::new_v4().to_string();
+        self.pg.get().await
+            .execute("INSERT INTO users (user_id, name) VALUES ($1, $2)", &[&user_id, &name])
+            .await?;
+
+        self.insert_key(&user_id).await
+    }
+
+    /// Returns `None` when the user does not exist.
+    pub async fn create_key(&self, user_id: &str) -> Result<Option<IssuedKey>, tokio_postgres::Error> {
+        let exists = self.pg.get().await
+            .query_opt("SELECT 1 FROM users WHERE user_id = $1", &[&user_id])
+            .await?
+            .is_some();
+
+        if !exists {
+            return Ok(None);
+        }
+        self.insert_key(user_id).await.map(Some)
+    }
+
+    /// Retries with a fresh key when the prefix is already taken.
+    async fn insert_key(&self, user_id: &str) -> Result<IssuedKey, tokio_postgres::Error> {
+        let mut attempt = 1;
+        loop {
+            let api_key = generate_key();
+            let key_prefix = api_key[..KEY_PREFIX_LEN].to_string();
+            let inserted = self.pg.get().await
+                .execute(
+                    "INSERT INTO api_keys (user_id, key_prefix, key_hash) VALUES ($1, $2, $3)",
+                    &[&user_id, &key_prefix, &hash_key(&api_key)],
+                )
+                .await;
+
+            match inserted {
+                Ok(_) => return Ok(IssuedKey { user_id: user_id.to_string(), api_key, key_prefix }),
+                Err(e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) && attempt < MAX_KEY_ATTEMPTS => attempt += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Returns `false` when the user has no active key with this prefix.
+    pub async fn revoke_key(&self, user_id: &str, key_prefix: &str) -> Result<bool, tokio_postgres::Error> {
+        let revoked = self.pg.get().await
+            .execute(
+                "UPDATE api_keys SET revoked_at = NOW() WHERE user_id = $1 AND key_prefix = $2 AND revoked_at IS NULL",
+                &[&user_id, &key_prefix],
+            )
+            .await?;
+        Ok(revoked > 0)
+    }
+}
+
+/// Resolves the caller from the `x-api-key` header, or the `api_key` query
+/// parameter for WebSocket clients that cannot set headers.
+pub async fn require_api_key(
+    State(api_keys): State<Arc<ApiKeyStore>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let api_key = request.headers()
+        .get("x-api-key")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .or_else(|| query_param(request.uri().query(), "api_key"))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let user = api_keys.authenticate(&api_key)
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    request.extensions_mut().insert(user);
+    Ok(next.run(request).await)
+}
+
+/// Admin routes are disabled unless `ADMIN_TOKEN` is set.
+pub async fn require_admin(
+    State(admin_token): State<Option<Arc<str>>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let admin_token = admin_token.ok_or(StatusCode::NOT_FOUND)?;
+    let provided = request.headers()
+        .get("x-admin-token")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if hash_key(provided) != hash_key(&admin_token) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(next.run(request).await)
+}
+
+fn generate_key() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("ak_{}", hex::encode(bytes))
+}
+
+fn hash_key(api_key: &str) -> String {
+    hex::encode(Sha256::digest(api_key.as_bytes()))
+}
+
+fn query_param(query: Option<&str>, name: &str) -> Option<String> {
+    query?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_string())
+}
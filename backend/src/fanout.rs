@@ -0,0 +1,234 @@
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures_util::stream::{Stream, StreamExt};
+use redis::streams::{StreamId, StreamRangeReply};
+use redis::{from_redis_value, AsyncCommands};
+use std::convert::Infallible;
+use std::sync::Arc;
+use common::telemetry::TraceContext;
+use crate::orders;
+use crate::redis_pool::{RedisPool, Unavailable};
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+
+const STATUS_STREAM: &str = "status_updates";
+const REPLAY_BATCH: usize = 500;
+const FINAL_STATUSES: &[&str] = &["confirmed", "partially_filled", "failed", "rejected", "cancelled"];
+
+/// One entry of `status_updates`. `id` is its Redis stream id, which SSE
+/// clients send back as `Last-Event-ID`.
+#[derive(Debug)]
+pub struct StatusEvent {
+    pub id: String,
+    pub order_id: String,
+    pub user_id: Option<String>,
+    pub parent_order_id: Option<String>,
+    pub status: String,
+    pub data: String,
+    /// The context of the router span that published the update.
+    pub trace_context: Option<TraceContext>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Filter {
+    /// A single order, until its final status.
+    Order(String),
+    /// Every order of the user, with no end.
+    User(String),
+}
+
+/// Delivers status updates to every WebSocket and SSE subscriber from the
+/// one `status_updates` reader.
+pub struct StatusFanout {
+    redis: RedisPool,
+    events: broadcast::Sender<Arc<StatusEvent>>,
+}
+
+impl StatusEvent {
+    pub fn from_stream(message: &StreamId) -> Option<Self> {
+        let data = from_redis_value::<String>(message.map.get("status_data")?).ok()?;
+        Self::from_data(message.id.clone(), data)
+    }
+
+    fn from_data(id: String, data: String) -> Option<Self> {
+        let update = serde_json::from_str::<serde_json::Value>(&data).ok()?;
+        Some(Self {
+            id,
+            order_id: update.get("order_id")?.as_str()?.to_string(),
+            user_id: update.get("user_id").and_then(|v| v.as_str()).map(str::to_string),
+            parent_order_id: update.get("parent_order_id").and_then(|v| v.as_str()).map(str::to_string),
+            status: update.get("status").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+            trace_context: update.get("trace_context").and_then(|v| serde_json::from_value(v.clone()).ok()),
+            data,
+        })
+    }
+
+    pub fn is_final(&self) -> bool {
+        FINAL_STATUSES.contains(&self.status.as_str())
+    }
+}
+
+impl Filter {
+    fn matches(&self, event: &StatusEvent) -> bool {
+        match self {
+            Filter::Order(order_id) => &event.order_id == order_id,
+            Filter::User(user_id) => event.user_id.as_ref() == Some(user_id),
+        }
+    }
+}
+
+impl StatusFanout {
+    pub fn new(redis: RedisPool) -> Self {
+        Self { redis, events: broadcast::channel(4096).0 }
+    }
+
+    pub fn publish(&self, event: StatusEvent) {
+        let _ = self.events.send(Arc::new(event));
+    }
+
+    /// Updates matching `filter`, starting after `last_event_id` when given.
+    /// An order subscription without one starts with the order's latest
+    /// stored status. The receiver closes after an order's final status, or
+    /// early if the subscriber falls too far behind; an SSE client then
+    /// resumes from the last id it saw.
+    pub fn subscribe(&self, filter: Filter, last_event_id: Option<String>) -> mpsc::Receiver<Arc<StatusEvent>> {
+        let (tx, rx) = mpsc::channel(64);
+        let mut live = self.events.subscribe();
+        let redis = self.redis.clone();
+
+        tokio::spawn(async move {
+            let mut last_sent = last_event_id.as_deref().and_then(parse_stream_id);
+            let caught_up = match (last_event_id.as_deref().filter(|_| last_sent.is_some()), &filter) {
+                (Some(from), _) => replay(&redis, &filter, from, &tx).await,
+                (None, Filter::Order(order_id)) => latest(&redis, order_id, &tx).await,
+                (None, Filter::User(_)) => Ok(Replayed::Caught { last: None, done: false }),
+            };
+            match caught_up {
+                Ok(Replayed::Caught { last, done }) => {
+                    last_sent = last.or(last_sent);
+                    if done {
+                        return;
+                    }
+                }
+                Ok(Replayed::Disconnected) => return,
+                Err(e) => {
+                    tracing::warn!(from = ?last_event_id, error = %e, "status replay failed");
+                    return;
+                }
+            }
+
+            loop {
+                // Watching `tx` too ends a subscription whose receiver is
+                // gone even while no update matches it.
+                let event = tokio::select! {
+                    event = live.recv() => match event {
+                        Ok(event) => event,
+                        Err(_) => return,
+                    },
+                    _ = tx.closed() => return,
+                };
+                // Already delivered by the replay.
+                if last_sent.is_some_and(|last| parse_stream_id(&event.id).is_some_and(|id| id <= last)) {
+                    continue;
+                }
+                if !filter.matches(&event) {
+                    continue;
+                }
+
+                let done = matches!(filter, Filter::Order(_)) && event.is_final();
+                if tx.send(event).await.is_err() || done {
+                    return;
+                }
+            }
+        });
+
+        rx
+    }
+}
+
+enum Replayed {
+    Caught { last: Option<(u64, u64)>, done: bool },
+    Disconnected,
+}
+
+/// Sends the stored updates after `from` that match `filter`, oldest first.
+async fn replay(
+    redis: &RedisPool,
+    filter: &Filter,
+    from: &str,
+    tx: &mpsc::Sender<Arc<StatusEvent>>,
+) -> Result<Replayed, redis::RedisError> {
+    let mut conn = redis.get().await.map_err(unavailable)?;
+    let mut cursor = from.to_string();
+    let mut last = None;
+
+    loop {
+        let batch: StreamRangeReply = conn
+            .xrange_count(STATUS_STREAM, format!("({}", cursor), "+", REPLAY_BATCH)
+            .await?;
+        let count = batch.ids.len();
+
+        for message in batch.ids {
+            cursor = message.id.clone();
+            last = parse_stream_id(&message.id);
+            let Some(event) = StatusEvent::from_stream(&message) else {
+                continue;
+            };
+            if !filter.matches(&event) {
+                continue;
+            }
+
+            let done = matches!(filter, Filter::Order(_)) && event.is_final();
+            if tx.send(Arc::new(event)).await.is_err() {
+                return Ok(Replayed::Disconnected);
+            }
+            if done {
+                return Ok(Replayed::Caught { last, done: true });
+            }
+        }
+
+        if count < REPLAY_BATCH {
+            return Ok(Replayed::Caught { last, done: false });
+        }
+    }
+}
+
+/// Sends the order's latest stored status, so a subscriber that has none
+/// sees where the order stands and is not left waiting on a finished one.
+async fn latest(
+    redis: &RedisPool,
+    order_id: &str,
+    tx: &mpsc::Sender<Arc<StatusEvent>>,
+) -> Result<Replayed, redis::RedisError> {
+    let mut conn = redis.get().await.map_err(unavailable)?;
+    let Some(event) = orders::status(&mut conn, order_id)
+        .await?
+        .and_then(|status| StatusEvent::from_data(status.event_id, status.data))
+    else {
+        return Ok(Replayed::Caught { last: None, done: false });
+    };
+
+    let last = parse_stream_id(&event.id);
+    let done = event.is_final();
+    if tx.send(Arc::new(event)).await.is_err() {
+        return Ok(Replayed::Disconnected);
+    }
+    Ok(Replayed::Caught { last, done })
+}
+
+fn unavailable(_: Unavailable) -> redis::RedisError {
+    redis::RedisError::from(std::io::Error::new(std::io::ErrorKind::NotConnected, "redis unavailable"))
+}
+
+/// Stream ids are `{ms}-{seq}`; anything else is not a resumable id.
+fn parse_stream_id(id: &str) -> Option<(u64, u64)> {
+    let (ms, seq) = id.split_once('-')?;
+    Some((ms.parse().ok()?, seq.parse().ok()?))
+}
+
+/// Serves a subscription as Server-Sent Events. Each event carries the
+/// update's stream id, so a reconnecting client resumes after it.
+pub fn sse(updates: mpsc::Receiver<Arc<StatusEvent>>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let events = ReceiverStream::new(updates)
+        .map(|update| Ok(Event::default().id(update.id.clone()).event("status").data(update.data.clone())));
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,208 @@
+use crate::auth::{ApiKeyStore, AuthUser};
+use crate::fanout::{Filter, StatusFanout};
+use crate::metrics;
+use crate::models::{OrderRequest, PriorityFeePolicy};
+use crate::orders::{self, Cancellation, OrderStatus, Rejected};
+use crate::rate_limit::RateLimiter;
+use crate::redis_pool::{RedisPool, Unavailable};
+use futures_util::stream::{Stream, StreamExt};
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{metadata::MetadataMap, Request, Response, Status};
+
+pub mod proto {
+    tonic::include_proto!("orders.v1");
+}
+
+use proto::order_service_server::OrderService;
+use proto::priority_fee::Policy;
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+/// The gRPC face of the order API. Orders go through the same validation,
+/// throttles and Redis streams as `POST /api/orders/execute`, and updates
+/// come from the same fan-out as the WebSocket and SSE routes.
+pub struct OrderGrpc {
+    pub api_keys: Arc<ApiKeyStore>,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub fanout: Arc<StatusFanout>,
+    pub redis: RedisPool,
+}
+
+impl OrderGrpc {
+    /// Resolves the caller from the `x-api-key` metadata.
+    async fn authenticate(&self, metadata: &MetadataMap) -> Result<AuthUser, Status> {
+        let api_key = metadata
+            .get("x-api-key")
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| Status::unauthenticated("missing x-api-key"))?;
+
+        self.api_keys
+            .authenticate(api_key)
+            .await
+            .map_err(|_| Status::unavailable("api key store unavailable"))?
+            .ok_or_else(|| Status::unauthenticated("invalid api key"))
+    }
+
+    async fn authorize_order(&self, metadata: &MetadataMap, order_id: &str) -> Result<String, Status> {
+        let user_id = self.authenticate(metadata).await?.user_id;
+        if !orders::is_order_owner(&self.redis, order_id, &user_id).await.map_err(unavailable)? {
+            return Err(Status::permission_denied("forbidden"));
+        }
+        Ok(user_id)
+    }
+}
+
+#[tonic::async_trait]
+impl OrderService for OrderGrpc {
+    async fn submit_order(&self, request: Request<proto::SubmitOrderRequest>) -> Result<Response<proto::SubmitOrderResponse>, Status> {
+        let user = self.authenticate(request.metadata()).await?;
+        // Lets an `idempotency-key` metadata entry act like the HTTP header.
+        let headers = request.metadata().clone().into_headers();
+        let payload = order_request(request.into_inner());
+
+        // Not throttled per IP: gRPC callers such as the FIX gateway submit
+        // for many clients from one address, so the per-key limits apply.
+        let submitted = orders::submit(&self.redis, &self.rate_limiter, &user, None, &headers, &payload).await;
+        metrics::order_submitted("grpc", &submitted);
+        let accepted = submitted.map_err(rejected_status)?;
+        Ok(Response::new(proto::SubmitOrderResponse {
+            order_id: accepted.order_id,
+            client_order_id: accepted.client_order_id,
+            replayed: accepted.replayed,
+        }))
+    }
+
+    async fn get_order(&self, request: Request<proto::GetOrderRequest>) -> Result<Response<proto::Order>, Status> {
+        let order_id = request.get_ref().order_id.clone();
+        self.authorize_order(request.metadata(), &order_id).await?;
+
+        let mut conn = self.redis.get().await.map_err(unavailable)?;
+        let status = orders::status(&mut conn, &order_id).await.map_err(|_| Status::unavailable("redis unavailable"))?;
+        Ok(Response::new(order(order_id, status)))
+    }
+
+    async fn list_orders(&self, request: Request<proto::ListOrdersRequest>) -> Result<Response<proto::ListOrdersResponse>, Status> {
+        let user_id = self.authenticate(request.metadata()).await?.user_id;
+        let request = request.into_inner();
+        let page_size = match request.page_size as usize {
+            0 => DEFAULT_PAGE_SIZE,
+            size => size.min(MAX_PAGE_SIZE),
+        };
+        let offset = match request.page_token.as_str() {
+            "" => 0,
+            token => token.parse::<usize>().map_err(|_| Status::invalid_argument("invalid page_token"))?,
+        };
+
+        let mut conn = self.redis.get().await.map_err(unavailable)?;
+        let page = orders::list(&mut conn, &user_id, offset, page_size + 1)
+            .await
+            .map_err(|_| Status::unavailable("redis unavailable"))?;
+
+        let next_page_token = if page.len() > page_size { (offset + page_size).to_string() } else { String::new() };
+        let orders = page.into_iter().take(page_size).map(|(order_id, status)| order(order_id, status)).collect();
+        Ok(Response::new(proto::ListOrdersResponse { orders, next_page_token }))
+    }
+
+    async fn cancel_order(&self, request: Request<proto::CancelOrderRequest>) -> Result<Response<proto::CancelOrderResponse>, Status> {
+        let order_id = request.get_ref().order_id.clone();
+        let user_id = self.authorize_order(request.metadata(), &order_id).await?;
+
+        let mut conn = self.redis.dedicated().await.map_err(unavailable)?;
+        match orders::cancel(&mut conn, &order_id, &user_id).await {
+            Ok(Cancellation::Cancelled) => Ok(Response::new(proto::CancelOrderResponse { order_id, status: "cancelled".to_string() })),
+            Ok(Cancellation::NotCancellable) => Err(Status::failed_precondition("not_cancellable")),
+            Ok(Cancellation::TimedOut) => Err(Status::deadline_exceeded("cancel_timeout")),
+            Err(_) => Err(Status::unavailable("redis unavailable")),
+        }
+    }
+
+    type WatchOrdersStream = Pin<Box<dyn Stream<Item = Result<proto::Order, Status>> + Send>>;
+
+    async fn watch_orders(&self, request: Request<proto::WatchOrdersRequest>) -> Result<Response<Self::WatchOrdersStream>, Status> {
+        let filter = match &request.get_ref().order_id {
+            Some(order_id) => {
+                self.authorize_order(request.metadata(), order_id).await?;
+                Filter::Order(order_id.clone())
+            }
+            None => Filter::User(self.authenticate(request.metadata()).await?.user_id),
+        };
+
+        let updates = self.fanout.subscribe(filter, request.into_inner().last_event_id);
+        let stream = ReceiverStream::new(updates)
+            .map(|update| {
+                let status = OrderStatus { event_id: update.id.clone(), data: update.data.clone() };
+                order(update.order_id.clone(), Some(status))
+            })
+            .map(Ok);
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+fn order_request(request: proto::SubmitOrderRequest) -> OrderRequest {
+    let priority_fee = request.priority_fee.and_then(|fee| fee.policy).map(|policy| match policy {
+        Policy::Fixed(fixed) => PriorityFeePolicy::Fixed { micro_lamports: fixed.micro_lamports },
+        Policy::Percentile(percentile) => PriorityFeePolicy::Percentile {
+            percentile: percentile.percentile,
+            max_micro_lamports: percentile.max_micro_lamports,
+        },
+        Policy::MaxCap(cap) => PriorityFeePolicy::MaxCap { max_micro_lamports: cap.max_micro_lamports },
+    });
+
+    OrderRequest {
+        client_order_id: request.client_order_id,
+        token_in: request.token_in,
+        token_out: request.token_out,
+        amount: request.amount,
+        order_type: request.order_type,
+        max_slippage: request.max_slippage,
+        commitment: request.commitment,
+        priority_fee,
+        compute_units: request.compute_units,
+        protection: request.protection,
+        quote_id: request.quote_id,
+        duration_secs: request.duration_secs,
+        slices: request.slices,
+        clip_size: request.clip_size,
+        trigger_price: request.trigger_price,
+        oco_order_id: request.oco_order_id,
+    }
+}
+
+/// An order the router has not reported yet is `pending`.
+fn order(order_id: String, status: Option<OrderStatus>) -> proto::Order {
+    let Some(OrderStatus { event_id, data }) = status else {
+        return proto::Order { order_id, status: "pending".to_string(), ..Default::default() };
+    };
+
+    let update = serde_json::from_str::<serde_json::Value>(&data).unwrap_or_default();
+    let text = |field: &str| update.get(field).and_then(|value| value.as_str()).map(str::to_string);
+    proto::Order {
+        status: text("status").unwrap_or_default(),
+        reason: text("reason"),
+        tx_hash: text("tx_hash"),
+        execution_price: update.get("execution_price").and_then(|value| value.as_f64()),
+        parent_order_id: text("parent_order_id"),
+        order_id,
+        event_id,
+        update_json: data,
+    }
+}
+
+/// Carries the same JSON bodies as the HTTP errors in the status message.
+fn rejected_status(rejected: Rejected) -> Status {
+    let body = rejected.body().to_string();
+    match rejected {
+        Rejected::Invalid(_) => Status::invalid_argument(body),
+        Rejected::IdempotencyConflict { .. } => Status::already_exists(body),
+        Rejected::Halted(_) => Status::unavailable(body),
+        Rejected::Throttled(_) => Status::resource_exhausted(body),
+        Rejected::Unavailable => Status::unavailable("redis unavailable"),
+    }
+}
+
+fn unavailable(_: Unavailable) -> Status {
+    Status::unavailable("redis unavailable")
+}
@@ -0,0 +1,68 @@
+use crate::redis_pool::RedisConn;
+use crate::validation::halt_pair_field;
+use redis::AsyncCommands;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HALTS_KEY: &str = "trading:halts";
+const CONTROL_STREAM: &str = "trading_control";
+
+/// Stores the halt and announces it on the control stream in one step, so
+/// the router never sees the message before the hash is updated.
+pub async fn set(
+    conn: &mut RedisConn,
+    field: &str,
+    scope: &str,
+    target: Option<&str>,
+    mode: &str,
+    reason: Option<&str>,
+) -> Result<serde_json::Value, redis::RedisError> {
+    let halted_at_ms = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+    let halt = serde_json::json!({
+        "scope": scope,
+        "target": target,
+        "mode": mode,
+        "reason": reason,
+        "halted_at_ms": halted_at_ms,
+    });
+
+    let _: () = redis::pipe()
+        .atomic()
+        .hset(HALTS_KEY, field, halt.to_string()).ignore()
+        .xadd(CONTROL_STREAM, "*", &[("action", "halt"), ("field", field)]).ignore()
+        .query_async(conn)
+        .await?;
+    Ok(halt)
+}
+
+/// Removes the halt and announces it in one step, like `set`. Returns
+/// `false` when no such halt was active; the router just reloads the hash on
+/// the `resume` message then.
+pub async fn clear(conn: &mut RedisConn, field: &str) -> Result<bool, redis::RedisError> {
+    let (removed,): (u32,) = redis::pipe()
+        .atomic()
+        .hdel(HALTS_KEY, field)
+        .xadd(CONTROL_STREAM, "*", &[("action", "resume"), ("field", field)]).ignore()
+        .query_async(conn)
+        .await?;
+    Ok(removed > 0)
+}
+
+pub async fn list(conn: &mut RedisConn) -> Result<Vec<serde_json::Value>, redis::RedisError> {
+    let halts: Vec<String> = conn.hvals(HALTS_KEY).await?;
+    Ok(halts.iter().filter_map(|halt| serde_json::from_str(halt).ok()).collect())
+}
+
+/// The global or pair halt blocking new orders for the pair, global first.
+pub async fn intake_halt(
+    conn: &mut RedisConn,
+    token_in: &str,
+    token_out: &str,
+) -> Result<Option<serde_json::Value>, redis::RedisError> {
+    let (global, pair): (Option<String>, Option<String>) = redis::pipe()
+        .hget(HALTS_KEY, "global")
+        .hget(HALTS_KEY, halt_pair_field(token_in, token_out))
+        .query_async(conn)
+        .await?;
+
+    Ok(global.or(pair).and_then(|halt| serde_json::from_str(&halt).ok()))
+}
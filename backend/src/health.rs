@@ -0,0 +1,61 @@
+use crate::auth::ApiKeyStore;
+use crate::redis_pool::RedisPool;
+use crate::webhooks::WebhookStore;
+use axum::{http::StatusCode, Json};
+use common::health::{probe, status_code, Check, StreamLimits};
+use common::streams::StreamReaders;
+use std::sync::Arc;
+
+/// Answers `/healthz` and `/readyz`.
+pub struct Health {
+    limits: StreamLimits,
+    streams: StreamReaders,
+    redis: RedisPool,
+    api_keys: Arc<ApiKeyStore>,
+    webhooks: Arc<WebhookStore>,
+}
+
+impl Health {
+    pub fn new(streams: StreamReaders, redis: RedisPool, api_keys: Arc<ApiKeyStore>, webhooks: Arc<WebhookStore>) -> Self {
+        Self { limits: StreamLimits::from_env(), streams, redis, api_keys, webhooks }
+    }
+
+    /// Fails only on what a restart fixes: a stream listener that stopped
+    /// reading. Postgres and Redis connections are reopened by themselves.
+    pub fn live(&self) -> (StatusCode, Json<serde_json::Value>) {
+        let stalled = self.limits.stalled(&self.streams);
+        let healthy = stalled.is_empty();
+
+        let body = serde_json::json!({
+            "status": if healthy { "ok" } else { "unhealthy" },
+            "stalled": stalled,
+        });
+        (status_code(healthy), Json(body))
+    }
+
+    /// Fails while Redis or Postgres is unreachable, or a stream reader is
+    /// disconnected or further behind than `MAX_STREAM_LAG_MS`.
+    pub async fn ready(&self) -> (StatusCode, Json<serde_json::Value>) {
+        let mut conn = self.redis.get().await.ok();
+        let redis = match conn.as_mut() {
+            Some(conn) => probe(redis::cmd("PING").query_async::<_, String>(conn)).await,
+            None => Check { ok: false, latency_ms: 0, error: Some("redis_unavailable".to_string()) },
+        };
+        let postgres = probe(async {
+            self.api_keys.ping().await?;
+            self.webhooks.ping().await
+        })
+        .await;
+        let streams = self.streams.report(conn.as_mut().filter(|_| redis.ok)).await;
+
+        let ready = redis.ok && postgres.ok && self.limits.caught_up(&streams);
+
+        let body = serde_json::json!({
+            "status": if ready { "ready" } else { "not_ready" },
+            "redis": redis,
+            "postgres": postgres,
+            "streams": streams,
+        });
+        (status_code(ready), Json(body))
+    }
+}
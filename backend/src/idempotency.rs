@@ -0,0 +1,282 @@
+use crate::redis_pool::RedisConn;
+use crate::validation::Violation;
+use axum::http::HeaderMap;
+use redis::{AsyncCommands, Script};
+use sha2::{Digest, Sha256};
+
+const MAX_KEY_LEN: usize = 64;
+const DEFAULT_TTL_SECS: u64 = 86400;
+
+// Claims the idempotency key and publishes the order in one step, so two
+// concurrent retries cannot both reach the order stream. Returns the
+// existing record when the key is already taken.
+const PUBLISH_ONCE_SCRIPT: &str = r#"
+local existing = redis.call('GET', KEYS[1])
+if existing then
+    return existing
+end
+redis.call('SET', KEYS[1], ARGV[1], 'EX', ARGV[2])
+redis.call('SET', KEYS[2], ARGV[3], 'EX', ARGV[4])
+redis.call('XADD', KEYS[3], '*', 'order_data', ARGV[5])
+return false
+"#;
+
+// Publishes a batch of orders in one step. KEYS holds the order stream,
+// then each order's owner key and idempotency record key; ARGV holds the
+// all-or-nothing flag, both TTLs and the user id, then each order's record
+// ('' without a client_order_id) and order data. Orders whose key is
+// already taken are skipped, or the whole batch when it is all-or-nothing.
+// Returns the existing record, or '', for each order.
+const PUBLISH_BATCH_SCRIPT: &str = r#"
+local count = (#KEYS - 1) / 2
+local existing = {}
+local taken = false
+for i = 1, count do
+    existing[i] = ''
+    if ARGV[3 + 2 * i] ~= '' then
+        existing[i] = redis.call('GET', KEYS[1 + 2 * i]) or ''
+        taken = taken or existing[i] ~= ''
+    end
+end
+if taken and ARGV[1] == '1' then
+    return existing
+end
+for i = 1, count do
+    if existing[i] == '' then
+        if ARGV[3 + 2 * i] ~= '' then
+            redis.call('SET', KEYS[1 + 2 * i], ARGV[3 + 2 * i], 'EX', ARGV[2])
+        end
+        redis.call('SET', KEYS[2 * i], ARGV[4], 'EX', ARGV[3])
+        redis.call('XADD', KEYS[1], '*', 'order_data', ARGV[4 + 2 * i])
+    end
+end
+return existing
+"#;
+
+pub enum Submission {
+    Published { order_id: String },
+    Replayed { order_id: String },
+    Conflict,
+}
+
+/// Reads the key from the `Idempotency-Key` header or the body's
+/// `client_order_id`. Both may be sent, but they must match.
+pub fn client_order_id(headers: &HeaderMap, body_key: Option<&str>) -> Result<Option<String>, Violation> {
+    let header_key = match headers.get("idempotency-key") {
+        Some(value) => Some(value.to_str().map_err(|_| invalid_key())?),
+        None => None,
+    };
+
+    let key = match (header_key, body_key) {
+        (Some(header_key), Some(body_key)) if header_key != body_key => {
+            return Err(Violation::new(
+                "client_order_id",
+                "client_order_id_mismatch",
+                "Idempotency-Key header and client_order_id must match",
+            ));
+        }
+        (Some(key), _) | (None, Some(key)) => key,
+        (None, None) => return Ok(None),
+    };
+
+    let valid = !key.is_empty()
+        && key.len() <= MAX_KEY_LEN
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'));
+    if !valid {
+        return Err(invalid_key());
+    }
+    Ok(Some(key.to_string()))
+}
+
+/// Looks up a key without claiming it, so retries are answered before they
+/// count against rate limits.
+pub async fn lookup(
+    conn: &mut RedisConn,
+    user_id: &str,
+    key: &str,
+    fingerprint: &str,
+) -> Result<Option<Submission>, redis::RedisError> {
+    let record: Option<String> = conn.get(record_key(user_id, key)).await?;
+    Ok(record.map(|record| replay(&record, fingerprint)))
+}
+
+/// [`lookup`] for many keys in one round trip.
+pub async fn lookup_many(
+    conn: &mut RedisConn,
+    user_id: &str,
+    keys: &[(&str, &str)],
+) -> Result<Vec<Option<Submission>>, redis::RedisError> {
+    if keys.is_empty() {
+        return Ok(Vec::new());
+    }
+    let record_keys: Vec<String> = keys.iter().map(|(key, _)| record_key(user_id, key)).collect();
+    let records: Vec<Option<String>> = redis::cmd("MGET").arg(&record_keys).query_async(conn).await?;
+    Ok(records
+        .into_iter()
+        .zip(keys)
+        .map(|(record, (_, fingerprint))| record.map(|record| replay(&record, fingerprint)))
+        .collect())
+}
+
+pub async fn publish_once(
+    conn: &mut RedisConn,
+    user_id: &str,
+    key: &str,
+    fingerprint: &str,
+    order_id: &str,
+    owner_ttl_secs: u64,
+    order_data: &serde_json::Value,
+) -> Result<Submission, redis::RedisError> {
+    let existing: Option<String> = Script::new(PUBLISH_ONCE_SCRIPT)
+        .key(record_key(user_id, key))
+        .key(format!("order:{}:owner", order_id))
+        .key("order_stream")
+        .arg(format!("{} {}", order_id, fingerprint))
+        .arg(ttl_secs())
+        .arg(user_id)
+        .arg(owner_ttl_secs)
+        .arg(order_data.to_string())
+        .invoke_async(conn)
+        .await?;
+
+    Ok(match existing {
+        Some(record) => replay(&record, fingerprint),
+        None => Submission::Published { order_id: order_id.to_string() },
+    })
+}
+
+/// An order of a batch, ready to publish.
+pub struct Publication<'a> {
+    pub order_id: &'a str,
+    pub client_order_id: Option<&'a str>,
+    pub fingerprint: &'a str,
+    pub order_data: &'a serde_json::Value,
+}
+
+/// Publishes every order of a batch in a single script call. Returns, per
+/// order, `None` when it was published and the existing submission when its
+/// key was already taken. An all-or-nothing batch publishes nothing if any
+/// key was taken.
+pub async fn publish_batch(
+    conn: &mut RedisConn,
+    user_id: &str,
+    orders: &[Publication<'_>],
+    owner_ttl_secs: u64,
+    all_or_nothing: bool,
+) -> Result<Vec<Option<Submission>>, redis::RedisError> {
+    let script = Script::new(PUBLISH_BATCH_SCRIPT);
+    let mut invocation = script.prepare_invoke();
+    invocation.key("order_stream").arg(if all_or_nothing { 1 } else { 0 }).arg(ttl_secs()).arg(owner_ttl_secs).arg(user_id);
+    for order in orders {
+        let record = order.client_order_id.map(|_| format!("{} {}", order.order_id, order.fingerprint));
+        invocation
+            .key(format!("order:{}:owner", order.order_id))
+            .key(record_key(user_id, order.client_order_id.unwrap_or_default()))
+            .arg(record.unwrap_or_default())
+            .arg(order.order_data.to_string());
+    }
+    let existing: Vec<String> = invocation.invoke_async(conn).await?;
+
+    Ok(existing
+        .iter()
+        .zip(orders)
+        .map(|(record, order)| (!record.is_empty()).then(|| replay(record, order.fingerprint)))
+        .collect())
+}
+
+/// Hash of the order as published, minus its order id. A key reused with
+/// different parameters is a conflict rather than a retry.
+pub fn fingerprint(order_data: &serde_json::Value) -> String {
+    hex::encode(Sha256::digest(order_data.to_string().as_bytes()))
+}
+
+fn invalid_key() -> Violation {
+    Violation::new(
+        "client_order_id",
+        "invalid_client_order_id",
+        format!("client_order_id must be 1-{} characters of letters, digits, '-', '_', '.' or ':'", MAX_KEY_LEN),
+    )
+}
+
+fn replay(record: &str, fingerprint: &str) -> Submission {
+    match record.split_once(' ') {
+        Some((order_id, stored)) if stored == fingerprint => Submission::Replayed { order_id: order_id.to_string() },
+        _ => Submission::Conflict,
+    }
+}
+
+fn ttl_secs() -> u64 {
+    std::env::var("IDEMPOTENCY_TTL_SECS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_TTL_SECS)
+}
+
+fn record_key(user_id: &str, key: &str) -> String {
+    format!("idempotency:{}:{}", user_id, key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use serde_json::json;
+
+    fn headers(key: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("idempotency-key", HeaderValue::from_str(key).unwrap());
+        headers
+    }
+
+    #[test]
+    fn key_comes_from_header_or_body() {
+        assert_eq!(client_order_id(&HeaderMap::new(), None).unwrap(), None);
+        assert_eq!(client_order_id(&headers("retry-1"), None).unwrap().as_deref(), Some("retry-1"));
+        assert_eq!(client_order_id(&HeaderMap::new(), Some("retry-1")).unwrap().as_deref(), Some("retry-1"));
+        assert_eq!(client_order_id(&headers("retry-1"), Some("retry-1")).unwrap().as_deref(), Some("retry-1"));
+    }
+
+    #[test]
+    fn mismatched_keys_are_rejected() {
+        let violation = client_order_id(&headers("retry-1"), Some("retry-2")).unwrap_err();
+        assert_eq!(violation.code, "client_order_id_mismatch");
+    }
+
+    #[test]
+    fn malformed_keys_are_rejected() {
+        let too_long = "a".repeat(MAX_KEY_LEN + 1);
+        for key in ["", "has space", "slash/key", too_long.as_str()] {
+            let violation = client_order_id(&HeaderMap::new(), Some(key)).unwrap_err();
+            assert_eq!(violation.code, "invalid_client_order_id", "{key:?}");
+        }
+        assert!(client_order_id(&HeaderMap::new(), Some(&"a".repeat(MAX_KEY_LEN))).is_ok());
+        assert!(client_order_id(&HeaderMap::new(), Some("bot-7:order_1.2")).is_ok());
+    }
+
+    #[test]
+    fn fingerprint_depends_on_parameters_not_field_order() {
+        let order = json!({"token_in": "SOL", "token_out": "USDC", "amount": 1.0});
+        let reordered = json!({"amount": 1.0, "token_out": "USDC", "token_in": "SOL"});
+        let changed = json!({"token_in": "SOL", "token_out": "USDC", "amount": 2.0});
+
+        assert_eq!(fingerprint(&order), fingerprint(&reordered));
+        assert_ne!(fingerprint(&order), fingerprint(&changed));
+        assert_eq!(fingerprint(&order).len(), 64);
+    }
+
+    #[test]
+    fn replay_returns_the_original_order_only_for_the_same_fingerprint() {
+        let record = format!("order-1 {}", "f".repeat(64));
+        match replay(&record, &"f".repeat(64)) {
+            Submission::Replayed { order_id } => assert_eq!(order_id, "order-1"),
+            _ => panic!("expected a replay"),
+        }
+        assert!(matches!(replay(&record, &"0".repeat(64)), Submission::Conflict));
+        assert!(matches!(replay("garbage", "f"), Submission::Conflict));
+    }
+
+    #[test]
+    fn records_are_scoped_per_user() {
+        assert_ne!(record_key("alice", "retry-1"), record_key("bob", "retry-1"));
+    }
+}
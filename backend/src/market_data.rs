@@ -0,0 +1,131 @@
+use axum::extract::ws::{Message, WebSocket};
+use futures_util::{sink::SinkExt, stream::StreamExt};
+use crate::redis_pool::RedisPool;
+use common::streams::StreamReaders;
+use redis::streams::{StreamId, StreamRangeReply};
+use redis::{from_redis_value, AsyncCommands};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use tokio::sync::broadcast;
+
+const MARKET_DATA_STREAM: &str = "market_data";
+const BACKFILL_COUNT: usize = 100;
+
+/// The router's price feed as read from the `market_data` stream: the
+/// latest snapshot per pair, and every new one for WebSocket subscribers.
+pub struct MarketData {
+    latest: RwLock<HashMap<String, serde_json::Value>>,
+    updates: broadcast::Sender<serde_json::Value>,
+}
+
+impl Default for MarketData {
+    fn default() -> Self {
+        Self { latest: RwLock::new(HashMap::new()), updates: broadcast::channel(1024).0 }
+    }
+}
+
+impl MarketData {
+    /// Latest snapshots for `pairs`, or every pair when empty, sorted by pair.
+    pub fn snapshot(&self, pairs: &[String]) -> Vec<serde_json::Value> {
+        let latest = self.latest.read().unwrap();
+        let mut snapshots: Vec<_> = latest
+            .iter()
+            .filter(|(pair, _)| wanted(pairs, pair))
+            .map(|(_, snapshot)| snapshot.clone())
+            .collect();
+        snapshots.sort_by(|a, b| a["pair"].as_str().cmp(&b["pair"].as_str()));
+        snapshots
+    }
+
+    /// Seeds the cache from the end of the stream, then follows it. When
+    /// Redis is down at startup the cache fills from new snapshots instead.
+    pub async fn listen(&self, streams: StreamReaders, redis: RedisPool) {
+        let mut latest_msg_id = "$".to_string();
+        if let Ok(mut conn) = redis.get().await {
+            let backfill: Result<StreamRangeReply, _> = conn.xrevrange_count(MARKET_DATA_STREAM, "+", "-", BACKFILL_COUNT).await;
+            if let Ok(backfill) = backfill {
+                if let Some(newest) = backfill.ids.first() {
+                    latest_msg_id = newest.id.clone();
+                }
+                for message in backfill.ids.iter().rev() {
+                    self.record(message);
+                }
+            }
+        }
+
+        let mut reader = streams.reader("market_data", MARKET_DATA_STREAM, latest_msg_id);
+        loop {
+            for message in reader.next().await {
+                self.record(&message);
+            }
+        }
+    }
+
+    /// Sends the latest snapshots for `pairs`, then every update for them,
+    /// until the client goes away.
+    pub async fn stream(&self, socket: WebSocket, pairs: Vec<String>) {
+        let (mut sender, mut receiver) = socket.split();
+        let mut updates = self.updates.subscribe();
+
+        for snapshot in self.snapshot(&pairs) {
+            if sender.send(Message::Text(snapshot.to_string())).await.is_err() {
+                return;
+            }
+        }
+
+        loop {
+            tokio::select! {
+                update = updates.recv() => match update {
+                    Ok(update) => {
+                        let pair = update["pair"].as_str().unwrap_or_default();
+                        if wanted(&pairs, pair) && sender.send(Message::Text(update.to_string())).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+                message = receiver.next() => match message {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                    Some(Ok(_)) => {}
+                },
+            }
+        }
+    }
+
+    fn record(&self, message: &StreamId) {
+        let Some(snapshot) = message
+            .map
+            .get("market_data")
+            .and_then(|value| from_redis_value::<String>(value).ok())
+            .and_then(|data| serde_json::from_str::<serde_json::Value>(&data).ok())
+        else {
+            return;
+        };
+        let Some(pair) = snapshot["pair"].as_str().map(str::to_string) else {
+            return;
+        };
+
+        self.latest.write().unwrap().insert(pair, snapshot.clone());
+        let _ = self.updates.send(snapshot);
+    }
+}
+
+/// Splits a `pairs` query parameter such as `SOL/USDC,BONK/SOL`.
+pub fn parse_pairs(pairs: Option<&str>) -> Vec<String> {
+    pairs
+        .unwrap_or_default()
+        .split(',')
+        .map(|pair| pair.trim().to_string())
+        .filter(|pair| !pair.is_empty())
+        .collect()
+}
+
+/// Pairs match in either direction, so `USDC/SOL` selects the `SOL/USDC`
+/// snapshot.
+fn wanted(pairs: &[String], pair: &str) -> bool {
+    pairs.is_empty()
+        || pairs.iter().any(|wanted| {
+            wanted == pair || wanted.split_once('/').is_some_and(|(a, b)| pair == format!("{}/{}", b, a))
+        })
+}
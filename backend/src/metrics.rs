@@ -0,0 +1,51 @@
+use crate::orders::{Accepted, Rejected};
+use prometheus::{register_int_counter_vec, register_int_gauge_vec, IntCounterVec, IntGaugeVec};
+use std::sync::LazyLock;
+
+pub use common::metrics::{render, set_streams};
+
+static ORDERS_ACCEPTED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("backend_orders_accepted_total", "Orders published to the router", &["api"]).unwrap()
+});
+
+static ORDERS_REJECTED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("backend_orders_rejected_total", "Orders refused at intake", &["api", "reason"]).unwrap()
+});
+
+static WEBSOCKETS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!("backend_websocket_connections", "Open WebSocket connections", &["endpoint"]).unwrap()
+});
+
+/// Counts an open WebSocket for as long as it is held.
+pub struct WebSocketGuard(&'static str);
+
+impl WebSocketGuard {
+    pub fn new(endpoint: &'static str) -> Self {
+        WEBSOCKETS.with_label_values(&[endpoint]).inc();
+        Self(endpoint)
+    }
+}
+
+impl Drop for WebSocketGuard {
+    fn drop(&mut self) {
+        WEBSOCKETS.with_label_values(&[self.0]).dec();
+    }
+}
+
+pub fn order_submitted(api: &str, result: &Result<Accepted, Rejected>) {
+    match result {
+        Ok(accepted) => order_accepted(api, accepted),
+        Err(rejected) => order_rejected(api, rejected.as_str(), 1),
+    }
+}
+
+/// Replays of an order already accepted are not counted again.
+pub fn order_accepted(api: &str, accepted: &Accepted) {
+    if !accepted.replayed {
+        ORDERS_ACCEPTED.with_label_values(&[api]).inc();
+    }
+}
+
+pub fn order_rejected(api: &str, reason: &str, count: u64) {
+    ORDERS_REJECTED.with_label_values(&[api, reason]).inc_by(count);
+}
@@ -1,10 +1,81 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Deserialize)]
 pub struct OrderRequest {
+    pub client_order_id: Option<String>,
     pub token_in: String,
     pub token_out: String,
     pub amount: f64,
     pub order_type: String,
     pub max_slippage: Option<f64>,
+    pub commitment: Option<String>,
+    pub priority_fee: Option<PriorityFeePolicy>,
+    pub compute_units: Option<u32>,
+    pub protection: Option<String>,
+    pub quote_id: Option<String>,
+    pub duration_secs: Option<u64>,
+    pub slices: Option<u32>,
+    pub clip_size: Option<f64>,
+    pub trigger_price: Option<f64>,
+    pub oco_order_id: Option<String>,
+}
+
+/// Orders are kept as raw JSON so one malformed order is reported on its
+/// own instead of failing the whole batch.
+#[derive(Debug, Deserialize)]
+pub struct BatchOrderRequest {
+    pub orders: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub all_or_nothing: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuoteRequest {
+    pub token_in: String,
+    pub token_out: String,
+    pub amount: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PriorityFeePolicy {
+    Fixed { micro_lamports: u64 },
+    Percentile { percentile: f64, max_micro_lamports: Option<u64> },
+    MaxCap { max_micro_lamports: u64 },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SeedWalletRequest {
+    pub balances: HashMap<String, f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HaltRequest {
+    pub scope: String,
+    pub target: Option<String>,
+    pub mode: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HaltTarget {
+    pub scope: String,
+    pub target: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MarketDataQuery {
+    pub pairs: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookRequest {
+    pub url: String,
+    pub statuses: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateUserRequest {
+    pub name: String,
 }
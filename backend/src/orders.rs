@@ -0,0 +1,481 @@
+use crate::auth::AuthUser;
+use crate::fanout::StatusEvent;
+use crate::halts;
+use crate::idempotency::{self, Publication, Submission};
+use crate::models::OrderRequest;
+use crate::rate_limit::{RateLimiter, Throttled};
+use crate::redis_pool::{RedisConn, RedisPool, Unavailable};
+use common::telemetry;
+use crate::validation::{self, ValidOrder, ValidationErrors, Violation};
+use axum::{
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use redis::AsyncCommands;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::Span;
+
+pub const ORDER_OWNER_TTL_SECS: u64 = 86400;
+const DEFAULT_CANCEL_TIMEOUT_MS: u64 = 5000;
+
+/// An order that was published, or found already published under its
+/// `client_order_id`.
+pub struct Accepted {
+    pub order_id: String,
+    pub client_order_id: Option<String>,
+    pub replayed: bool,
+}
+
+/// Why an order was not published. HTTP and gRPC report the same cases.
+pub enum Rejected {
+    Invalid(ValidationErrors),
+    IdempotencyConflict { client_order_id: String },
+    Halted(serde_json::Value),
+    Throttled(Throttled),
+    Unavailable,
+}
+
+/// An order's latest update on `status_updates`, as kept by the backend.
+pub struct OrderStatus {
+    pub event_id: String,
+    pub data: String,
+}
+
+pub enum Cancellation {
+    Cancelled,
+    NotCancellable,
+    TimedOut,
+}
+
+/// Why an order of a batch was or was not published.
+pub enum BatchOutcome {
+    Accepted(Accepted),
+    Rejected(Rejected),
+    /// Valid, but held back because another order of an all-or-nothing
+    /// batch was rejected.
+    NotSubmitted,
+}
+
+/// A validated order, not yet checked against Redis.
+struct Prepared {
+    order_id: String,
+    client_order_id: Option<String>,
+    fingerprint: String,
+    order_data: serde_json::Value,
+    /// Starts the order's trace, which the router and the status updates
+    /// it publishes continue.
+    span: Span,
+}
+
+/// Validates, deduplicates and throttles an order, then publishes it to
+/// `order_stream`. Shared by the HTTP and gRPC APIs; only HTTP orders carry
+/// a client IP to throttle.
+pub async fn submit(
+    redis: &RedisPool,
+    rate_limiter: &RateLimiter,
+    user: &AuthUser,
+    ip: Option<IpAddr>,
+    headers: &HeaderMap,
+    payload: &OrderRequest,
+) -> Result<Accepted, Rejected> {
+    let user_id = user.user_id.as_str();
+    let order = prepare(user_id, headers, payload)?;
+    let mut conn = redis.get().await.map_err(|_| Rejected::Unavailable)?;
+    if let Some(key) = &order.client_order_id {
+        match idempotency::lookup(&mut conn, user_id, key, &order.fingerprint).await {
+            Ok(None) => {}
+            Ok(Some(previous)) => return accepted(previous, key),
+            Err(_) => return Err(Rejected::Unavailable),
+        }
+    }
+
+    check_intake(&mut conn, user_id, payload, &mut HashMap::new()).await?;
+    let notional = rate_limiter.notional(&payload.token_in, payload.amount);
+    admit(rate_limiter.admit_order(&user.key_prefix, ip, &order.order_id, notional), user_id, ip)?;
+
+    let Some(key) = &order.client_order_id else {
+        if publish(&mut conn, &order.order_id, user_id, &order.order_data).await.is_err() {
+            rate_limiter.order_closed(&order.order_id);
+            return Err(Rejected::Unavailable);
+        }
+        tracing::info!(parent: &order.span, "order published");
+        return Ok(Accepted { order_id: order.order_id, client_order_id: None, replayed: false });
+    };
+
+    let submission = idempotency::publish_once(&mut conn, user_id, key, &order.fingerprint, &order.order_id, ORDER_OWNER_TTL_SECS, &order.order_data)
+        .await
+        .map_err(|_| Rejected::Unavailable);
+    if matches!(submission, Ok(Submission::Published { .. })) {
+        tracing::info!(parent: &order.span, client_order_id = %key, "order published");
+    } else {
+        rate_limiter.order_closed(&order.order_id);
+    }
+    accepted(submission?, key)
+}
+
+/// Runs every order of a batch through the checks of [`submit`] and
+/// publishes the ones that pass in a single script call, returning one
+/// outcome per order in request order. An all-or-nothing batch publishes
+/// nothing unless every order passes, and is throttled as a whole.
+pub async fn submit_batch(
+    redis: &RedisPool,
+    rate_limiter: &RateLimiter,
+    user: &AuthUser,
+    ip: Option<IpAddr>,
+    orders: Vec<serde_json::Value>,
+    all_or_nothing: bool,
+) -> Result<Vec<BatchOutcome>, Rejected> {
+    let user_id = user.user_id.as_str();
+    let mut outcomes: Vec<Option<BatchOutcome>> = orders.iter().map(|_| None).collect();
+    let mut valid = Vec::new();
+    let mut keys = HashSet::new();
+    for (index, order) in orders.into_iter().enumerate() {
+        let prepared = serde_json::from_value::<OrderRequest>(order)
+            .map_err(|e| invalid(Violation::new("body", "invalid_field", e.to_string())))
+            .and_then(|payload| Ok((prepare(user_id, &HeaderMap::new(), &payload)?, payload)));
+        match prepared {
+            Ok((order, _)) if order.client_order_id.as_ref().is_some_and(|key| !keys.insert(key.clone())) => {
+                let violation = Violation::new("client_order_id", "duplicate_client_order_id", "client_order_id must be unique within a batch");
+                outcomes[index] = Some(BatchOutcome::Rejected(invalid(violation)));
+            }
+            Ok((order, payload)) => valid.push((index, order, payload)),
+            Err(rejected) => outcomes[index] = Some(BatchOutcome::Rejected(rejected)),
+        }
+    }
+
+    let mut conn = redis.get().await.map_err(|_| Rejected::Unavailable)?;
+    let keyed: Vec<(&str, &str)> = valid
+        .iter()
+        .filter_map(|(_, order, _)| order.client_order_id.as_deref().map(|key| (key, order.fingerprint.as_str())))
+        .collect();
+    let mut previous = idempotency::lookup_many(&mut conn, user_id, &keyed)
+        .await
+        .map_err(|_| Rejected::Unavailable)?
+        .into_iter();
+
+    let mut halts = HashMap::new();
+    let mut new_orders = Vec::new();
+    for (index, order, payload) in valid {
+        if let Some(key) = &order.client_order_id {
+            if let Some(previous) = previous.next().flatten() {
+                outcomes[index] = Some(batch_outcome(accepted(previous, key)));
+                continue;
+            }
+        }
+        match check_intake(&mut conn, user_id, &payload, &mut halts).await {
+            Ok(()) => new_orders.push((index, order, rate_limiter.notional(&payload.token_in, payload.amount))),
+            Err(Rejected::Unavailable) => return Err(Rejected::Unavailable),
+            Err(rejected) => outcomes[index] = Some(BatchOutcome::Rejected(rejected)),
+        }
+    }
+
+    if all_or_nothing {
+        if outcomes.iter().any(|outcome| matches!(outcome, Some(BatchOutcome::Rejected(_)))) {
+            return Ok(finish(outcomes));
+        }
+        let admitted: Vec<(&str, f64)> = new_orders.iter().map(|(_, order, notional)| (order.order_id.as_str(), *notional)).collect();
+        admit(rate_limiter.admit_orders(&user.key_prefix, ip, &admitted), user_id, ip)?;
+    } else {
+        new_orders.retain(|(index, order, notional)| match admit(rate_limiter.admit_order(&user.key_prefix, ip, &order.order_id, *notional), user_id, ip) {
+            Ok(()) => true,
+            Err(rejected) => {
+                outcomes[*index] = Some(BatchOutcome::Rejected(rejected));
+                false
+            }
+        });
+    }
+    if new_orders.is_empty() {
+        return Ok(finish(outcomes));
+    }
+
+    let publications: Vec<Publication> = new_orders
+        .iter()
+        .map(|(_, order, _)| Publication {
+            order_id: &order.order_id,
+            client_order_id: order.client_order_id.as_deref(),
+            fingerprint: &order.fingerprint,
+            order_data: &order.order_data,
+        })
+        .collect();
+    let existing = match idempotency::publish_batch(&mut conn, user_id, &publications, ORDER_OWNER_TTL_SECS, all_or_nothing).await {
+        Ok(existing) => existing,
+        Err(_) => {
+            new_orders.iter().for_each(|(_, order, _)| rate_limiter.order_closed(&order.order_id));
+            return Err(Rejected::Unavailable);
+        }
+    };
+
+    // A key claimed by a concurrent request since the lookup above holds
+    // back the whole of an all-or-nothing batch.
+    let aborted = all_or_nothing && existing.iter().any(Option::is_some);
+    for ((index, order, _), existing) in new_orders.into_iter().zip(existing) {
+        if aborted || existing.is_some() {
+            rate_limiter.order_closed(&order.order_id);
+        }
+        outcomes[index] = Some(match existing {
+            Some(previous) => batch_outcome(accepted(previous, order.client_order_id.as_deref().unwrap_or_default())),
+            None if aborted => BatchOutcome::NotSubmitted,
+            None => {
+                tracing::info!(parent: &order.span, "order published");
+                BatchOutcome::Accepted(Accepted { order_id: order.order_id, client_order_id: order.client_order_id, replayed: false })
+            }
+        });
+    }
+    Ok(finish(outcomes))
+}
+
+fn prepare(user_id: &str, headers: &HeaderMap, payload: &OrderRequest) -> Result<Prepared, Rejected> {
+    let ValidOrder { max_slippage: max_slippage_decimal, client_order_id } =
+        validation::validate_order(payload, headers).map_err(Rejected::Invalid)?;
+
+    let order_id = uuid::Uuid::new_v4().to_string();
+    let fingerprint = idempotency::fingerprint(&create_order_data("", user_id, client_order_id.as_deref(), payload, max_slippage_decimal));
+    let mut order_data = create_order_data(&order_id, user_id, client_order_id.as_deref(), payload, max_slippage_decimal);
+
+    // The trace context is left out of the fingerprint: a retry starts a
+    // new trace but is still the same order.
+    let span = tracing::info_span!("order", order_id = %order_id, user_id, trace_id = tracing::field::Empty);
+    telemetry::record_trace_id(&span);
+    order_data["trace_context"] = serde_json::json!(telemetry::inject(&span));
+    Ok(Prepared { order_id, client_order_id, fingerprint, order_data, span })
+}
+
+/// The checks a new order must pass against Redis: an OCO partner must be
+/// the user's own order, and its pair must not be halted. Halts are cached
+/// per pair in `halts` across the orders of a batch.
+async fn check_intake(
+    conn: &mut RedisConn,
+    user_id: &str,
+    payload: &OrderRequest,
+    halts: &mut HashMap<(String, String), Option<serde_json::Value>>,
+) -> Result<(), Rejected> {
+    if let Some(oco_order_id) = &payload.oco_order_id {
+        let owner: Option<String> = conn.get(owner_key(oco_order_id)).await.map_err(|_| Rejected::Unavailable)?;
+        if owner.as_deref() != Some(user_id) {
+            return Err(invalid(Violation::new("oco_order_id", "oco_order_not_found", "oco_order_id must be one of your own orders")));
+        }
+    }
+
+    let pair = (payload.token_in.clone(), payload.token_out.clone());
+    let halt = match halts.get(&pair) {
+        Some(halt) => halt.clone(),
+        None => {
+            let halt = halts::intake_halt(conn, &pair.0, &pair.1).await.map_err(|_| Rejected::Unavailable)?;
+            halts.insert(pair, halt.clone());
+            halt
+        }
+    };
+    match halt {
+        Some(halt) => Err(Rejected::Halted(halt)),
+        None => Ok(()),
+    }
+}
+
+fn admit(admitted: Result<(), Throttled>, user_id: &str, ip: Option<IpAddr>) -> Result<(), Rejected> {
+    admitted.map_err(|throttled| {
+        tracing::info!(user_id, ip = ?ip, limit = throttled.limit.as_str(), "order throttled");
+        Rejected::Throttled(throttled)
+    })
+}
+
+fn accepted(submission: Submission, client_order_id: &str) -> Result<Accepted, Rejected> {
+    let (order_id, replayed) = match submission {
+        Submission::Published { order_id } => (order_id, false),
+        Submission::Replayed { order_id } => (order_id, true),
+        Submission::Conflict => return Err(Rejected::IdempotencyConflict { client_order_id: client_order_id.to_string() }),
+    };
+    Ok(Accepted { order_id, client_order_id: Some(client_order_id.to_string()), replayed })
+}
+
+fn batch_outcome(result: Result<Accepted, Rejected>) -> BatchOutcome {
+    match result {
+        Ok(accepted) => BatchOutcome::Accepted(accepted),
+        Err(rejected) => BatchOutcome::Rejected(rejected),
+    }
+}
+
+fn finish(outcomes: Vec<Option<BatchOutcome>>) -> Vec<BatchOutcome> {
+    outcomes.into_iter().map(|outcome| outcome.unwrap_or(BatchOutcome::NotSubmitted)).collect()
+}
+
+fn invalid(violation: Violation) -> Rejected {
+    Rejected::Invalid(ValidationErrors(vec![violation]))
+}
+
+pub async fn is_order_owner(redis: &RedisPool, order_id: &str, user_id: &str) -> Result<bool, Unavailable> {
+    let mut conn = redis.get().await?;
+    let owner: Option<String> = conn.get(owner_key(order_id)).await.map_err(|_| Unavailable)?;
+    Ok(owner.as_deref() == Some(user_id))
+}
+
+/// Keeps the update as its order's latest status, and lists top-level
+/// orders under their user, oldest first, for as long as order ownership
+/// is kept.
+pub async fn record_status(conn: &mut RedisConn, event: &StatusEvent) -> Result<(), redis::RedisError> {
+    let status_key = format!("order:{}:status", event.order_id);
+    let mut pipe = redis::pipe();
+    pipe.atomic()
+        .hset_multiple(&status_key, &[("event_id", event.id.as_str()), ("data", event.data.as_str())]).ignore()
+        .expire(&status_key, ORDER_OWNER_TTL_SECS as i64).ignore();
+
+    if let (Some(user_id), None) = (&event.user_id, &event.parent_order_id) {
+        let orders_key = user_orders_key(user_id);
+        let received_ms = event.id.split_once('-').and_then(|(ms, _)| ms.parse::<u64>().ok()).unwrap_or_else(now_ms);
+        let expired_ms = now_ms().saturating_sub(ORDER_OWNER_TTL_SECS * 1000);
+        pipe.cmd("ZADD").arg(&orders_key).arg("NX").arg(received_ms).arg(&event.order_id).ignore()
+            .cmd("ZREMRANGEBYSCORE").arg(&orders_key).arg("-inf").arg(format!("({}", expired_ms)).ignore()
+            .expire(&orders_key, ORDER_OWNER_TTL_SECS as i64).ignore();
+    }
+
+    pipe.query_async(conn).await
+}
+
+/// `None` until the router has reported the order.
+pub async fn status(conn: &mut RedisConn, order_id: &str) -> Result<Option<OrderStatus>, redis::RedisError> {
+    let (event_id, data): (Option<String>, Option<String>) = conn
+        .hget(format!("order:{}:status", order_id), &["event_id", "data"])
+        .await?;
+    Ok(event_id.zip(data).map(|(event_id, data)| OrderStatus { event_id, data }))
+}
+
+/// The user's orders, newest first, with their latest status when known.
+pub async fn list(
+    conn: &mut RedisConn,
+    user_id: &str,
+    offset: usize,
+    limit: usize,
+) -> Result<Vec<(String, Option<OrderStatus>)>, redis::RedisError> {
+    let order_ids: Vec<String> = conn
+        .zrevrange(user_orders_key(user_id), offset as isize, (offset + limit) as isize - 1)
+        .await?;
+
+    let mut orders = Vec::with_capacity(order_ids.len());
+    for order_id in order_ids {
+        let status = status(conn, &order_id).await?;
+        orders.push((order_id, status));
+    }
+    Ok(orders)
+}
+
+/// Asks the router to cancel the order on `order_cancels` and waits for its
+/// reply on `cancel_reply:{cancel_id}`. Blocks `conn` while waiting, so it
+/// should be a dedicated connection.
+pub async fn cancel(conn: &mut RedisConn, order_id: &str, user_id: &str) -> Result<Cancellation, redis::RedisError> {
+    let cancel_id = uuid::Uuid::new_v4().to_string();
+    let cancel_data = serde_json::json!({ "cancel_id": cancel_id, "order_id": order_id, "user_id": user_id });
+    let _: String = conn
+        .xadd("order_cancels", "*", &[("cancel_data", cancel_data.to_string().as_str())])
+        .await?;
+
+    let reply: Option<(String, String)> = redis::cmd("BLPOP")
+        .arg(format!("cancel_reply:{}", cancel_id))
+        .arg(cancel_timeout_ms() as f64 / 1000.0)
+        .query_async(conn)
+        .await?;
+
+    let Some((_, reply)) = reply else {
+        return Ok(Cancellation::TimedOut);
+    };
+    let cancelled = serde_json::from_str::<serde_json::Value>(&reply)
+        .ok()
+        .and_then(|reply| reply["cancelled"].as_bool())
+        .unwrap_or(false);
+    Ok(if cancelled { Cancellation::Cancelled } else { Cancellation::NotCancellable })
+}
+
+fn create_order_data(order_id: &str, user_id: &str, client_order_id: Option<&str>, payload: &OrderRequest, max_slippage: f64) -> serde_json::Value {
+    serde_json::json!({
+        "order_id": order_id,
+        "client_order_id": client_order_id,
+        "user_id": user_id,
+        "token_in": payload.token_in,
+        "token_out": payload.token_out,
+        "amount": payload.amount,
+        "order_type": payload.order_type,
+        "max_slippage": max_slippage * 100.0,
+        "commitment": payload.commitment,
+        "priority_fee": payload.priority_fee,
+        "compute_units": payload.compute_units,
+        "protection": payload.protection,
+        "quote_id": payload.quote_id,
+        "duration_secs": payload.duration_secs,
+        "slices": payload.slices,
+        "clip_size": payload.clip_size,
+        "trigger_price": payload.trigger_price,
+        "oco_order_id": payload.oco_order_id
+    })
+}
+
+async fn publish(conn: &mut RedisConn, order_id: &str, user_id: &str, order_data: &serde_json::Value) -> Result<(), redis::RedisError> {
+    redis::pipe()
+        .atomic()
+        .set_ex(owner_key(order_id), user_id, ORDER_OWNER_TTL_SECS).ignore()
+        .xadd("order_stream", "*", &[("order_data", order_data.to_string().as_str())]).ignore()
+        .query_async(conn)
+        .await
+}
+
+fn owner_key(order_id: &str) -> String {
+    format!("order:{}:owner", order_id)
+}
+
+fn user_orders_key(user_id: &str) -> String {
+    format!("user:{}:orders", user_id)
+}
+
+fn cancel_timeout_ms() -> u64 {
+    std::env::var("CANCEL_TIMEOUT_MS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_CANCEL_TIMEOUT_MS)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+impl Rejected {
+    /// A short name for the reason, used as a metric label.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rejected::Invalid(_) => "invalid",
+            Rejected::IdempotencyConflict { .. } => "idempotency_conflict",
+            Rejected::Halted(_) => "halted",
+            Rejected::Throttled(_) => "throttled",
+            Rejected::Unavailable => "unavailable",
+        }
+    }
+
+    /// The error body the HTTP API answers with; gRPC sends it as the
+    /// status message and batches report it per order.
+    pub fn body(&self) -> serde_json::Value {
+        match self {
+            Rejected::Invalid(errors) => serde_json::json!({ "error": "validation_failed", "violations": errors.0 }),
+            Rejected::IdempotencyConflict { client_order_id } => {
+                serde_json::json!({ "error": "idempotency_key_reused", "client_order_id": client_order_id })
+            }
+            Rejected::Halted(halt) => serde_json::json!({ "error": "trading_halted", "halt": halt }),
+            Rejected::Throttled(throttled) => serde_json::json!({
+                "error": "rate_limited",
+                "limit": throttled.limit.as_str(),
+                "retry_after_ms": throttled.retry_after.as_millis() as u64,
+            }),
+            Rejected::Unavailable => serde_json::json!({ "error": "unavailable" }),
+        }
+    }
+}
+
+impl IntoResponse for Rejected {
+    fn into_response(self) -> Response {
+        match self {
+            Rejected::Invalid(errors) => errors.into_response(),
+            Rejected::IdempotencyConflict { .. } => (StatusCode::UNPROCESSABLE_ENTITY, Json(self.body())).into_response(),
+            Rejected::Halted(_) => (StatusCode::SERVICE_UNAVAILABLE, Json(self.body())).into_response(),
+            Rejected::Throttled(throttled) => throttled.into_response(),
+            Rejected::Unavailable => Unavailable.into_response(),
+        }
+    }
+}
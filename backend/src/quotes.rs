@@ -0,0 +1,41 @@
+use crate::models::QuoteRequest;
+use crate::redis_pool::RedisConn;
+use redis::AsyncCommands;
+
+const DEFAULT_TIMEOUT_MS: u64 = 5000;
+
+/// Asks the router to price a trade and waits for its reply on
+/// `quote_reply:{quote_id}`. Returns `None` if no reply arrives in time.
+/// Blocks `conn` while waiting, so it should be a dedicated connection.
+pub async fn request_quote(
+    conn: &mut RedisConn,
+    quote_id: &str,
+    user_id: &str,
+    request: &QuoteRequest,
+) -> Result<Option<serde_json::Value>, redis::RedisError> {
+    let quote_data = serde_json::json!({
+        "quote_id": quote_id,
+        "user_id": user_id,
+        "token_in": request.token_in,
+        "token_out": request.token_out,
+        "amount": request.amount,
+    });
+    let _: String = conn
+        .xadd("quote_requests", "*", &[("quote_data", quote_data.to_string().as_str())])
+        .await?;
+
+    let reply: Option<(String, String)> = redis::cmd("BLPOP")
+        .arg(format!("quote_reply:{}", quote_id))
+        .arg(timeout_ms() as f64 / 1000.0)
+        .query_async(conn)
+        .await?;
+
+    Ok(reply.and_then(|(_, data)| serde_json::from_str(&data).ok()))
+}
+
+fn timeout_ms() -> u64 {
+    std::env::var("QUOTE_TIMEOUT_MS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_TIMEOUT_MS)
+}
@@ -0,0 +1,450 @@
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    pub orders_per_sec: f64,
+    pub order_burst: f64,
+    pub ip_orders_per_sec: f64,
+    pub ip_order_burst: f64,
+    pub max_open_orders: usize,
+    pub open_order_ttl: Duration,
+    pub notional_per_min: f64,
+    pub reference_prices: HashMap<String, f64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Limit {
+    OrdersPerSecond,
+    IpOrdersPerSecond,
+    OpenOrders,
+    NotionalPerMinute,
+}
+
+#[derive(Debug)]
+pub struct Throttled {
+    pub limit: Limit,
+    pub retry_after: Duration,
+}
+
+struct TokenBucket {
+    capacity: f64,
+    refill_per_sec: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Default)]
+struct LimiterState {
+    key_orders: HashMap<String, TokenBucket>,
+    ip_orders: HashMap<IpAddr, TokenBucket>,
+    key_notional: HashMap<String, TokenBucket>,
+    /// Orders admitted here and not closed yet, with when they were
+    /// admitted, by API key.
+    open_orders: HashMap<String, HashMap<String, Instant>>,
+    /// The API key each open order was admitted under.
+    open_order_keys: HashMap<String, String>,
+}
+
+#[derive(Default)]
+struct Counters {
+    allowed: AtomicU64,
+    orders_per_second: AtomicU64,
+    ip_orders_per_second: AtomicU64,
+    open_orders: AtomicU64,
+    notional_per_minute: AtomicU64,
+}
+
+/// Order throttles kept in process memory. Rates, notional and open orders
+/// are per API key, identified by its prefix, with a separate order rate per
+/// client IP for HTTP callers. Open orders are tracked by order id, so only orders admitted
+/// here are released by their final status, and an order that never gets
+/// one stops counting after `open_order_ttl`.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    state: Mutex<LimiterState>,
+    counters: Counters,
+}
+
+impl RateLimitConfig {
+    pub fn from_env() -> Self {
+        let reference_prices = std::env::var("RATE_LIMIT_REFERENCE_PRICES")
+            .unwrap_or_else(|_| "SOL=220,USDC=1".to_string())
+            .split(',')
+            .filter_map(|pair| pair.split_once('='))
+            .filter_map(|(token, price)| Some((token.trim().to_string(), price.trim().parse().ok()?)))
+            .collect();
+
+        Self {
+            orders_per_sec: env_or("RATE_LIMIT_ORDERS_PER_SEC", 5.0),
+            order_burst: env_or("RATE_LIMIT_ORDER_BURST", 10.0),
+            ip_orders_per_sec: env_or("RATE_LIMIT_IP_ORDERS_PER_SEC", 20.0),
+            ip_order_burst: env_or("RATE_LIMIT_IP_ORDER_BURST", 40.0),
+            max_open_orders: env_or("RATE_LIMIT_MAX_OPEN_ORDERS", 50),
+            open_order_ttl: Duration::from_secs(env_or("RATE_LIMIT_OPEN_ORDER_TTL_SECS", 3600)),
+            notional_per_min: env_or("RATE_LIMIT_NOTIONAL_PER_MIN", 1_000_000.0),
+            reference_prices,
+        }
+    }
+}
+
+impl Limit {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Limit::OrdersPerSecond => "orders_per_second",
+            Limit::IpOrdersPerSecond => "ip_orders_per_second",
+            Limit::OpenOrders => "open_orders",
+            Limit::NotionalPerMinute => "notional_per_minute",
+        }
+    }
+}
+
+impl TokenBucket {
+    fn new(capacity: f64, refill_per_sec: f64) -> Self {
+        Self { capacity, refill_per_sec, tokens: capacity, updated: Instant::now() }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// Time until `cost` tokens are available, or zero if they are now.
+    fn wait_for(&self, cost: f64) -> Duration {
+        if self.tokens >= cost {
+            return Duration::ZERO;
+        }
+        if cost > self.capacity || self.refill_per_sec <= 0.0 {
+            return Duration::from_secs(60);
+        }
+        Duration::from_secs_f64((cost - self.tokens) / self.refill_per_sec)
+    }
+
+    fn is_full(&self) -> bool {
+        self.tokens >= self.capacity
+    }
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(LimiterState::default()),
+            counters: Counters::default(),
+        }
+    }
+
+    pub fn notional(&self, token_in: &str, amount: f64) -> f64 {
+        amount * self.config.reference_prices.get(token_in).copied().unwrap_or(1.0)
+    }
+
+    /// Admits an order only if every limit allows it. Nothing is consumed
+    /// when one of them throttles.
+    pub fn admit_order(&self, key: &str, ip: Option<IpAddr>, order_id: &str, notional: f64) -> Result<(), Throttled> {
+        self.admit_orders(key, ip, &[(order_id, notional)])
+    }
+
+    /// Admits a batch of orders, given as order id and notional, only if
+    /// every limit allows all of them. Orders without a client IP skip the
+    /// per-IP rate.
+    pub fn admit_orders(&self, key: &str, ip: Option<IpAddr>, orders: &[(&str, f64)]) -> Result<(), Throttled> {
+        let count = orders.len();
+        let notional: f64 = orders.iter().map(|(_, notional)| notional).sum();
+        let config = &self.config;
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let LimiterState { key_orders, ip_orders, key_notional, open_orders, open_order_keys } = &mut *state;
+
+        let key_bucket = key_orders
+            .entry(key.to_string())
+            .or_insert_with(|| TokenBucket::new(config.order_burst, config.orders_per_sec));
+        let mut ip_bucket = ip.map(|ip| {
+            ip_orders
+                .entry(ip)
+                .or_insert_with(|| TokenBucket::new(config.ip_order_burst, config.ip_orders_per_sec))
+        });
+        let notional_bucket = key_notional
+            .entry(key.to_string())
+            .or_insert_with(|| TokenBucket::new(config.notional_per_min, config.notional_per_min / 60.0));
+        let open = open_orders.entry(key.to_string()).or_default();
+        expire(open, open_order_keys, config.open_order_ttl, now);
+
+        key_bucket.refill(now);
+        if let Some(ip_bucket) = ip_bucket.as_mut() {
+            ip_bucket.refill(now);
+        }
+        notional_bucket.refill(now);
+
+        let throttled = [
+            (Limit::OrdersPerSecond, key_bucket.wait_for(count as f64)),
+            (Limit::IpOrdersPerSecond, ip_bucket.as_ref().map_or(Duration::ZERO, |bucket| bucket.wait_for(count as f64))),
+            (Limit::NotionalPerMinute, notional_bucket.wait_for(notional)),
+        ]
+        .into_iter()
+        .find(|(_, wait)| !wait.is_zero())
+        .or_else(|| (open.len() + count > config.max_open_orders).then_some((Limit::OpenOrders, Duration::from_secs(1))));
+
+        if let Some((limit, retry_after)) = throttled {
+            if open.is_empty() {
+                open_orders.remove(key);
+            }
+            self.counter(limit).fetch_add(1, Ordering::Relaxed);
+            return Err(Throttled { limit, retry_after });
+        }
+
+        key_bucket.tokens -= count as f64;
+        if let Some(ip_bucket) = ip_bucket {
+            ip_bucket.tokens -= count as f64;
+        }
+        notional_bucket.tokens -= notional;
+        for (order_id, _) in orders {
+            open.insert(order_id.to_string(), now);
+            open_order_keys.insert(order_id.to_string(), key.to_string());
+        }
+        self.counters.allowed.fetch_add(count as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Called when an order reaches a final status, or was admitted but not
+    /// published. Orders not admitted here are ignored.
+    pub fn order_closed(&self, order_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        let Some(key) = state.open_order_keys.remove(order_id) else {
+            return;
+        };
+        if let Some(open) = state.open_orders.get_mut(&key) {
+            open.remove(order_id);
+            if open.is_empty() {
+                state.open_orders.remove(&key);
+            }
+        }
+    }
+
+    /// Drops buckets that have refilled completely and open orders past
+    /// their TTL, so idle keys and IPs do not accumulate.
+    pub fn prune(&self) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let LimiterState { key_orders, ip_orders, key_notional, open_orders, open_order_keys } = &mut *state;
+        retain_active(key_orders, now);
+        retain_active(key_notional, now);
+        retain_active(ip_orders, now);
+        open_orders.retain(|_, open| {
+            expire(open, open_order_keys, self.config.open_order_ttl, now);
+            !open.is_empty()
+        });
+    }
+
+    pub fn stats(&self) -> serde_json::Value {
+        let open_orders = self.state.lock().unwrap().open_order_keys.len();
+        serde_json::json!({
+            "allowed": self.counters.allowed.load(Ordering::Relaxed),
+            "throttled": {
+                "orders_per_second": self.counters.orders_per_second.load(Ordering::Relaxed),
+                "ip_orders_per_second": self.counters.ip_orders_per_second.load(Ordering::Relaxed),
+                "open_orders": self.counters.open_orders.load(Ordering::Relaxed),
+                "notional_per_minute": self.counters.notional_per_minute.load(Ordering::Relaxed),
+            },
+            "open_orders": open_orders,
+        })
+    }
+
+    fn counter(&self, limit: Limit) -> &AtomicU64 {
+        match limit {
+            Limit::OrdersPerSecond => &self.counters.orders_per_second,
+            Limit::IpOrdersPerSecond => &self.counters.ip_orders_per_second,
+            Limit::OpenOrders => &self.counters.open_orders,
+            Limit::NotionalPerMinute => &self.counters.notional_per_minute,
+        }
+    }
+}
+
+impl IntoResponse for Throttled {
+    fn into_response(self) -> Response {
+        let retry_after_secs = self.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+        let body = Json(serde_json::json!({
+            "error": "rate_limited",
+            "limit": self.limit.as_str(),
+            "retry_after_ms": self.retry_after.as_millis() as u64,
+        }));
+
+        let mut response = (StatusCode::TOO_MANY_REQUESTS, body).into_response();
+        response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry_after_secs));
+        response
+    }
+}
+
+fn retain_active<K>(buckets: &mut HashMap<K, TokenBucket>, now: Instant) {
+    buckets.retain(|_, bucket| {
+        bucket.refill(now);
+        !bucket.is_full()
+    });
+}
+
+fn expire(open: &mut HashMap<String, Instant>, open_order_keys: &mut HashMap<String, String>, ttl: Duration, now: Instant) {
+    open.retain(|order_id, admitted| {
+        let live = now.duration_since(*admitted) < ttl;
+        if !live {
+            open_order_keys.remove(order_id);
+        }
+        live
+    });
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const IP: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+    fn config() -> RateLimitConfig {
+        RateLimitConfig {
+            orders_per_sec: 5.0,
+            order_burst: 10.0,
+            ip_orders_per_sec: 1000.0,
+            ip_order_burst: 1000.0,
+            max_open_orders: 1000,
+            open_order_ttl: Duration::from_secs(3600),
+            notional_per_min: 1_000_000.0,
+            reference_prices: HashMap::from([("SOL".to_string(), 200.0)]),
+        }
+    }
+
+    fn admit_many(limiter: &RateLimiter, key: &str, count: usize) -> Result<(), Throttled> {
+        for n in 0..count {
+            limiter.admit_order(key, IP, &format!("{key}-{n}"), 1.0)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn bucket_refills_at_its_rate_up_to_capacity() {
+        let mut bucket = TokenBucket::new(10.0, 5.0);
+        let start = bucket.updated;
+        bucket.tokens = 0.0;
+
+        bucket.refill(start + Duration::from_millis(400));
+        assert!((bucket.tokens - 2.0).abs() < 1e-9);
+
+        bucket.refill(start + Duration::from_secs(60));
+        assert_eq!(bucket.tokens, 10.0);
+        assert!(bucket.is_full());
+    }
+
+    #[test]
+    fn wait_for_is_the_time_to_refill_the_shortfall() {
+        let mut bucket = TokenBucket::new(10.0, 5.0);
+        assert_eq!(bucket.wait_for(10.0), Duration::ZERO);
+
+        bucket.tokens = 0.5;
+        assert_eq!(bucket.wait_for(1.0), Duration::from_millis(100));
+        assert_eq!(bucket.wait_for(3.0), Duration::from_millis(500));
+        // A cost the bucket can never hold gets a fixed back-off.
+        assert_eq!(bucket.wait_for(11.0), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn burst_is_throttled_with_a_retry_after() {
+        let limiter = RateLimiter::new(config());
+        admit_many(&limiter, "ak_a", 10).unwrap();
+
+        let throttled = limiter.admit_order("ak_a", IP, "ak_a-10", 1.0).unwrap_err();
+        assert!(matches!(throttled.limit, Limit::OrdersPerSecond));
+        assert!(throttled.retry_after > Duration::ZERO && throttled.retry_after <= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn limits_are_per_api_key() {
+        let limiter = RateLimiter::new(config());
+        admit_many(&limiter, "ak_a", 10).unwrap();
+        assert!(limiter.admit_order("ak_a", IP, "ak_a-10", 1.0).is_err());
+        assert!(limiter.admit_order("ak_b", IP, "ak_b-0", 1.0).is_ok());
+    }
+
+    #[test]
+    fn client_ip_is_limited_across_keys() {
+        let limiter = RateLimiter::new(RateLimitConfig { ip_orders_per_sec: 1.0, ip_order_burst: 2.0, ..config() });
+        limiter.admit_order("ak_a", IP, "a", 1.0).unwrap();
+        limiter.admit_order("ak_b", IP, "b", 1.0).unwrap();
+
+        let throttled = limiter.admit_order("ak_c", IP, "c", 1.0).unwrap_err();
+        assert!(matches!(throttled.limit, Limit::IpOrdersPerSecond));
+        assert!(limiter.admit_order("ak_c", Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))), "c", 1.0).is_ok());
+    }
+
+    #[test]
+    fn orders_without_a_client_ip_skip_the_ip_limit() {
+        let limiter = RateLimiter::new(RateLimitConfig { ip_orders_per_sec: 1.0, ip_order_burst: 1.0, ..config() });
+        limiter.admit_order("ak_a", IP, "a", 1.0).unwrap();
+        assert!(limiter.admit_order("ak_b", IP, "b", 1.0).is_err());
+
+        limiter.admit_order("ak_b", None, "b", 1.0).unwrap();
+        limiter.admit_order("ak_c", None, "c", 1.0).unwrap();
+    }
+
+    #[test]
+    fn notional_is_priced_by_token_in() {
+        let limiter = RateLimiter::new(config());
+        assert_eq!(limiter.notional("SOL", 2.0), 400.0);
+        assert_eq!(limiter.notional("BONK", 2.0), 2.0);
+
+        let throttled = limiter.admit_order("ak_a", IP, "big", limiter.notional("SOL", 10_000.0)).unwrap_err();
+        assert!(matches!(throttled.limit, Limit::NotionalPerMinute));
+    }
+
+    #[test]
+    fn throttled_batches_consume_nothing() {
+        let limiter = RateLimiter::new(config());
+        let batch: Vec<(String, f64)> = (0..11).map(|n| (n.to_string(), 1.0)).collect();
+        let batch: Vec<(&str, f64)> = batch.iter().map(|(id, notional)| (id.as_str(), *notional)).collect();
+
+        assert!(limiter.admit_orders("ak_a", IP, &batch).is_err());
+        assert!(limiter.admit_orders("ak_a", IP, &batch[..10]).is_ok());
+    }
+
+    #[test]
+    fn open_orders_are_released_by_id() {
+        let limiter = RateLimiter::new(RateLimitConfig { max_open_orders: 2, ..config() });
+        admit_many(&limiter, "ak_a", 2).unwrap();
+        let throttled = limiter.admit_order("ak_a", IP, "ak_a-2", 1.0).unwrap_err();
+        assert!(matches!(throttled.limit, Limit::OpenOrders));
+
+        // Orders admitted elsewhere, such as another key's, free nothing.
+        limiter.order_closed("unknown");
+        limiter.order_closed("ak_b-0");
+        assert!(limiter.admit_order("ak_a", IP, "ak_a-2", 1.0).is_err());
+
+        limiter.order_closed("ak_a-0");
+        limiter.order_closed("ak_a-0");
+        assert!(limiter.admit_order("ak_a", IP, "ak_a-2", 1.0).is_ok());
+        assert_eq!(limiter.stats()["open_orders"], 2);
+    }
+
+    #[test]
+    fn open_orders_expire_after_their_ttl() {
+        let limiter = RateLimiter::new(RateLimitConfig { max_open_orders: 1, open_order_ttl: Duration::from_millis(20), ..config() });
+        limiter.admit_order("ak_a", IP, "lost", 1.0).unwrap();
+        assert!(limiter.admit_order("ak_a", IP, "next", 1.0).is_err());
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(limiter.admit_order("ak_a", IP, "next", 1.0).is_ok());
+
+        std::thread::sleep(Duration::from_millis(30));
+        limiter.prune();
+        assert_eq!(limiter.stats()["open_orders"], 0);
+    }
+}
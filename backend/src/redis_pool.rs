@@ -0,0 +1,191 @@
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use redis::aio::{ConnectionLike, MultiplexedConnection};
+use redis::{Client, Cmd, Pipeline, RedisError, RedisFuture, RedisResult, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+const DEFAULT_COMMAND_TIMEOUT_MS: u64 = 2000;
+
+/// Redis access shared by every request. Commands go over one multiplexed
+/// connection, opened on first use and reopened once it drops. Failed
+/// attempts back off exponentially, and until the next attempt is due
+/// callers get [`Unavailable`] at once instead of queuing on a dead server.
+#[derive(Clone)]
+pub struct RedisPool {
+    client: Client,
+    command_timeout: Duration,
+    state: Arc<Mutex<PoolState>>,
+}
+
+#[derive(Default)]
+struct PoolState {
+    conn: Option<MultiplexedConnection>,
+    /// Bumped for every new shared connection, so an error seen on an old
+    /// one does not discard its replacement.
+    generation: u64,
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+/// A connection handed out by [`RedisPool`]. A shared connection's commands
+/// time out after `REDIS_COMMAND_TIMEOUT_MS`, and one that drops or times
+/// out is discarded so the next caller reconnects.
+pub struct RedisConn {
+    conn: MultiplexedConnection,
+    pool: RedisPool,
+    /// The pool generation of a shared connection, `None` for a dedicated one.
+    generation: Option<u64>,
+}
+
+/// Redis could not be reached. Answered with `503`.
+#[derive(Debug)]
+pub struct Unavailable;
+
+impl RedisPool {
+    pub fn new(client: Client) -> Self {
+        let command_timeout_ms = std::env::var("REDIS_COMMAND_TIMEOUT_MS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_COMMAND_TIMEOUT_MS);
+
+        Self {
+            client,
+            command_timeout: Duration::from_millis(command_timeout_ms),
+            state: Arc::new(Mutex::new(PoolState::default())),
+        }
+    }
+
+    /// The shared connection, for commands that answer right away.
+    pub async fn get(&self) -> Result<RedisConn, Unavailable> {
+        // Held while connecting, so a recovering server sees one attempt
+        // rather than one per waiting request.
+        let mut state = self.state.lock().await;
+        if let Some(conn) = &state.conn {
+            return Ok(RedisConn { conn: conn.clone(), pool: self.clone(), generation: Some(state.generation) });
+        }
+
+        backing_off(&state)?;
+        let conn = self.connect(&mut state).await?;
+        state.conn = Some(conn.clone());
+        state.generation += 1;
+        Ok(RedisConn { conn, pool: self.clone(), generation: Some(state.generation) })
+    }
+
+    /// A connection of its own, for blocking commands such as `BLPOP` that
+    /// would hold up every other request on the shared one. Its commands
+    /// do not time out.
+    pub async fn dedicated(&self) -> Result<RedisConn, Unavailable> {
+        backing_off(&*self.state.lock().await)?;
+        let connected = tokio::time::timeout(CONNECT_TIMEOUT, self.client.get_multiplexed_tokio_connection()).await;
+        let conn = self.record(&mut *self.state.lock().await, connected)?;
+        Ok(RedisConn { conn, pool: self.clone(), generation: None })
+    }
+
+    async fn connect(&self, state: &mut PoolState) -> Result<MultiplexedConnection, Unavailable> {
+        let connected = tokio::time::timeout(CONNECT_TIMEOUT, self.client.get_multiplexed_tokio_connection()).await;
+        self.record(state, connected)
+    }
+
+    fn record(
+        &self,
+        state: &mut PoolState,
+        connected: Result<RedisResult<MultiplexedConnection>, tokio::time::error::Elapsed>,
+    ) -> Result<MultiplexedConnection, Unavailable> {
+        let error = match connected {
+            Ok(Ok(conn)) => {
+                if state.failures > 0 {
+                    tracing::info!(failures = state.failures, "reconnected to redis");
+                }
+                state.failures = 0;
+                state.retry_at = None;
+                return Ok(conn);
+            }
+            Ok(Err(e)) => e.to_string(),
+            Err(_) => "connection timed out".to_string(),
+        };
+
+        let backoff = MIN_BACKOFF.saturating_mul(1 << state.failures.min(16)).min(MAX_BACKOFF);
+        state.failures += 1;
+        state.retry_at = Some(Instant::now() + backoff);
+        tracing::warn!(error = %error, ?backoff, "failed to connect to redis");
+        Err(Unavailable)
+    }
+
+    async fn discard(&self, generation: u64, error: &RedisError) {
+        let mut state = self.state.lock().await;
+        if state.generation == generation && state.conn.take().is_some() {
+            tracing::warn!(error = %error, "redis connection lost");
+        }
+    }
+}
+
+impl RedisConn {
+    async fn checked<T>(&self, result: RedisResult<T>) -> RedisResult<T> {
+        if let (Some(generation), Err(e)) = (self.generation, &result) {
+            if e.is_io_error() || e.is_connection_dropped() || e.is_timeout() {
+                self.pool.discard(generation, e).await;
+            }
+        }
+        result
+    }
+
+    fn limit(&self) -> Option<Duration> {
+        self.generation.map(|_| self.pool.command_timeout)
+    }
+}
+
+impl ConnectionLike for RedisConn {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            let limit = self.limit();
+            let result = within(limit, self.conn.req_packed_command(cmd)).await;
+            self.checked(result).await
+        })
+    }
+
+    fn req_packed_commands<'a>(&'a mut self, cmd: &'a Pipeline, offset: usize, count: usize) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            let limit = self.limit();
+            let result = within(limit, self.conn.req_packed_commands(cmd, offset, count)).await;
+            self.checked(result).await
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.conn.get_db()
+    }
+}
+
+impl IntoResponse for Unavailable {
+    fn into_response(self) -> Response {
+        let body = Json(serde_json::json!({ "error": "redis_unavailable" }));
+        let mut response = (StatusCode::SERVICE_UNAVAILABLE, body).into_response();
+        response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from_static("1"));
+        response
+    }
+}
+
+fn backing_off(state: &PoolState) -> Result<(), Unavailable> {
+    match state.retry_at {
+        Some(retry_at) if Instant::now() < retry_at => Err(Unavailable),
+        _ => Ok(()),
+    }
+}
+
+async fn within<T>(limit: Option<Duration>, command: RedisFuture<'_, T>) -> RedisResult<T> {
+    let Some(limit) = limit else {
+        return command.await;
+    };
+    tokio::time::timeout(limit, command).await.unwrap_or_else(|_| {
+        Err(RedisError::from(std::io::Error::new(std::io::ErrorKind::TimedOut, "redis command timed out")))
+    })
+}
@@ -0,0 +1,472 @@
+use crate::idempotency;
+use crate::models::{BatchOrderRequest, HaltRequest, OrderRequest, PriorityFeePolicy, QuoteRequest, SeedWalletRequest, WebhookRequest};
+use axum::{
+    extract::rejection::JsonRejection,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use std::sync::OnceLock;
+
+const ORDER_TYPES: &[&str] = &["market", "twap", "iceberg", "stop_loss", "take_profit"];
+const TRIGGER_ORDER_TYPES: &[&str] = &["stop_loss", "take_profit"];
+const COMMITMENTS: &[&str] = &["processed", "confirmed", "finalized"];
+const PROTECTIONS: &[&str] = &["public", "protected"];
+const HALT_SCOPES: &[&str] = &["global", "pair", "venue"];
+const HALT_MODES: &[&str] = &["reject", "pause"];
+const ORDER_STATUSES: &[&str] = &[
+    "pending", "routing", "building", "submitted", "confirmed", "failed", "rejected",
+    "working", "partially_filled", "armed", "triggered", "cancelled",
+];
+const MAX_COMPUTE_UNITS: u32 = 1_400_000;
+const MAX_TWAP_DURATION_SECS: u64 = 86_400;
+const MAX_CHILD_ORDERS: u32 = 100;
+const DEFAULT_BATCH_MAX_ORDERS: usize = 500;
+
+#[derive(Debug, Serialize)]
+pub struct Violation {
+    pub field: &'static str,
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// A rejected request body, listing every problem found rather than the
+/// first one.
+#[derive(Debug)]
+pub struct ValidationErrors(pub Vec<Violation>);
+
+pub struct ValidOrder {
+    pub max_slippage: f64,
+    pub client_order_id: Option<String>,
+}
+
+impl Violation {
+    pub fn new(field: &'static str, code: &'static str, message: impl Into<String>) -> Self {
+        Self { field, code, message: message.into() }
+    }
+}
+
+pub fn validate_order(payload: &OrderRequest, headers: &HeaderMap) -> Result<ValidOrder, ValidationErrors> {
+    let mut violations = Vec::new();
+    check_trade(&payload.token_in, &payload.token_out, payload.amount, &mut violations);
+
+    if !ORDER_TYPES.contains(&payload.order_type.as_str()) {
+        violations.push(one_of("order_type", "unsupported_order_type", ORDER_TYPES));
+    }
+    check_order_type(payload, &mut violations);
+
+    let max_slippage = payload.max_slippage.unwrap_or(0.05);
+    if !(max_slippage > 0.01 && max_slippage < 0.5) {
+        violations.push(Violation::new("max_slippage", "slippage_out_of_range", "max_slippage must be between 0.01 and 0.5 (exclusive)"));
+    }
+
+    if payload.commitment.as_deref().is_some_and(|value| !COMMITMENTS.contains(&value)) {
+        violations.push(one_of("commitment", "invalid_commitment", COMMITMENTS));
+    }
+    if payload.protection.as_deref().is_some_and(|value| !PROTECTIONS.contains(&value)) {
+        violations.push(one_of("protection", "invalid_protection", PROTECTIONS));
+    }
+
+    if payload.compute_units.is_some_and(|units| units == 0 || units > MAX_COMPUTE_UNITS) {
+        violations.push(Violation::new("compute_units", "compute_units_out_of_range", format!(
+            "compute_units must be between 1 and {}", MAX_COMPUTE_UNITS
+        )));
+    }
+    if let Some(PriorityFeePolicy::Percentile { percentile, .. }) = &payload.priority_fee {
+        if !(0.0..=100.0).contains(percentile) {
+            violations.push(Violation::new("priority_fee", "percentile_out_of_range", "percentile must be between 0 and 100"));
+        }
+    }
+
+    if payload.quote_id.as_deref().is_some_and(|id| uuid::Uuid::parse_str(id).is_err()) {
+        violations.push(Violation::new("quote_id", "invalid_quote_id", "quote_id must be an id returned by POST /api/quotes"));
+    }
+
+    let client_order_id = match idempotency::client_order_id(headers, payload.client_order_id.as_deref()) {
+        Ok(key) => key,
+        Err(violation) => {
+            violations.push(violation);
+            None
+        }
+    };
+
+    if !violations.is_empty() {
+        return Err(ValidationErrors(violations));
+    }
+    Ok(ValidOrder { max_slippage, client_order_id })
+}
+
+/// Checks the batch as a whole; each order is validated on its own.
+pub fn validate_batch(payload: &BatchOrderRequest) -> Result<(), ValidationErrors> {
+    let max_orders = batch_max_orders();
+    if payload.orders.is_empty() || payload.orders.len() > max_orders {
+        let violation = Violation::new("orders", "invalid_batch_size", format!("orders must hold between 1 and {} orders", max_orders));
+        return Err(ValidationErrors(vec![violation]));
+    }
+    Ok(())
+}
+
+pub fn validate_quote(payload: &QuoteRequest) -> Result<(), ValidationErrors> {
+    let mut violations = Vec::new();
+    check_trade(&payload.token_in, &payload.token_out, payload.amount, &mut violations);
+
+    if !violations.is_empty() {
+        return Err(ValidationErrors(violations));
+    }
+    Ok(())
+}
+
+/// Resolves the `trading:halts` field a halt is stored under. Pairs are
+/// written `BASE/QUOTE` and stored with their tokens sorted.
+pub fn halt_field(scope: &str, target: Option<&str>) -> Result<String, ValidationErrors> {
+    let target = target.map(str::trim).filter(|target| !target.is_empty());
+    let field = match (scope, target) {
+        ("global", None) => Some("global".to_string()),
+        ("pair", Some(pair)) => pair
+            .split_once('/')
+            .filter(|(a, b)| !a.is_empty() && !b.is_empty() && a != b)
+            .map(|(a, b)| halt_pair_field(a, b)),
+        ("venue", Some(venue)) => Some(format!("venue:{}", venue)),
+        _ => None,
+    };
+
+    field.ok_or_else(|| {
+        let violation = if !HALT_SCOPES.contains(&scope) {
+            one_of("scope", "invalid_halt_scope", HALT_SCOPES)
+        } else {
+            Violation::new("target", "invalid_halt_target", "global takes no target, pair takes BASE/QUOTE, venue takes a venue name")
+        };
+        ValidationErrors(vec![violation])
+    })
+}
+
+pub fn halt_pair_field(token_a: &str, token_b: &str) -> String {
+    let (first, second) = if token_a <= token_b { (token_a, token_b) } else { (token_b, token_a) };
+    format!("pair:{}/{}", first, second)
+}
+
+pub fn validate_halt(payload: &HaltRequest) -> Result<String, ValidationErrors> {
+    let field = halt_field(&payload.scope, payload.target.as_deref());
+    if payload.mode.as_deref().is_some_and(|mode| !HALT_MODES.contains(&mode)) {
+        let mut violations = field.err().map(|errors| errors.0).unwrap_or_default();
+        violations.push(one_of("mode", "invalid_halt_mode", HALT_MODES));
+        return Err(ValidationErrors(violations));
+    }
+    field
+}
+
+/// Seeded balances must be for supported tokens and non-negative.
+pub fn validate_wallet_seed(payload: &SeedWalletRequest) -> Result<(), ValidationErrors> {
+    let mut violations = Vec::new();
+
+    let mut tokens: Vec<_> = payload.balances.iter().collect();
+    tokens.sort_by(|a, b| a.0.cmp(b.0));
+    for (token, amount) in tokens {
+        if !supported_tokens().contains(token) {
+            violations.push(Violation::new("balances", "unsupported_token", format!(
+                "{} is not supported, expected one of: {}", token, supported_tokens().join(", ")
+            )));
+        } else if !amount.is_finite() || *amount < 0.0 {
+            violations.push(Violation::new("balances", "invalid_balance", format!(
+                "balance of {} must be a number of at least 0", token
+            )));
+        }
+    }
+
+    if !violations.is_empty() {
+        return Err(ValidationErrors(violations));
+    }
+    Ok(())
+}
+
+pub fn validate_webhook(payload: &WebhookRequest) -> Result<(), ValidationErrors> {
+    let mut violations = Vec::new();
+
+    let url = reqwest::Url::parse(payload.url.trim());
+    if !url.is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.host().is_some()) {
+        violations.push(Violation::new("url", "invalid_webhook_url", "url must be an absolute http or https URL"));
+    }
+    if let Some(statuses) = &payload.statuses {
+        if statuses.is_empty() || statuses.iter().any(|status| !ORDER_STATUSES.contains(&status.as_str())) {
+            violations.push(Violation::new("statuses", "invalid_webhook_status", format!(
+                "statuses must list at least one of: {}", ORDER_STATUSES.join(", ")
+            )));
+        }
+    }
+
+    if !violations.is_empty() {
+        return Err(ValidationErrors(violations));
+    }
+    Ok(())
+}
+
+fn check_trade(token_in: &str, token_out: &str, amount: f64, violations: &mut Vec<Violation>) {
+    if !amount.is_finite() || amount <= 0.0 {
+        violations.push(Violation::new("amount", "amount_not_positive", "amount must be a number greater than 0"));
+    }
+
+    for (field, token) in [("token_in", token_in), ("token_out", token_out)] {
+        if token.is_empty() {
+            violations.push(Violation::new(field, "token_required", format!("{} is required", field)));
+        } else if !supported_tokens().iter().any(|supported| supported == token) {
+            violations.push(Violation::new(field, "unsupported_token", format!(
+                "{} is not supported, expected one of: {}", token, supported_tokens().join(", ")
+            )));
+        }
+    }
+    if !token_in.is_empty() && token_in == token_out {
+        violations.push(Violation::new("token_out", "same_token_pair", "token_out must differ from token_in"));
+    }
+}
+
+/// TWAP orders need `duration_secs` and `slices`, icebergs a `clip_size`
+/// smaller than the order. Either may split into at most `MAX_CHILD_ORDERS`
+/// children, and neither can redeem a quote priced for the full amount.
+/// Stop-loss and take-profit orders need a `trigger_price` and may name an
+/// OCO partner; they execute long after any quote has expired.
+fn check_order_type(payload: &OrderRequest, violations: &mut Vec<Violation>) {
+    let order_type = payload.order_type.as_str();
+    let twap = order_type == "twap";
+    let iceberg = order_type == "iceberg";
+
+    if twap && !payload.duration_secs.is_some_and(|secs| (1..=MAX_TWAP_DURATION_SECS).contains(&secs)) {
+        violations.push(Violation::new("duration_secs", "duration_out_of_range", format!(
+            "twap orders need duration_secs between 1 and {}", MAX_TWAP_DURATION_SECS
+        )));
+    }
+    if twap && !payload.slices.is_some_and(|slices| (2..=MAX_CHILD_ORDERS).contains(&slices)) {
+        violations.push(Violation::new("slices", "slices_out_of_range", format!(
+            "twap orders need slices between 2 and {}", MAX_CHILD_ORDERS
+        )));
+    }
+    if iceberg
+        && !payload.clip_size.is_some_and(|clip| {
+            clip.is_finite() && clip > 0.0 && clip < payload.amount && payload.amount / clip <= MAX_CHILD_ORDERS as f64
+        })
+    {
+        violations.push(Violation::new("clip_size", "clip_size_out_of_range", format!(
+            "iceberg orders need a clip_size below amount and no smaller than amount / {}", MAX_CHILD_ORDERS
+        )));
+    }
+
+    let trigger = TRIGGER_ORDER_TYPES.contains(&order_type);
+    if trigger && !payload.trigger_price.is_some_and(|price| price.is_finite() && price > 0.0) {
+        violations.push(Violation::new("trigger_price", "invalid_trigger_price", format!(
+            "{} orders need a trigger_price above 0, in token_out per token_in", order_type
+        )));
+    }
+    if payload.oco_order_id.as_deref().is_some_and(|id| uuid::Uuid::parse_str(id).is_err()) {
+        violations.push(Violation::new("oco_order_id", "invalid_oco_order_id", "oco_order_id must be the order_id of a stop_loss or take_profit order"));
+    }
+
+    let unexpected = [
+        ("duration_secs", payload.duration_secs.is_some() && !twap),
+        ("slices", payload.slices.is_some() && !twap),
+        ("clip_size", payload.clip_size.is_some() && !iceberg),
+        ("trigger_price", payload.trigger_price.is_some() && !trigger),
+        ("oco_order_id", payload.oco_order_id.is_some() && !trigger),
+        ("quote_id", payload.quote_id.is_some() && (twap || iceberg || trigger)),
+    ];
+    for (field, present) in unexpected {
+        if present {
+            violations.push(Violation::new(field, "unexpected_field", format!(
+                "{} is not accepted on {} orders", field, order_type
+            )));
+        }
+    }
+}
+
+fn one_of(field: &'static str, code: &'static str, allowed: &[&str]) -> Violation {
+    Violation::new(field, code, format!("{} must be one of: {}", field, allowed.join(", ")))
+}
+
+fn batch_max_orders() -> usize {
+    std::env::var("BATCH_MAX_ORDERS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_BATCH_MAX_ORDERS)
+}
+
+pub fn supported_tokens() -> &'static Vec<String> {
+    static TOKENS: OnceLock<Vec<String>> = OnceLock::new();
+    TOKENS.get_or_init(|| {
+        std::env::var("SUPPORTED_TOKENS")
+            .unwrap_or_else(|_| "SOL,USDC".to_string())
+            .split(',')
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty())
+            .collect()
+    })
+}
+
+impl From<JsonRejection> for ValidationErrors {
+    fn from(rejection: JsonRejection) -> Self {
+        let code = match rejection {
+            JsonRejection::JsonDataError(_) => "invalid_field",
+            JsonRejection::JsonSyntaxError(_) => "malformed_json",
+            JsonRejection::MissingJsonContentType(_) => "unsupported_content_type",
+            _ => "invalid_body",
+        };
+        ValidationErrors(vec![Violation::new("body", code, rejection.body_text())])
+    }
+}
+
+impl IntoResponse for ValidationErrors {
+    fn into_response(self) -> Response {
+        let body = Json(serde_json::json!({
+            "error": "validation_failed",
+            "violations": self.0,
+        }));
+        (StatusCode::BAD_REQUEST, body).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn order(overrides: serde_json::Value) -> OrderRequest {
+        let mut body = json!({
+            "order_type": "market",
+            "token_in": "SOL",
+            "token_out": "USDC",
+            "amount": 1.0,
+            "max_slippage": 0.1,
+        });
+        body.as_object_mut().unwrap().extend(overrides.as_object().unwrap().clone());
+        serde_json::from_value(body).unwrap()
+    }
+
+    fn codes(result: Result<ValidOrder, ValidationErrors>) -> Vec<&'static str> {
+        result.err().map(|errors| errors.0.iter().map(|v| v.code).collect()).unwrap_or_default()
+    }
+
+    #[test]
+    fn accepts_a_plain_market_order() {
+        let valid = validate_order(&order(json!({})), &HeaderMap::new()).unwrap();
+        assert_eq!(valid.max_slippage, 0.1);
+        assert_eq!(valid.client_order_id, None);
+    }
+
+    #[test]
+    fn reports_every_violation_at_once() {
+        let payload = order(json!({"amount": -1.0, "token_out": "SOL", "max_slippage": 0.9, "order_type": "limit"}));
+        assert_eq!(
+            codes(validate_order(&payload, &HeaderMap::new())),
+            ["amount_not_positive", "same_token_pair", "unsupported_order_type", "slippage_out_of_range"]
+        );
+    }
+
+    #[test]
+    fn checks_tokens_against_the_supported_list() {
+        let payload = order(json!({"token_in": "", "token_out": "DOGE"}));
+        assert_eq!(codes(validate_order(&payload, &HeaderMap::new())), ["token_required", "unsupported_token"]);
+    }
+
+    #[test]
+    fn checks_optional_execution_parameters() {
+        let payload = order(json!({
+            "commitment": "rooted",
+            "protection": "private",
+            "compute_units": 0,
+            "priority_fee": {"type": "percentile", "percentile": 120.0},
+            "quote_id": "not-a-uuid",
+        }));
+        assert_eq!(
+            codes(validate_order(&payload, &HeaderMap::new())),
+            ["invalid_commitment", "invalid_protection", "compute_units_out_of_range", "percentile_out_of_range", "invalid_quote_id"]
+        );
+    }
+
+    #[test]
+    fn twap_and_iceberg_need_their_parameters() {
+        assert_eq!(
+            codes(validate_order(&order(json!({"order_type": "twap", "slices": 1})), &HeaderMap::new())),
+            ["duration_out_of_range", "slices_out_of_range"]
+        );
+        assert!(validate_order(&order(json!({"order_type": "twap", "duration_secs": 60, "slices": 4})), &HeaderMap::new()).is_ok());
+
+        assert_eq!(
+            codes(validate_order(&order(json!({"order_type": "iceberg", "clip_size": 0.001})), &HeaderMap::new())),
+            ["clip_size_out_of_range"]
+        );
+        assert!(validate_order(&order(json!({"order_type": "iceberg", "clip_size": 0.25})), &HeaderMap::new()).is_ok());
+    }
+
+    #[test]
+    fn trigger_orders_need_a_price_and_reject_foreign_fields() {
+        assert_eq!(
+            codes(validate_order(&order(json!({"order_type": "stop_loss"})), &HeaderMap::new())),
+            ["invalid_trigger_price"]
+        );
+        assert_eq!(
+            codes(validate_order(&order(json!({"trigger_price": 90.0, "slices": 3})), &HeaderMap::new())),
+            ["unexpected_field", "unexpected_field"]
+        );
+        assert_eq!(
+            codes(validate_order(&order(json!({"order_type": "take_profit", "trigger_price": 110.0, "oco_order_id": "x"})), &HeaderMap::new())),
+            ["invalid_oco_order_id"]
+        );
+    }
+
+    #[test]
+    fn batches_must_hold_at_least_one_order() {
+        let empty = BatchOrderRequest { orders: Vec::new(), all_or_nothing: false };
+        assert_eq!(validate_batch(&empty).unwrap_err().0[0].code, "invalid_batch_size");
+
+        let one = BatchOrderRequest { orders: vec![json!({})], all_or_nothing: false };
+        assert!(validate_batch(&one).is_ok());
+    }
+
+    #[test]
+    fn halt_fields_sort_pair_tokens() {
+        assert_eq!(halt_field("global", None).unwrap(), "global");
+        assert_eq!(halt_field("pair", Some("USDC/SOL")).unwrap(), "pair:SOL/USDC");
+        assert_eq!(halt_field("pair", Some(" SOL/USDC ")).unwrap(), "pair:SOL/USDC");
+        assert_eq!(halt_field("venue", Some("raydium")).unwrap(), "venue:raydium");
+
+        assert_eq!(halt_field("global", Some("SOL/USDC")).unwrap_err().0[0].code, "invalid_halt_target");
+        assert_eq!(halt_field("pair", Some("SOL/SOL")).unwrap_err().0[0].code, "invalid_halt_target");
+        assert_eq!(halt_field("market", None).unwrap_err().0[0].code, "invalid_halt_scope");
+    }
+
+    #[test]
+    fn halt_mode_is_checked_alongside_the_target() {
+        let payload = HaltRequest { scope: "pair".into(), target: None, mode: Some("drain".into()), reason: None };
+        let codes: Vec<_> = validate_halt(&payload).unwrap_err().0.iter().map(|v| v.code).collect();
+        assert_eq!(codes, ["invalid_halt_target", "invalid_halt_mode"]);
+    }
+
+    #[test]
+    fn wallet_seeds_need_supported_tokens_and_non_negative_balances() {
+        let seed = |balances: serde_json::Value| serde_json::from_value::<SeedWalletRequest>(json!({ "balances": balances })).unwrap();
+
+        assert!(validate_wallet_seed(&seed(json!({}))).is_ok());
+        assert!(validate_wallet_seed(&seed(json!({"SOL": 5.0, "USDC": 0.0}))).is_ok());
+
+        let codes: Vec<_> = validate_wallet_seed(&seed(json!({"DOGE": 1.0, "SOL": -1.0}))).unwrap_err().0.iter().map(|v| v.code).collect();
+        assert_eq!(codes, ["unsupported_token", "invalid_balance"]);
+    }
+
+    #[test]
+    fn webhooks_need_an_http_url_and_known_statuses() {
+        let webhook = |url: &str, statuses: Option<Vec<&str>>| WebhookRequest {
+            url: url.to_string(),
+            statuses: statuses.map(|s| s.into_iter().map(String::from).collect()),
+        };
+
+        assert_eq!(validate_webhook(&webhook("ftp://example.com/hook", None)).unwrap_err().0[0].code, "invalid_webhook_url");
+        assert_eq!(validate_webhook(&webhook("/relative", None)).unwrap_err().0[0].code, "invalid_webhook_url");
+        assert_eq!(
+            validate_webhook(&webhook("https://example.com/hook", Some(vec![]))).unwrap_err().0[0].code,
+            "invalid_webhook_status"
+        );
+        assert_eq!(
+            validate_webhook(&webhook("https://example.com/hook", Some(vec!["done"]))).unwrap_err().0[0].code,
+            "invalid_webhook_status"
+        );
+        assert!(validate_webhook(&webhook("https://example.com/hook", Some(vec!["confirmed", "failed"]))).is_ok());
+    }
+}
@@ -0,0 +1,63 @@
+use crate::redis_pool::RedisConn;
+use redis::{AsyncCommands, Script};
+use std::collections::HashMap;
+
+// Replaces the available balances unless an open order still holds funds,
+// which would otherwise be released on top of the new balances. The wallet
+// is rewritten rather than deleted, so the router never mistakes a seeded
+// wallet for a new one and refills it with its starting balances.
+const SEED_SCRIPT: &str = r#"
+local held = redis.call('HVALS', KEYS[2])
+for i = 1, #held do
+    if tonumber(held[i]) ~= 0 then
+        return 0
+    end
+end
+redis.call('DEL', KEYS[1])
+redis.call('HSET', KEYS[1], unpack(ARGV))
+return 1
+"#;
+
+pub enum Seeded {
+    Seeded(serde_json::Value),
+    FundsHeld(serde_json::Value),
+}
+
+/// Sets every token in `tokens` to its balance in `balances`, or to zero
+/// when it is left out.
+pub async fn seed(
+    conn: &mut RedisConn,
+    user_id: &str,
+    tokens: &[String],
+    balances: &HashMap<String, f64>,
+) -> Result<Seeded, redis::RedisError> {
+    let script = Script::new(SEED_SCRIPT);
+    let mut invocation = script.prepare_invoke();
+    invocation.key(wallet_key(user_id)).key(held_key(user_id));
+    for token in tokens {
+        invocation.arg(token).arg(balances.get(token).copied().unwrap_or(0.0));
+    }
+    let seeded: i32 = invocation.invoke_async(conn).await?;
+
+    let wallet = view(conn, user_id).await?;
+    Ok(if seeded == 1 { Seeded::Seeded(wallet) } else { Seeded::FundsHeld(wallet) })
+}
+
+pub async fn view(conn: &mut RedisConn, user_id: &str) -> Result<serde_json::Value, redis::RedisError> {
+    let balances: HashMap<String, f64> = conn.hgetall(wallet_key(user_id)).await?;
+    let held: HashMap<String, f64> = conn.hgetall(held_key(user_id)).await?;
+
+    Ok(serde_json::json!({
+        "user_id": user_id,
+        "balances": balances,
+        "held": held,
+    }))
+}
+
+fn wallet_key(user_id: &str) -> String {
+    format!("wallet:{}", user_id)
+}
+
+fn held_key(user_id: &str) -> String {
+    format!("wallet:{}:held", user_id)
+}
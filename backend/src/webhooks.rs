@@ -0,0 +1,675 @@
+use crate::fanout::StatusEvent;
+use common::pg_pool::PgPool;
+use crate::redis_pool::RedisPool;
+use common::streams::StreamReaders;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use redis::AsyncCommands;
+use serde::Serialize;
+use sha2::Sha256;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio_postgres::Row;
+
+const STATUS_STREAM: &str = "status_updates";
+const CURSOR_KEY: &str = "webhooks:cursor";
+const CLAIM_BATCH: i64 = 50;
+// Long enough that a claimed delivery is not claimed again while its
+// request is still in flight.
+const CLAIM_LEASE_SECS: f64 = 60.0;
+const MAX_ERROR_LEN: usize = 500;
+
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    pub max_attempts: i32,
+    pub retry_base: Duration,
+    pub retry_max: Duration,
+    pub timeout: Duration,
+    pub poll_interval: Duration,
+    /// Lets webhooks reach loopback, private and internal hosts, e.g. a
+    /// test receiver next to the backend.
+    pub allow_private: bool,
+}
+
+#[derive(Serialize)]
+pub struct Webhook {
+    pub webhook_id: String,
+    pub url: String,
+    pub statuses: Option<Vec<String>>,
+    pub created_at_ms: i64,
+    /// Only returned when the webhook is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Delivery {
+    pub delivery_id: i64,
+    pub event_id: String,
+    pub order_id: String,
+    pub status: String,
+    pub state: String,
+    pub attempts: i32,
+    pub last_response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub next_attempt_at_ms: Option<i64>,
+    pub delivered_at_ms: Option<i64>,
+    pub created_at_ms: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history: Option<Vec<Attempt>>,
+}
+
+#[derive(Serialize)]
+pub struct Attempt {
+    pub response_status: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+    pub attempted_at_ms: i64,
+}
+
+/// A due delivery, leased to this worker until it records the attempt.
+struct Claimed {
+    delivery_id: i64,
+    webhook_id: String,
+    event_id: String,
+    order_id: String,
+    status: String,
+    status_data: String,
+    attempts: i32,
+    url: String,
+    secret: String,
+}
+
+/// Webhook registrations and their delivery log in Postgres. Every
+/// `status_updates` entry for a user becomes one delivery per matching
+/// webhook, retried until it is acknowledged or runs out of attempts.
+pub struct WebhookStore {
+    pg: PgPool,
+}
+
+const DELIVERY_COLUMNS: &str = "delivery_id, event_id, order_id, status, state, attempts, last_response_status, last_error,
+    CASE WHEN state = 'pending' THEN (EXTRACT(EPOCH FROM next_attempt_at) * 1000)::BIGINT END,
+    (EXTRACT(EPOCH FROM delivered_at) * 1000)::BIGINT,
+    (EXTRACT(EPOCH FROM created_at) * 1000)::BIGINT";
+
+impl WebhookConfig {
+    pub fn from_env() -> Self {
+        Self {
+            max_attempts: env_or("WEBHOOK_MAX_ATTEMPTS", 6).max(1) as i32,
+            retry_base: Duration::from_millis(env_or("WEBHOOK_RETRY_BASE_MS", 2000)),
+            retry_max: Duration::from_millis(env_or("WEBHOOK_RETRY_MAX_MS", 300_000)),
+            timeout: Duration::from_millis(env_or("WEBHOOK_TIMEOUT_MS", 10_000)),
+            poll_interval: Duration::from_millis(env_or("WEBHOOK_POLL_MS", 500)),
+            allow_private: std::env::var("WEBHOOK_ALLOW_PRIVATE").is_ok_and(|value| matches!(value.as_str(), "true" | "1")),
+        }
+    }
+
+    /// Doubles after every failed attempt, up to `retry_max`.
+    fn backoff(&self, attempts: i32) -> Duration {
+        let exponent = attempts.saturating_sub(1).clamp(0, 20) as u32;
+        self.retry_base.saturating_mul(1 << exponent).min(self.retry_max)
+    }
+}
+
+impl WebhookStore {
+    pub async fn connect(database_url: &str) -> Result<Self, tokio_postgres::Error> {
+        Ok(Self { pg: PgPool::connect(database_url).await? })
+    }
+
+    pub async fn ping(&self) -> Result<(), tokio_postgres::Error> {
+        self.pg.ping().await
+    }
+
+    pub async fn create(&self, user_id: &str, url: &str, statuses: Option<&[String]>) -> Result<Webhook, tokio_postgres::Error> {
+        let webhook_id = uuid::Uuid::new_v4().to_string();
+        let secret = generate_secret();
+        let row = self.pg.get().await
+            .query_one(
+                "INSERT INTO webhooks (webhook_id, user_id, url, secret, statuses) VALUES ($1, $2, $3, $4, $5)
+                 RETURNING (EXTRACT(EPOCH FROM created_at) * 1000)::BIGINT",
+                &[&webhook_id, &user_id, &url, &secret, &statuses],
+            )
+            .await?;
+
+        Ok(Webhook {
+            webhook_id,
+            url: url.to_string(),
+            statuses: statuses.map(<[String]>::to_vec),
+            created_at_ms: row.get(0),
+            secret: Some(secret),
+        })
+    }
+
+    pub async fn list(&self, user_id: &str) -> Result<Vec<Webhook>, tokio_postgres::Error> {
+        let rows = self.pg.get().await
+            .query(
+                "SELECT webhook_id, url, statuses, (EXTRACT(EPOCH FROM created_at) * 1000)::BIGINT
+                 FROM webhooks WHERE user_id = $1 ORDER BY created_at",
+                &[&user_id],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| Webhook {
+                webhook_id: row.get(0),
+                url: row.get(1),
+                statuses: row.get(2),
+                created_at_ms: row.get(3),
+                secret: None,
+            })
+            .collect())
+    }
+
+    /// Returns `false` when the user has no such webhook.
+    pub async fn delete(&self, user_id: &str, webhook_id: &str) -> Result<bool, tokio_postgres::Error> {
+        let deleted = self.pg.get().await
+            .execute("DELETE FROM webhooks WHERE webhook_id = $1 AND user_id = $2", &[&webhook_id, &user_id])
+            .await?;
+        Ok(deleted > 0)
+    }
+
+    /// The newest deliveries of the user's webhook, or `None` when the user
+    /// has no such webhook.
+    pub async fn deliveries(&self, user_id: &str, webhook_id: &str, limit: i64) -> Result<Option<Vec<Delivery>>, tokio_postgres::Error> {
+        if !self.owns(user_id, webhook_id).await? {
+            return Ok(None);
+        }
+
+        let rows = self.pg.get().await
+            .query(
+                &format!(
+                    "SELECT {} FROM webhook_deliveries WHERE webhook_id = $1 ORDER BY delivery_id DESC LIMIT $2",
+                    DELIVERY_COLUMNS
+                ),
+                &[&webhook_id, &limit],
+            )
+            .await?;
+        Ok(Some(rows.iter().map(delivery_from_row).collect()))
+    }
+
+    /// One delivery with every attempt made for it, oldest first.
+    pub async fn delivery(&self, user_id: &str, webhook_id: &str, delivery_id: i64) -> Result<Option<Delivery>, tokio_postgres::Error> {
+        if !self.owns(user_id, webhook_id).await? {
+            return Ok(None);
+        }
+
+        let row = self.pg.get().await
+            .query_opt(
+                &format!("SELECT {} FROM webhook_deliveries WHERE webhook_id = $1 AND delivery_id = $2", DELIVERY_COLUMNS),
+                &[&webhook_id, &delivery_id],
+            )
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let attempts = self.pg.get().await
+            .query(
+                "SELECT response_status, error, duration_ms, (EXTRACT(EPOCH FROM attempted_at) * 1000)::BIGINT
+                 FROM webhook_attempts WHERE delivery_id = $1 ORDER BY attempt_id",
+                &[&delivery_id],
+            )
+            .await?;
+
+        let mut delivery = delivery_from_row(&row);
+        delivery.history = Some(
+            attempts
+                .iter()
+                .map(|row| Attempt {
+                    response_status: row.get(0),
+                    error: row.get(1),
+                    duration_ms: row.get(2),
+                    attempted_at_ms: row.get(3),
+                })
+                .collect(),
+        );
+        Ok(Some(delivery))
+    }
+
+    /// Queues a delivery to be sent again right away with a fresh set of
+    /// attempts, whatever its state. Returns `None` when the user has no
+    /// such delivery.
+    pub async fn redeliver(&self, user_id: &str, webhook_id: &str, delivery_id: i64) -> Result<Option<Delivery>, tokio_postgres::Error> {
+        if !self.owns(user_id, webhook_id).await? {
+            return Ok(None);
+        }
+
+        let row = self.pg.get().await
+            .query_opt(
+                &format!(
+                    "UPDATE webhook_deliveries SET state = 'pending', attempts = 0, next_attempt_at = NOW()
+                     WHERE webhook_id = $1 AND delivery_id = $2 RETURNING {}",
+                    DELIVERY_COLUMNS
+                ),
+                &[&webhook_id, &delivery_id],
+            )
+            .await?;
+        Ok(row.as_ref().map(delivery_from_row))
+    }
+
+    async fn owns(&self, user_id: &str, webhook_id: &str) -> Result<bool, tokio_postgres::Error> {
+        let row = self.pg.get().await
+            .query_opt("SELECT 1 FROM webhooks WHERE webhook_id = $1 AND user_id = $2", &[&webhook_id, &user_id])
+            .await?;
+        Ok(row.is_some())
+    }
+
+    /// Creates a delivery for every webhook of the event's user that wants
+    /// its status. Reading the same event twice adds nothing.
+    async fn enqueue(&self, event: &StatusEvent) -> Result<(), tokio_postgres::Error> {
+        let Some(user_id) = &event.user_id else {
+            return Ok(());
+        };
+
+        self.pg.get().await
+            .execute(
+                "INSERT INTO webhook_deliveries (webhook_id, event_id, order_id, status, status_data)
+                 SELECT webhook_id, $2::VARCHAR, $3::VARCHAR, $4::VARCHAR, $5::TEXT FROM webhooks
+                 WHERE user_id = $1 AND (statuses IS NULL OR $4::VARCHAR = ANY(statuses))
+                 ON CONFLICT (webhook_id, event_id) DO NOTHING",
+                &[user_id, &event.id, &event.order_id, &event.status, &event.data],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Leases up to `CLAIM_BATCH` due deliveries, so other backend instances
+    /// skip them while they are being sent.
+    async fn claim_due(&self) -> Result<Vec<Claimed>, tokio_postgres::Error> {
+        let rows = self.pg.get().await
+            .query(
+                "UPDATE webhook_deliveries d SET next_attempt_at = NOW() + make_interval(secs => $2)
+                 FROM webhooks w
+                 WHERE d.webhook_id = w.webhook_id AND d.delivery_id IN (
+                     SELECT delivery_id FROM webhook_deliveries
+                     WHERE state = 'pending' AND next_attempt_at <= NOW()
+                     ORDER BY next_attempt_at LIMIT $1
+                     FOR UPDATE SKIP LOCKED
+                 )
+                 RETURNING d.delivery_id, d.webhook_id, d.event_id, d.order_id, d.status, d.status_data, d.attempts, w.url, w.secret",
+                &[&CLAIM_BATCH, &CLAIM_LEASE_SECS],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| Claimed {
+                delivery_id: row.get(0),
+                webhook_id: row.get(1),
+                event_id: row.get(2),
+                order_id: row.get(3),
+                status: row.get(4),
+                status_data: row.get(5),
+                attempts: row.get(6),
+                url: row.get(7),
+                secret: row.get(8),
+            })
+            .collect())
+    }
+
+    async fn record_attempt(
+        &self,
+        config: &WebhookConfig,
+        claimed: &Claimed,
+        response_status: Option<i32>,
+        error: Option<&str>,
+        elapsed: Duration,
+    ) -> Result<(), tokio_postgres::Error> {
+        let attempts = claimed.attempts + 1;
+        let delivered = response_status.is_some_and(|status| (200..300).contains(&status));
+        let state = match (delivered, attempts >= config.max_attempts) {
+            (true, _) => "delivered",
+            (false, true) => "failed",
+            (false, false) => "pending",
+        };
+        let retry_in = config.backoff(attempts).as_secs_f64();
+        let duration_ms = elapsed.as_millis().min(i32::MAX as u128) as i32;
+
+        self.pg.get().await
+            .execute(
+                "INSERT INTO webhook_attempts (delivery_id, response_status, error, duration_ms) VALUES ($1, $2, $3, $4)",
+                &[&claimed.delivery_id, &response_status, &error, &duration_ms],
+            )
+            .await?;
+        self.pg.get().await
+            .execute(
+                "UPDATE webhook_deliveries SET
+                    state = $2, attempts = $3, last_response_status = $4, last_error = $5,
+                    next_attempt_at = NOW() + make_interval(secs => $6),
+                    delivered_at = CASE WHEN $7 THEN NOW() END
+                 WHERE delivery_id = $1",
+                &[&claimed.delivery_id, &state, &attempts, &response_status, &error, &retry_in, &delivered],
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// Turns `status_updates` into deliveries. The last enqueued stream id is
+/// kept in Redis, so updates published while the backend was down are
+/// delivered once it is back.
+pub async fn listen(store: Arc<WebhookStore>, streams: StreamReaders, redis: RedisPool) {
+    // Waits for Redis rather than starting at `$` without the cursor, which
+    // would skip whatever was published in the meantime.
+    let cursor: Option<String> = loop {
+        if let Ok(mut conn) = redis.get().await {
+            if let Ok(cursor) = conn.get(CURSOR_KEY).await {
+                break cursor;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+    };
+    let mut reader = streams.reader("webhooks", STATUS_STREAM, cursor.unwrap_or_else(|| "$".to_string()));
+
+    loop {
+        for message in reader.next().await {
+            if let Some(event) = StatusEvent::from_stream(&message) {
+                // Retried until Postgres is back, so no update is skipped.
+                while let Err(e) = store.enqueue(&event).await {
+                    tracing::warn!(event_id = %event.id, error = %e, "failed to queue webhooks");
+                    tokio::time::sleep(Duration::from_millis(1000)).await;
+                }
+            }
+        }
+        if let Ok(mut conn) = redis.get().await {
+            let _: Result<(), _> = conn.set(CURSOR_KEY, reader.last_id()).await;
+        }
+    }
+}
+
+/// Sends due deliveries until the process exits.
+pub async fn dispatch(store: Arc<WebhookStore>, config: WebhookConfig) {
+    // Redirects are not followed, so a receiver cannot send the request on
+    // to an address it would not have been allowed to register.
+    let mut builder = reqwest::Client::builder().timeout(config.timeout).redirect(reqwest::redirect::Policy::none());
+    if !config.allow_private {
+        builder = builder.dns_resolver(Arc::new(PublicResolver));
+    }
+    let http = match builder.build() {
+        Ok(http) => http,
+        Err(e) => {
+            tracing::error!(error = %e, "webhook dispatcher failed to start");
+            return;
+        }
+    };
+
+    loop {
+        let claimed = match store.claim_due().await {
+            Ok(claimed) => claimed,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to claim webhook deliveries");
+                Vec::new()
+            }
+        };
+        if claimed.is_empty() {
+            tokio::time::sleep(config.poll_interval).await;
+            continue;
+        }
+
+        let sends = claimed.iter().map(|claimed| send(&store, &config, &http, claimed));
+        futures_util::future::join_all(sends).await;
+    }
+}
+
+async fn send(store: &WebhookStore, config: &WebhookConfig, http: &reqwest::Client, claimed: &Claimed) {
+    let update = serde_json::from_str::<serde_json::Value>(&claimed.status_data).unwrap_or_default();
+    let body = serde_json::json!({
+        "delivery_id": claimed.delivery_id,
+        "webhook_id": claimed.webhook_id,
+        "event_id": claimed.event_id,
+        "order_id": claimed.order_id,
+        "status": claimed.status,
+        "update": update,
+    })
+    .to_string();
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0).to_string();
+
+    // Names are checked as they are resolved, but an address in the URL
+    // never reaches the resolver.
+    if !config.allow_private {
+        if let Some(ip) = literal_ip(&claimed.url).filter(|ip| !is_public(*ip)) {
+            let error = format!("{} is not a public address", ip);
+            if let Err(e) = store.record_attempt(config, claimed, None, Some(&error), Duration::ZERO).await {
+                tracing::warn!(delivery_id = %claimed.delivery_id, error = %e, "failed to record webhook delivery");
+            }
+            return;
+        }
+    }
+
+    let started = Instant::now();
+    let result = http
+        .post(&claimed.url)
+        .header("content-type", "application/json")
+        .header("x-webhook-id", &claimed.webhook_id)
+        .header("x-webhook-delivery", claimed.delivery_id.to_string())
+        .header("x-webhook-timestamp", &timestamp)
+        .header("x-webhook-signature", format!("v1={}", sign(&claimed.secret, &timestamp, &body)))
+        .body(body)
+        .send()
+        .await;
+
+    let (response_status, error) = match result {
+        Ok(response) if response.status().is_success() => (Some(response.status().as_u16() as i32), None),
+        Ok(response) => (Some(response.status().as_u16() as i32), Some(format!("HTTP {}", response.status()))),
+        Err(e) => (None, Some(error_chain(&e).chars().take(MAX_ERROR_LEN).collect())),
+    };
+
+    if let Err(e) = store.record_attempt(config, claimed, response_status, error.as_deref(), started.elapsed()).await {
+        tracing::warn!(delivery_id = %claimed.delivery_id, error = %e, "failed to record webhook delivery");
+    }
+}
+
+/// Refuses a webhook URL whose host is internal or resolves to an address
+/// that is not public, unless `allow_private` is set.
+pub async fn check_destination(url: &str, config: &WebhookConfig) -> Result<(), String> {
+    if config.allow_private {
+        return Ok(());
+    }
+    let url = reqwest::Url::parse(url).map_err(|e| e.to_string())?;
+    if let Some(ip) = literal_ip(url.as_str()) {
+        if !is_public(ip) {
+            return Err(format!("{} is not a public address", ip));
+        }
+        return Ok(());
+    }
+    let host = url.host_str().ok_or("url has no host")?;
+    resolve_public(host).await.map(|_| ())
+}
+
+/// Resolves names for webhook requests, refusing any that lead to an
+/// internal host. Checking here rather than only on registration means a
+/// name cannot be re-pointed at a private address afterwards.
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let host = name.as_str().to_owned();
+        Box::pin(async move {
+            let addrs = resolve_public(&host).await?;
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+async fn resolve_public(host: &str) -> Result<Vec<SocketAddr>, String> {
+    if is_internal_host(host) {
+        return Err(format!("{} is an internal host", host));
+    }
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0))
+        .await
+        .map_err(|e| format!("{} does not resolve: {}", host, e))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("{} does not resolve", host));
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+        return Err(format!("{} resolves to {}, which is not a public address", host, addr.ip()));
+    }
+    Ok(addrs)
+}
+
+fn literal_ip(url: &str) -> Option<IpAddr> {
+    let url = reqwest::Url::parse(url).ok()?;
+    url.host_str()?.trim_start_matches('[').trim_end_matches(']').parse().ok()
+}
+
+/// Single-label names (`localhost`, compose services such as `redis`) and
+/// the suffixes reserved for local networks.
+fn is_internal_host(host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    !host.contains('.')
+        || [".localhost", ".local", ".internal", ".lan", ".home.arpa"].iter().any(|suffix| host.ends_with(suffix))
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_v4(mapped),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        || a >= 240
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 192 && b == 0 && ip.octets()[2] == 0)
+        || (a == 198 && (b == 18 || b == 19)))
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    // IPv4-compatible and NAT64 addresses carry the IPv4 address they reach.
+    if ip.segments()[..6] == [0; 6] || ip.segments()[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+        let [.., a, b, c, d] = ip.octets();
+        return is_public_v4(Ipv4Addr::new(a, b, c, d));
+    }
+    !(ip.is_multicast()
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+        || (first == 0x2001 && ip.segments()[1] == 0xdb8))
+}
+
+/// The error with its causes, which carry the reason a request was not sent,
+/// e.g. a refused destination.
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+/// Hex HMAC-SHA256 of `{timestamp}.{body}` under the webhook's secret.
+fn sign(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("whsec_{}", hex::encode(bytes))
+}
+
+fn delivery_from_row(row: &Row) -> Delivery {
+    Delivery {
+        delivery_id: row.get(0),
+        event_id: row.get(1),
+        order_id: row.get(2),
+        status: row.get(3),
+        state: row.get(4),
+        attempts: row.get(5),
+        last_response_status: row.get(6),
+        last_error: row.get(7),
+        next_attempt_at_ms: row.get(8),
+        delivered_at_ms: row.get(9),
+        created_at_ms: row.get(10),
+        history: None,
+    }
+}
+
+fn env_or(name: &str, default: u64) -> u64 {
+    std::env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(allow_private: bool) -> WebhookConfig {
+        WebhookConfig {
+            max_attempts: 6,
+            retry_base: Duration::from_millis(2000),
+            retry_max: Duration::from_millis(300_000),
+            timeout: Duration::from_millis(10_000),
+            poll_interval: Duration::from_millis(500),
+            allow_private,
+        }
+    }
+
+    #[test]
+    fn only_public_addresses_are_public() {
+        for ip in ["93.184.216.34", "8.8.8.8", "2606:2800:220:1::1", "::ffff:8.8.8.8", "64:ff9b::808:808"] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "0.0.0.0",
+            "255.255.255.255", "224.0.0.1", "198.18.0.1", "::1", "::", "fd00::1", "fe80::1", "::ffff:127.0.0.1",
+            "::ffff:10.0.0.1", "::127.0.0.1", "64:ff9b::a00:1", "2001:db8::1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn single_label_and_local_names_are_internal() {
+        for host in ["localhost", "redis", "postgres.", "host.docker.internal", "printer.local", "app.localhost", "nas.home.arpa"] {
+            assert!(is_internal_host(host), "{}", host);
+        }
+        for host in ["example.com", "hooks.example.com.", "internal.example.com"] {
+            assert!(!is_internal_host(host), "{}", host);
+        }
+    }
+
+    #[tokio::test]
+    async fn destinations_are_checked_unless_private_ones_are_allowed() {
+        let refused = config(false);
+        for url in [
+            "http://127.0.0.1:4010/hooks", "http://[::1]/hooks", "http://169.254.169.254/latest",
+            "http://localhost/hooks", "http://host.docker.internal:4010/hooks", "http://redis:6379",
+        ] {
+            assert!(check_destination(url, &refused).await.is_err(), "{}", url);
+        }
+        assert!(check_destination("https://93.184.216.34/hooks", &refused).await.is_ok());
+
+        let allowed = config(true);
+        assert!(check_destination("http://127.0.0.1:4010/hooks", &allowed).await.is_ok());
+        assert!(check_destination("http://host.docker.internal:4010/hooks", &allowed).await.is_ok());
+    }
+
+    #[test]
+    fn literal_addresses_are_read_from_the_url() {
+        assert_eq!(literal_ip("http://10.0.0.1:8080/x"), Some("10.0.0.1".parse().unwrap()));
+        assert_eq!(literal_ip("http://[fe80::1]/x"), Some("fe80::1".parse().unwrap()));
+        assert_eq!(literal_ip("https://example.com/x"), None);
+    }
+}
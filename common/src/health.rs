@@ -0,0 +1,84 @@
+use crate::streams::{ReaderReport, StreamReaders};
+use axum::Router;
+use axum::http::StatusCode;
+use serde::Serialize;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// How long a dependency gets to answer a health probe.
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The outcome of one dependency probe.
+#[derive(Serialize)]
+pub struct Check {
+    pub ok: bool,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// When stream readers count as stalled or behind, from
+/// `STALL_TIMEOUT_MS` and `MAX_STREAM_LAG_MS`.
+#[derive(Debug, Clone, Copy)]
+pub struct StreamLimits {
+    pub stall_timeout: Duration,
+    pub max_stream_lag_ms: u64,
+}
+
+impl StreamLimits {
+    pub fn from_env() -> Self {
+        Self {
+            stall_timeout: Duration::from_millis(env_or("STALL_TIMEOUT_MS", 60_000)),
+            max_stream_lag_ms: env_or("MAX_STREAM_LAG_MS", 30_000),
+        }
+    }
+
+    /// Readers that have stopped reading, which only a restart recovers.
+    pub fn stalled(&self, streams: &StreamReaders) -> Vec<&'static str> {
+        streams.stalled(self.stall_timeout)
+    }
+
+    /// Whether every reader is connected and no further behind than
+    /// `max_stream_lag_ms`. A lag that could not be checked does not count.
+    pub fn caught_up(&self, reports: &[ReaderReport]) -> bool {
+        reports
+            .iter()
+            .all(|stream| stream.connected && stream.lag_ms.is_none_or(|lag| lag <= self.max_stream_lag_ms))
+    }
+}
+
+/// Runs `check` with [`PROBE_TIMEOUT`], timing it.
+pub async fn probe<E: std::fmt::Display>(check: impl Future<Output = Result<impl Sized, E>>) -> Check {
+    let started = Instant::now();
+    let error = match tokio::time::timeout(PROBE_TIMEOUT, check).await {
+        Ok(Ok(_)) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(_) => Some("timed out".to_string()),
+    };
+    Check { ok: error.is_none(), latency_ms: started.elapsed().as_millis() as u64, error }
+}
+
+pub fn status_code(ok: bool) -> StatusCode {
+    if ok { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE }
+}
+
+/// Serves `app`, the health and metrics routes of a service without a
+/// public API, on `port` until the process exits.
+pub async fn serve(app: Router, port: String) {
+    let bind_address = format!("0.0.0.0:{}", port);
+    let listener = match tokio::net::TcpListener::bind(&bind_address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!(address = %bind_address, error = %e, "failed to bind health server");
+            return;
+        }
+    };
+    tracing::info!(address = %bind_address, "health server running");
+    if let Err(e) = axum::serve(listener, app).await {
+        tracing::error!(error = %e, "health server stopped");
+    }
+}
+
+fn env_or(name: &str, default: u64) -> u64 {
+    std::env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+}
@@ -0,0 +1,9 @@
+//! Plumbing shared by the services: Redis stream readers and writers,
+//! logging and tracing, the health and metrics endpoints, and a Postgres
+//! client that reconnects.
+
+pub mod health;
+pub mod metrics;
+pub mod pg_pool;
+pub mod streams;
+pub mod telemetry;
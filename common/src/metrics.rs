@@ -0,0 +1,33 @@
+use crate::streams::ReaderReport;
+use prometheus::{register_int_gauge_vec, IntGaugeVec, TextEncoder};
+use std::sync::LazyLock;
+
+static STREAM_LAG_MS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!("stream_lag_ms", "Age of the oldest stream entry a reader has not read", &["reader", "stream"]).unwrap()
+});
+
+static STREAM_CONNECTED: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!("stream_connected", "Whether a stream reader is connected", &["reader", "stream"]).unwrap()
+});
+
+static STREAM_RECONNECTS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!("stream_reconnects", "Times a stream reader lost its connection", &["reader", "stream"]).unwrap()
+});
+
+/// Copies the readers' state into the stream gauges. A reader whose lag
+/// could not be checked keeps its last value.
+pub fn set_streams(reports: &[ReaderReport]) {
+    for report in reports {
+        let labels = [report.reader, report.stream];
+        STREAM_CONNECTED.with_label_values(&labels).set(report.connected as i64);
+        STREAM_RECONNECTS.with_label_values(&labels).set(report.reconnects as i64);
+        if let Some(lag_ms) = report.lag_ms {
+            STREAM_LAG_MS.with_label_values(&labels).set(lag_ms as i64);
+        }
+    }
+}
+
+/// Every registered metric in the Prometheus text format.
+pub fn render() -> String {
+    TextEncoder::new().encode_to_string(&prometheus::gather()).unwrap_or_default()
+}
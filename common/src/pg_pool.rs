@@ -0,0 +1,79 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tokio_postgres::{Client, Config, NoTls};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Postgres access shared by every request. The client is opened at start
+/// and reopened once its connection drops, so a database restart only fails
+/// the queries made while it is down. Failed attempts back off
+/// exponentially, and until the next attempt is due callers get the closed
+/// client, whose queries fail at once instead of waiting on a dead server.
+pub struct PgPool {
+    config: Config,
+    state: Mutex<PgState>,
+}
+
+struct PgState {
+    client: Arc<Client>,
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+impl PgPool {
+    pub async fn connect(database_url: &str) -> Result<Self, tokio_postgres::Error> {
+        let mut config: Config = database_url.parse()?;
+        config.connect_timeout(CONNECT_TIMEOUT);
+        let client = open(&config).await?;
+
+        Ok(Self {
+            config,
+            state: Mutex::new(PgState { client: Arc::new(client), failures: 0, retry_at: None }),
+        })
+    }
+
+    pub async fn get(&self) -> Arc<Client> {
+        // Held while connecting, so a recovering server sees one attempt
+        // rather than one per waiting request.
+        let mut state = self.state.lock().await;
+        let backing_off = state.retry_at.is_some_and(|retry_at| Instant::now() < retry_at);
+        if !state.client.is_closed() || backing_off {
+            return state.client.clone();
+        }
+
+        match open(&self.config).await {
+            Ok(client) => {
+                tracing::info!(failures = state.failures, "reconnected to postgres");
+                state.client = Arc::new(client);
+                state.failures = 0;
+                state.retry_at = None;
+            }
+            Err(e) => {
+                let backoff = MIN_BACKOFF.saturating_mul(1 << state.failures.min(16)).min(MAX_BACKOFF);
+                state.failures += 1;
+                state.retry_at = Some(Instant::now() + backoff);
+                tracing::warn!(error = %e, ?backoff, "failed to connect to postgres");
+            }
+        }
+        state.client.clone()
+    }
+
+    pub async fn ping(&self) -> Result<(), tokio_postgres::Error> {
+        self.get().await.simple_query("SELECT 1").await.map(|_| ())
+    }
+}
+
+async fn open(config: &Config) -> Result<Client, tokio_postgres::Error> {
+    let (client, connection) = config.connect(NoTls).await?;
+
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            tracing::error!(error = %e, "postgres connection closed");
+        }
+    });
+    Ok(client)
+}
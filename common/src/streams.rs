@@ -0,0 +1,273 @@
+use redis::aio::{Connection, ConnectionLike, MultiplexedConnection};
+use redis::streams::{StreamId, StreamRangeReply, StreamReadOptions, StreamReadReply};
+use redis::{AsyncCommands, Client, RedisError, RedisResult};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+/// How long a read waits for new entries before it is issued again.
+const READ_BLOCK_MS: usize = 5000;
+/// Extra time a blocked read gets before its connection is presumed dead.
+const READ_GRACE: Duration = Duration::from_secs(5);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Hands out stream readers and keeps track of them for the health and
+/// admin endpoints.
+#[derive(Clone)]
+pub struct StreamReaders {
+    client: Client,
+    readers: Arc<Mutex<Vec<Arc<StreamHealth>>>>,
+}
+
+/// A reader's connection state, shared with [`StreamReaders`].
+struct StreamHealth {
+    reader: &'static str,
+    stream: &'static str,
+    connected: AtomicBool,
+    reconnects: AtomicU64,
+    last_id: Mutex<String>,
+    /// When the reader last asked Redis for entries.
+    polled_at: Mutex<Instant>,
+}
+
+/// A reader's state as reported by the health and admin endpoints.
+#[derive(Serialize)]
+pub struct ReaderReport {
+    pub reader: &'static str,
+    pub stream: &'static str,
+    pub connected: bool,
+    pub reconnects: u64,
+    pub last_id: String,
+    /// How long the oldest entry not yet read has been in the stream, or
+    /// `None` if that could not be checked.
+    pub lag_ms: Option<u64>,
+}
+
+/// Follows a Redis stream across connection failures. A failed read drops
+/// the connection and reconnects with exponential backoff, resuming after
+/// the last entry returned, so entries added during an outage are still
+/// read once Redis is back.
+pub struct StreamReader {
+    client: Client,
+    stream: &'static str,
+    last_id: String,
+    conn: Option<Connection>,
+    failures: u32,
+    health: Arc<StreamHealth>,
+}
+
+/// Appends to a Redis stream in order, retrying each entry with backoff
+/// until it is written, so nothing is dropped while Redis is down.
+pub struct StreamWriter {
+    client: Client,
+    stream: &'static str,
+    field: &'static str,
+    conn: Option<MultiplexedConnection>,
+    failures: u32,
+}
+
+impl StreamReaders {
+    pub fn new(client: Client) -> Self {
+        Self { client, readers: Arc::default() }
+    }
+
+    /// Reads `stream` after `from`, where `$` means the entries added from
+    /// now on. `name` tells the reader apart from others on the same stream.
+    pub fn reader(&self, name: &'static str, stream: &'static str, from: impl Into<String>) -> StreamReader {
+        let last_id = from.into();
+        let health = Arc::new(StreamHealth {
+            reader: name,
+            stream,
+            connected: AtomicBool::new(false),
+            reconnects: AtomicU64::new(0),
+            last_id: Mutex::new(last_id.clone()),
+            polled_at: Mutex::new(Instant::now()),
+        });
+        self.readers.lock().unwrap().push(health.clone());
+        StreamReader { client: self.client.clone(), stream, last_id, conn: None, failures: 0, health }
+    }
+
+    /// Readers that have not asked for entries within `limit`. A reader
+    /// that is being followed asks at least every read timeout or backoff,
+    /// so one that stops means its consumer is stuck.
+    pub fn stalled(&self, limit: Duration) -> Vec<&'static str> {
+        self.readers
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|health| health.polled_at.lock().unwrap().elapsed() > limit)
+            .map(|health| health.reader)
+            .collect()
+    }
+
+    /// Every reader's state, with its lag read through `conn` when there is
+    /// one.
+    pub async fn report<C: ConnectionLike + Send>(&self, mut conn: Option<&mut C>) -> Vec<ReaderReport> {
+        let readers = self.readers.lock().unwrap().clone();
+        let mut reports = Vec::with_capacity(readers.len());
+        for health in readers {
+            let last_id = health.last_id.lock().unwrap().clone();
+            let lag_ms = match conn.as_deref_mut() {
+                Some(conn) => lag(conn, health.stream, &last_id).await,
+                None => None,
+            };
+            reports.push(ReaderReport {
+                reader: health.reader,
+                stream: health.stream,
+                connected: health.connected.load(Ordering::Relaxed),
+                reconnects: health.reconnects.load(Ordering::Relaxed),
+                last_id,
+                lag_ms,
+            });
+        }
+        reports
+    }
+}
+
+impl StreamReader {
+    /// The next entries, waiting for as long as it takes to get some.
+    pub async fn next(&mut self) -> Vec<StreamId> {
+        loop {
+            *self.health.polled_at.lock().unwrap() = Instant::now();
+            match self.read().await {
+                Ok(entries) if entries.is_empty() => {}
+                Ok(entries) => return entries,
+                Err(e) => {
+                    self.conn = None;
+                    let backoff = backoff(self.failures);
+                    self.failures += 1;
+                    if self.health.connected.swap(false, Ordering::Relaxed) {
+                        self.health.reconnects.fetch_add(1, Ordering::Relaxed);
+                    }
+                    tracing::warn!(stream = self.stream, error = %e, ?backoff, "stream read failed");
+                    tokio::time::sleep(backoff).await;
+                }
+            }
+        }
+    }
+
+    /// Counts as a poll for [`StreamReaders::stalled`], for a consumer that
+    /// is still working through the entries it was given, such as retrying
+    /// a write, rather than stuck.
+    pub fn keep_alive(&self) {
+        *self.health.polled_at.lock().unwrap() = Instant::now();
+    }
+
+    /// The id of the last entry returned, or where reading starts.
+    pub fn last_id(&self) -> &str {
+        &self.last_id
+    }
+
+    async fn read(&mut self) -> RedisResult<Vec<StreamId>> {
+        let conn = match &mut self.conn {
+            Some(conn) => conn,
+            None => {
+                let conn = tokio::time::timeout(CONNECT_TIMEOUT, self.connect())
+                    .await
+                    .map_err(|_| timed_out("stream connect timed out"))??;
+                if !self.health.connected.swap(true, Ordering::Relaxed) && self.failures > 0 {
+                    tracing::info!(stream = self.stream, after = %self.last_id, "stream reader reconnected");
+                }
+                self.conn.insert(conn)
+            }
+        };
+
+        let options = StreamReadOptions::default().block(READ_BLOCK_MS);
+        let read_timeout = Duration::from_millis(READ_BLOCK_MS as u64) + READ_GRACE;
+        let reply: StreamReadReply = tokio::time::timeout(read_timeout, conn.xread_options(&[self.stream], &[&self.last_id], &options))
+            .await
+            .map_err(|_| timed_out("stream read timed out"))??;
+        self.failures = 0;
+
+        let entries: Vec<StreamId> = reply.keys.into_iter().flat_map(|key| key.ids).collect();
+        if let Some(last) = entries.last() {
+            self.last_id = last.id.clone();
+            *self.health.last_id.lock().unwrap() = last.id.clone();
+        }
+        Ok(entries)
+    }
+
+    async fn connect(&mut self) -> RedisResult<Connection> {
+        let mut conn = self.client.get_async_connection().await?;
+        // Pins `$` to the current end of the stream, so a reconnect resumes
+        // from there instead of skipping what was added in between.
+        if self.last_id == "$" {
+            let newest: StreamRangeReply = conn.xrevrange_count(self.stream, "+", "-", 1).await?;
+            self.last_id = newest.ids.first().map(|entry| entry.id.clone()).unwrap_or_else(|| "0-0".to_string());
+            *self.health.last_id.lock().unwrap() = self.last_id.clone();
+        }
+        Ok(conn)
+    }
+}
+
+impl StreamWriter {
+    pub fn new(client: Client, stream: &'static str, field: &'static str) -> Self {
+        Self { client, stream, field, conn: None, failures: 0 }
+    }
+
+    /// Appends one entry, retrying until Redis takes it.
+    pub async fn add(&mut self, value: &str) {
+        loop {
+            match self.write(value).await {
+                Ok(()) => {
+                    if self.failures > 0 {
+                        tracing::info!(stream = self.stream, "stream writer reconnected");
+                    }
+                    self.failures = 0;
+                    return;
+                }
+                Err(e) => {
+                    self.conn = None;
+                    let backoff = backoff(self.failures);
+                    self.failures += 1;
+                    tracing::warn!(stream = self.stream, error = %e, ?backoff, "stream write failed");
+                    tokio::time::sleep(backoff).await;
+                }
+            }
+        }
+    }
+
+    async fn write(&mut self, value: &str) -> RedisResult<()> {
+        let conn = match &mut self.conn {
+            Some(conn) => conn,
+            None => {
+                let conn = tokio::time::timeout(CONNECT_TIMEOUT, self.client.get_multiplexed_tokio_connection())
+                    .await
+                    .map_err(|_| timed_out("stream connect timed out"))??;
+                self.conn.insert(conn)
+            }
+        };
+
+        let _: String = tokio::time::timeout(WRITE_TIMEOUT, conn.xadd(self.stream, "*", &[(self.field, value)]))
+            .await
+            .map_err(|_| timed_out("stream write timed out"))??;
+        Ok(())
+    }
+}
+
+/// How long the first entry after `last_id` has been waiting, or zero when
+/// the reader is caught up.
+async fn lag<C: ConnectionLike + Send>(conn: &mut C, stream: &str, last_id: &str) -> Option<u64> {
+    if last_id == "$" {
+        return None;
+    }
+    let next: StreamRangeReply = conn.xrange_count(stream, format!("({}", last_id), "+", 1).await.ok()?;
+    let Some(entry) = next.ids.first() else {
+        return Some(0);
+    };
+    let added_ms: u64 = entry.id.split('-').next()?.parse().ok()?;
+    let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_millis() as u64;
+    Some(now_ms.saturating_sub(added_ms))
+}
+
+fn backoff(failures: u32) -> Duration {
+    MIN_BACKOFF.saturating_mul(1 << failures.min(16)).min(MAX_BACKOFF)
+}
+
+fn timed_out(message: &'static str) -> RedisError {
+    RedisError::from(std::io::Error::new(std::io::ErrorKind::TimedOut, message))
+}
@@ -0,0 +1,82 @@
+use opentelemetry::global;
+use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use std::collections::HashMap;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter};
+
+/// The W3C trace context headers of the span a Redis message was sent
+/// from, carried in the message as `trace_context`.
+pub type TraceContext = HashMap<String, String>;
+
+/// Flushes exported spans when dropped at the end of `main`.
+pub struct Telemetry {
+    provider: SdkTracerProvider,
+}
+
+/// Logs to stdout, as JSON unless `LOG_FORMAT=text`, filtered by
+/// `RUST_LOG` (default `info`). Spans are also exported over OTLP/HTTP
+/// when `OTEL_EXPORTER_OTLP_ENDPOINT` is set; without it they still get
+/// trace ids, so logs and Redis messages can be correlated.
+pub fn init(service: &'static str) -> Telemetry {
+    let mut provider = SdkTracerProvider::builder().with_resource(Resource::builder().with_service_name(service).build());
+    let mut export_error = None;
+    if std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_some() {
+        match SpanExporter::builder().with_http().build() {
+            Ok(exporter) => provider = provider.with_batch_exporter(exporter),
+            Err(e) => export_error = Some(e),
+        }
+    }
+    let provider = provider.build();
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer(service)));
+    if std::env::var("LOG_FORMAT").is_ok_and(|format| format == "text") {
+        registry.with(fmt::layer()).init();
+    } else {
+        registry.with(fmt::layer().json().flatten_event(true).with_span_list(false)).init();
+    }
+
+    if let Some(e) = export_error {
+        tracing::error!(error = %e, "trace export disabled");
+    }
+    Telemetry { provider }
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        let _ = self.provider.shutdown();
+    }
+}
+
+/// `span`'s context, to send along with a message.
+pub fn inject(span: &Span) -> TraceContext {
+    let mut context = TraceContext::new();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&span.context(), &mut context));
+    context
+}
+
+/// Continues the trace a message was sent with in `span`, which must not
+/// have been entered yet, and records the trace id in its `trace_id` field.
+pub fn follow(span: &Span, context: Option<&TraceContext>) {
+    if let Some(context) = context {
+        let parent = global::get_text_map_propagator(|propagator| propagator.extract(context));
+        let _ = span.set_parent(parent);
+    }
+    record_trace_id(span);
+}
+
+/// Records the trace id in `span`'s `trace_id` field, so it shows in logs.
+pub fn record_trace_id(span: &Span) {
+    let trace_id = span.context().span().span_context().trace_id();
+    span.record("trace_id", tracing::field::display(trace_id));
+}
@@ -0,0 +1,3 @@
+ALTER TABLE orders DROP COLUMN IF EXISTS fee_lamports;
+ALTER TABLE orders DROP COLUMN IF EXISTS compute_units;
+ALTER TABLE orders DROP COLUMN IF EXISTS priority_fee;
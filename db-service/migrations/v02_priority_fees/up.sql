@@ -0,0 +1,3 @@
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS priority_fee BIGINT;
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS compute_units INTEGER;
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS fee_lamports BIGINT;
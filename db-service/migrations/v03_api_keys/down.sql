@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_orders_user_id;
+ALTER TABLE orders DROP COLUMN IF EXISTS user_id;
+DROP TABLE IF EXISTS api_keys;
+DROP TABLE IF EXISTS users;
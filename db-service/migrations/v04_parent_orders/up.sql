@@ -0,0 +1,2 @@
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS parent_order_id VARCHAR(255);
+CREATE INDEX IF NOT EXISTS idx_orders_parent_order_id ON orders(parent_order_id);
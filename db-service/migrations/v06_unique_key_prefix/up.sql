@@ -0,0 +1 @@
+ALTER TABLE api_keys ADD CONSTRAINT api_keys_key_prefix_key UNIQUE (key_prefix);
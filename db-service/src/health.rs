@@ -0,0 +1,97 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use common::health::{probe, status_code, StreamLimits, PROBE_TIMEOUT};
+use common::streams::{ReaderReport, StreamReaders};
+use common::pg_pool::PgPool;
+use redis::aio::MultiplexedConnection;
+use std::sync::Arc;
+use crate::metrics;
+
+/// `/healthz` and `/readyz` for orchestrators, and `/metrics` for
+/// Prometheus, served on `HEALTH_PORT`.
+pub struct Health {
+    limits: StreamLimits,
+    streams: StreamReaders,
+    redis_client: redis::Client,
+    pg: Arc<PgPool>,
+}
+
+impl Health {
+    pub fn new(streams: StreamReaders, redis_client: redis::Client, pg: Arc<PgPool>) -> Self {
+        Self {
+            limits: StreamLimits::from_env(),
+            streams,
+            redis_client,
+            pg,
+        }
+    }
+
+    pub async fn serve(self: Arc<Self>, port: String) {
+        let app = Router::new()
+            .route("/healthz", get(healthz))
+            .route("/readyz", get(readyz))
+            .route("/metrics", get(render_metrics))
+            .with_state(self);
+        common::health::serve(app, port).await;
+    }
+
+    /// The readers' state, with their lag when `conn` answers in time.
+    async fn streams(&self, conn: Option<MultiplexedConnection>) -> Vec<ReaderReport> {
+        let streams = match conn {
+            Some(mut conn) => tokio::time::timeout(PROBE_TIMEOUT, self.streams.report(Some(&mut conn))).await.ok(),
+            None => None,
+        };
+        match streams {
+            Some(streams) => streams,
+            None => self.streams.report(None::<&mut MultiplexedConnection>).await,
+        }
+    }
+}
+
+/// Fails when the status listener has stopped reading. Writes held up by
+/// Postgres being down do not count: they are retried until it is back.
+async fn healthz(State(health): State<Arc<Health>>) -> (StatusCode, Json<serde_json::Value>) {
+    let stalled = health.limits.stalled(&health.streams);
+    let healthy = stalled.is_empty();
+
+    let body = serde_json::json!({
+        "status": if healthy { "ok" } else { "unhealthy" },
+        "stalled": stalled,
+    });
+    (status_code(healthy), Json(body))
+}
+
+/// Fails while Redis or Postgres is unreachable, or the status listener is
+/// disconnected or further behind than `MAX_STREAM_LAG_MS`, as it is while
+/// catching up on the stream after a start.
+async fn readyz(State(health): State<Arc<Health>>) -> (StatusCode, Json<serde_json::Value>) {
+    let mut conn = None;
+    let redis = probe(async {
+        let mut connected = health.redis_client.get_multiplexed_tokio_connection().await?;
+        let pong = redis::cmd("PING").query_async::<_, String>(&mut connected).await;
+        conn = Some(connected);
+        pong
+    })
+    .await;
+    let postgres = probe(health.pg.ping()).await;
+
+    let streams = health.streams(conn.filter(|_| redis.ok)).await;
+
+    let ready = redis.ok && postgres.ok && health.limits.caught_up(&streams);
+
+    let body = serde_json::json!({
+        "status": if ready { "ready" } else { "not_ready" },
+        "redis": redis,
+        "postgres": postgres,
+        "streams": streams,
+    });
+    (status_code(ready), Json(body))
+}
+
+async fn render_metrics(State(health): State<Arc<Health>>) -> String {
+    let conn = tokio::time::timeout(PROBE_TIMEOUT, health.redis_client.get_multiplexed_tokio_connection()).await;
+    metrics::set_streams(&health.streams(conn.ok().and_then(Result::ok)).await);
+    metrics::render()
+}
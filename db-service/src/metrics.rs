@@ -0,0 +1,34 @@
+use prometheus::{exponential_buckets, register_histogram_vec, register_int_counter_vec, HistogramVec, IntCounterVec};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+pub use common::metrics::{render, set_streams};
+
+static WRITE_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "db_write_seconds",
+        "Time to store a final status, by result",
+        &["result"],
+        exponential_buckets(0.0005, 2.0, 14).unwrap()
+    )
+    .unwrap()
+});
+
+static ORDERS_STORED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("db_orders_stored_total", "Orders written to Postgres, by final status", &["status"]).unwrap()
+});
+
+/// Records one attempt to store an order's final `status`. `stored` is
+/// `Some(false)` when the order was already in the table and `None` when
+/// the write failed.
+pub fn order_written(status: &str, stored: Option<bool>, took: Duration) {
+    let result = match stored {
+        Some(true) => "inserted",
+        Some(false) => "duplicate",
+        None => "error",
+    };
+    WRITE_SECONDS.with_label_values(&[result]).observe(took.as_secs_f64());
+    if stored == Some(true) {
+        ORDERS_STORED.with_label_values(&[status]).inc();
+    }
+}
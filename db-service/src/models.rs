@@ -1,46 +1,51 @@
 use serde::{Deserialize, Serialize};
-use chrono::{DateTime, Utc};
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Order {
-    pub id: i32,
-    pub order_id: String,
-    pub status: String,
-    pub tx_hash: Option<String>,
-    pub reason: Option<String>,
-    pub execution_price: Option<rust_decimal::Decimal>,
-    pub created_at: DateTime<Utc>,
-    pub updated_at: DateTime<Utc>,
-}
+use common::telemetry::TraceContext;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct NewOrder {
     pub order_id: String,
+    pub user_id: Option<String>,
     pub status: String,
     pub tx_hash: Option<String>,
     pub reason: Option<String>,
     pub execution_price: Option<rust_decimal::Decimal>,
+    pub priority_fee: Option<i64>,
+    pub compute_units: Option<i32>,
+    pub fee_lamports: Option<i64>,
+    pub parent_order_id: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct StatusUpdate {
     pub order_id: String,
+    pub user_id: Option<String>,
     pub status: String,
     pub tx_hash: Option<String>,
     pub reason: Option<String>,
     pub execution_price: Option<f64>,
+    pub priority_fee: Option<u64>,
+    pub compute_units: Option<u32>,
+    pub fee_lamports: Option<u64>,
+    pub parent_order_id: Option<String>,
+    /// The context of the router span that published the update.
+    pub trace_context: Option<TraceContext>,
 }
 
 impl From<StatusUpdate> for NewOrder {
     fn from(status_update: StatusUpdate) -> Self {
         NewOrder {
             order_id: status_update.order_id,
+            user_id: status_update.user_id,
             status: status_update.status,
             tx_hash: status_update.tx_hash,
             reason: status_update.reason,
             execution_price: status_update.execution_price.map(|price| {
                 rust_decimal::Decimal::from_f64_retain(price).unwrap_or_default()
             }),
+            priority_fee: status_update.priority_fee.map(|fee| fee as i64),
+            compute_units: status_update.compute_units.map(|units| units as i32),
+            fee_lamports: status_update.fee_lamports.map(|fee| fee as i64),
+            parent_order_id: status_update.parent_order_id,
         }
     }
 }
@@ -1,56 +1,46 @@
-use redis::{Client, AsyncCommands, from_redis_value};
-use serde_json;
+use redis::from_redis_value;
+use crate::metrics;
 use crate::models::{StatusUpdate, NewOrder};
-use crate::database::create_connection;
-use tokio_postgres::Client as PgClient;
-use std::time::Duration;
-use tokio::time::sleep;
+use common::pg_pool::PgPool;
+use common::streams::{StreamReader, StreamReaders};
+use common::telemetry;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::Instrument;
+
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
 
 pub struct RedisMonitor {
-    redis_client: Client,
-    pg_client: PgClient,
+    streams: StreamReaders,
+    pg: Arc<PgPool>,
 }
 
 impl RedisMonitor {
-    pub async fn new(redis_url: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let redis_client = Client::open(redis_url)?;
-        let pg_client = create_connection().await?;
-        Ok(RedisMonitor {
-            redis_client,
-            pg_client,
-        })
+    pub fn new(streams: StreamReaders, pg: Arc<PgPool>) -> Self {
+        RedisMonitor {
+            streams,
+            pg,
+        }
     }
 
-    pub async fn start_monitoring(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let mut conn = self.redis_client.get_async_connection().await?;
-        let mut latest_msg_id = "0".to_string();
-        
-       loop {
-            let result: Result<redis::streams::StreamReadReply, _> = conn
-                .xread_options(
-                    &["status_updates"], 
-                    &[&latest_msg_id], 
-                    &redis::streams::StreamReadOptions::default().block(1000)
-                )
-                .await;
-            
-            match result {
-                Ok(reply) => {
-                    for stream in reply.keys {
-                        for message in stream.ids {
-                            latest_msg_id = message.id.clone();
-                            self.process_message(&message).await;
-                        }
-                    }
-                }
-                Err(_) => {
-                    sleep(Duration::from_millis(1000)).await;
-                }
+    /// Follows `status_updates` from the start of the stream. A dropped
+    /// Redis connection is reopened and reading resumes after the last
+    /// update read, and a write that fails while Postgres is down is
+    /// retried before the next update is read, so an outage of either
+    /// delays writes rather than losing them.
+    pub async fn start_monitoring(&self) {
+        let mut reader = self.streams.reader("orders", "status_updates", "0");
+
+        loop {
+            for message in reader.next().await {
+                self.process_message(&reader, &message).await;
             }
         }
     }
 
-    async fn process_message(&self, message: &redis::streams::StreamId) {
+    async fn process_message(&self, reader: &StreamReader, message: &redis::streams::StreamId) {
         let status_json_value = match message.map.get("status_data") {
             Some(value) => value,
             None => return,
@@ -66,34 +56,52 @@ impl RedisMonitor {
             Err(_) => return,
         };
 
-        if status_update.status == "confirmed" || status_update.status == "failed" {
-            let _ = self.store_new_order(&status_update).await;
+        if matches!(status_update.status.as_str(), "confirmed" | "partially_filled" | "failed" | "rejected" | "cancelled") {
+            let span = tracing::info_span!("store_order", order_id = %status_update.order_id, status = %status_update.status, trace_id = tracing::field::Empty);
+            telemetry::follow(&span, status_update.trace_context.as_ref());
+
+            let mut failures = 0;
+            loop {
+                let started = Instant::now();
+                let stored = self.store_new_order(&status_update).instrument(span.clone()).await;
+                metrics::order_written(&status_update.status, stored.as_ref().ok().copied(), started.elapsed());
+                match stored {
+                    Ok(true) => tracing::info!(parent: &span, "order stored"),
+                    Ok(false) => tracing::debug!(parent: &span, "order already stored"),
+                    Err(e) if retryable(&e) => {
+                        let backoff = MIN_BACKOFF.saturating_mul(1 << failures.min(16)).min(MAX_BACKOFF);
+                        failures += 1;
+                        tracing::warn!(parent: &span, error = %e, ?backoff, "failed to store order, retrying");
+                        // Waiting on Postgres is not a stuck listener, and
+                        // a restart would not bring the database back.
+                        reader.keep_alive();
+                        tokio::time::sleep(backoff).await;
+                        continue;
+                    }
+                    Err(e) => tracing::error!(parent: &span, error = %e, "failed to store order"),
+                }
+                break;
+            }
         }
     }
 
-    async fn store_new_order(&self, status_update: &StatusUpdate) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// Inserts the order unless it is already stored, returning whether it
+    /// was inserted.
+    async fn store_new_order(&self, status_update: &StatusUpdate) -> Result<bool, tokio_postgres::Error> {
         let new_order = NewOrder::from(status_update.clone());
-        
-        let check_query = "SELECT COUNT(*) FROM orders WHERE order_id = $1";
-        let count: i64 = self.pg_client
-            .query_one(check_query, &[&status_update.order_id])
-            .await?
-            .get(0);
-        
-        if count > 0 {
-            return Ok(());
-        }
-        
-        if let Some(price) = new_order.execution_price {
+        let pg = self.pg.get().await;
+
+        let inserted = if let Some(price) = new_order.execution_price {
             let price_str = price.to_string();
             let query = format!(
                 r#"
-                    INSERT INTO orders (order_id, status, tx_hash, reason, execution_price, created_at, updated_at)
-                    VALUES ($1, $2, $3, $4, {}::numeric, NOW(), NOW())
+                    INSERT INTO orders (order_id, status, tx_hash, reason, execution_price, priority_fee, compute_units, fee_lamports, user_id, parent_order_id, created_at, updated_at)
+                    VALUES ($1, $2, $3, $4, {}::numeric, $5, $6, $7, $8, $9, NOW(), NOW())
+                    ON CONFLICT (order_id) DO NOTHING
                 "#,
                 price_str
             );
-            self.pg_client
+            pg
                 .execute(
                     &query,
                     &[
@@ -101,26 +109,48 @@ impl RedisMonitor {
                         &status_update.status,
                         &status_update.tx_hash,
                         &status_update.reason,
+                        &new_order.priority_fee,
+                        &new_order.compute_units,
+                        &new_order.fee_lamports,
+                        &new_order.user_id,
+                        &new_order.parent_order_id,
                     ],
                 )
-                .await?;
+                .await?
         } else {
-            self.pg_client
+            pg
                 .execute(
                     r#"
-                        INSERT INTO orders (order_id, status, tx_hash, reason, execution_price, created_at, updated_at)
-                        VALUES ($1, $2, $3, $4, NULL, NOW(), NOW())
+                        INSERT INTO orders (order_id, status, tx_hash, reason, execution_price, priority_fee, compute_units, fee_lamports, user_id, parent_order_id, created_at, updated_at)
+                        VALUES ($1, $2, $3, $4, NULL, $5, $6, $7, $8, $9, NOW(), NOW())
+                        ON CONFLICT (order_id) DO NOTHING
                     "#,
                     &[
                         &status_update.order_id,
                         &status_update.status,
                         &status_update.tx_hash,
                         &status_update.reason,
+                        &new_order.priority_fee,
+                        &new_order.compute_units,
+                        &new_order.fee_lamports,
+                        &new_order.user_id,
+                        &new_order.parent_order_id,
                     ],
                 )
-                .await?;
-        }
-        
-        Ok(())
+                .await?
+        };
+
+        Ok(inserted > 0)
+    }
+}
+
+/// Whether a failed write may succeed if it is tried again: the connection
+/// was lost, or the server reported a condition that clears by itself
+/// (connection exceptions, rollbacks, insufficient resources, shutdowns).
+/// Anything else, such as a value the table rejects, would fail the same way.
+fn retryable(e: &tokio_postgres::Error) -> bool {
+    if e.is_closed() || e.source().is_some_and(|source| source.is::<std::io::Error>()) {
+        return true;
     }
+    e.code().is_some_and(|code| ["08", "40", "53", "57"].iter().any(|class| code.code().starts_with(class)))
 }
@@ -10,5 +10,83 @@ diesel::table! {
         execution_price -> Nullable<Numeric>,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        priority_fee -> Nullable<Int8>,
+        compute_units -> Nullable<Int4>,
+        fee_lamports -> Nullable<Int8>,
+        user_id -> Nullable<Varchar>,
+        parent_order_id -> Nullable<Varchar>,
     }
 }
+
+diesel::table! {
+    users (user_id) {
+        user_id -> Varchar,
+        name -> Varchar,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    api_keys (id) {
+        id -> Int4,
+        user_id -> Varchar,
+        key_prefix -> Varchar,
+        key_hash -> Bpchar,
+        created_at -> Timestamptz,
+        revoked_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    webhooks (webhook_id) {
+        webhook_id -> Varchar,
+        user_id -> Varchar,
+        url -> Text,
+        secret -> Varchar,
+        statuses -> Nullable<Array<Nullable<Text>>>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    webhook_deliveries (delivery_id) {
+        delivery_id -> Int8,
+        webhook_id -> Varchar,
+        event_id -> Varchar,
+        order_id -> Varchar,
+        status -> Varchar,
+        status_data -> Text,
+        state -> Varchar,
+        attempts -> Int4,
+        last_response_status -> Nullable<Int4>,
+        last_error -> Nullable<Text>,
+        next_attempt_at -> Timestamptz,
+        delivered_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    webhook_attempts (attempt_id) {
+        attempt_id -> Int8,
+        delivery_id -> Int8,
+        response_status -> Nullable<Int4>,
+        error -> Nullable<Text>,
+        duration_ms -> Int4,
+        attempted_at -> Timestamptz,
+    }
+}
+
+diesel::joinable!(api_keys -> users (user_id));
+diesel::joinable!(webhooks -> users (user_id));
+diesel::joinable!(webhook_deliveries -> webhooks (webhook_id));
+diesel::joinable!(webhook_attempts -> webhook_deliveries (delivery_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    api_keys,
+    orders,
+    users,
+    webhook_attempts,
+    webhook_deliveries,
+    webhooks,
+);
@@ -0,0 +1,12 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Use the bundled protoc so builds do not need one installed.
+    if std::env::var_os("PROTOC").is_none() {
+        // SAFETY: build scripts run single-threaded.
+        unsafe { std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?) };
+    }
+    // Orders are entered through the backend's gRPC API.
+    tonic_build::configure()
+        .build_server(false)
+        .compile_protos(&["../backend/proto/orders.proto"], &["../backend/proto"])?;
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use common::pg_pool::PgPool;
+use sha2::{Digest, Sha256};
+
+/// Looks up the backend's API keys, which FIX sessions send as the Logon
+/// Password.
+pub struct ApiKeyStore {
+    pg: PgPool,
+}
+
+impl ApiKeyStore {
+    pub async fn connect(database_url: &str) -> Result<Self, tokio_postgres::Error> {
+        Ok(Self { pg: PgPool::connect(database_url).await? })
+    }
+
+    pub async fn ping(&self) -> Result<(), tokio_postgres::Error> {
+        self.pg.ping().await
+    }
+
+    pub async fn authenticate(&self, api_key: &str) -> Result<Option<String>, tokio_postgres::Error> {
+        let row = self.pg.get().await
+            .query_opt(
+                "SELECT user_id FROM api_keys WHERE key_hash = $1 AND revoked_at IS NULL",
+                &[&hex::encode(Sha256::digest(api_key.as_bytes()))],
+            )
+            .await?;
+        Ok(row.map(|row| row.get(0)))
+    }
+}
@@ -0,0 +1,327 @@
+pub const BEGIN_STRING: &str = "FIX.4.4";
+const SOH: u8 = 0x01;
+const MAX_BODY_LENGTH: usize = 65536;
+
+pub mod tag {
+    pub const AVG_PX: u32 = 6;
+    pub const BEGIN_SEQ_NO: u32 = 7;
+    pub const CL_ORD_ID: u32 = 11;
+    pub const CUM_QTY: u32 = 14;
+    pub const END_SEQ_NO: u32 = 16;
+    pub const EXEC_ID: u32 = 17;
+    pub const LAST_PX: u32 = 31;
+    pub const LAST_QTY: u32 = 32;
+    pub const MSG_SEQ_NUM: u32 = 34;
+    pub const MSG_TYPE: u32 = 35;
+    pub const NEW_SEQ_NO: u32 = 36;
+    pub const ORDER_ID: u32 = 37;
+    pub const ORDER_QTY: u32 = 38;
+    pub const ORD_STATUS: u32 = 39;
+    pub const ORD_TYPE: u32 = 40;
+    pub const ORIG_CL_ORD_ID: u32 = 41;
+    pub const POSS_DUP_FLAG: u32 = 43;
+    pub const REF_SEQ_NUM: u32 = 45;
+    pub const SENDER_COMP_ID: u32 = 49;
+    pub const SENDING_TIME: u32 = 52;
+    pub const SIDE: u32 = 54;
+    pub const SYMBOL: u32 = 55;
+    pub const TARGET_COMP_ID: u32 = 56;
+    pub const TEXT: u32 = 58;
+    pub const TRANSACT_TIME: u32 = 60;
+    pub const ENCRYPT_METHOD: u32 = 98;
+    pub const STOP_PX: u32 = 99;
+    pub const CXL_REJ_REASON: u32 = 102;
+    pub const ORD_REJ_REASON: u32 = 103;
+    pub const HEART_BT_INT: u32 = 108;
+    pub const TEST_REQ_ID: u32 = 112;
+    pub const ORIG_SENDING_TIME: u32 = 122;
+    pub const GAP_FILL_FLAG: u32 = 123;
+    pub const RESET_SEQ_NUM_FLAG: u32 = 141;
+    pub const EXEC_TYPE: u32 = 150;
+    pub const LEAVES_QTY: u32 = 151;
+    pub const CASH_ORDER_QTY: u32 = 152;
+    pub const REF_TAG_ID: u32 = 371;
+    pub const REF_MSG_TYPE: u32 = 372;
+    pub const SESSION_REJECT_REASON: u32 = 373;
+    pub const BUSINESS_REJECT_REASON: u32 = 380;
+    pub const CXL_REJ_RESPONSE_TO: u32 = 434;
+    pub const SECONDARY_EXEC_ID: u32 = 527;
+    pub const PASSWORD: u32 = 554;
+}
+
+pub mod msg_type {
+    pub const HEARTBEAT: &str = "0";
+    pub const TEST_REQUEST: &str = "1";
+    pub const RESEND_REQUEST: &str = "2";
+    pub const REJECT: &str = "3";
+    pub const SEQUENCE_RESET: &str = "4";
+    pub const LOGOUT: &str = "5";
+    pub const EXECUTION_REPORT: &str = "8";
+    pub const ORDER_CANCEL_REJECT: &str = "9";
+    pub const LOGON: &str = "A";
+    pub const NEW_ORDER_SINGLE: &str = "D";
+    pub const ORDER_CANCEL_REQUEST: &str = "F";
+    pub const BUSINESS_MESSAGE_REJECT: &str = "j";
+
+    /// Session-level messages are never resent; a gap fill covers them.
+    pub fn is_admin(msg_type: &str) -> bool {
+        matches!(msg_type, HEARTBEAT | TEST_REQUEST | RESEND_REQUEST | REJECT | SEQUENCE_RESET | LOGOUT | LOGON)
+    }
+}
+
+/// A FIX message without its BeginString, BodyLength and CheckSum, which
+/// are added by `encode` and checked by `take_frame`. Fields keep their
+/// order, starting with MsgType.
+#[derive(Debug, Clone)]
+pub struct Message {
+    fields: Vec<(u32, String)>,
+}
+
+impl Message {
+    pub fn new(msg_type: &str) -> Self {
+        Self { fields: vec![(tag::MSG_TYPE, msg_type.to_string())] }
+    }
+
+    pub fn with(mut self, tag: u32, value: impl ToString) -> Self {
+        self.fields.push((tag, value.to_string()));
+        self
+    }
+
+    pub fn with_opt(self, tag: u32, value: Option<impl ToString>) -> Self {
+        match value {
+            Some(value) => self.with(tag, value),
+            None => self,
+        }
+    }
+
+    pub fn get(&self, tag: u32) -> Option<&str> {
+        self.fields.iter().find(|(t, _)| *t == tag).map(|(_, value)| value.as_str())
+    }
+
+    pub fn msg_type(&self) -> &str {
+        self.get(tag::MSG_TYPE).unwrap_or_default()
+    }
+
+    pub fn seq_num(&self) -> Option<u64> {
+        self.get(tag::MSG_SEQ_NUM)?.parse().ok()
+    }
+
+    pub fn flag(&self, tag: u32) -> bool {
+        self.get(tag) == Some("Y")
+    }
+
+    /// The fields after MsgType, skipping the standard header.
+    pub fn body(&self) -> impl Iterator<Item = &(u32, String)> {
+        const HEADER: &[u32] = &[
+            tag::MSG_TYPE, tag::SENDER_COMP_ID, tag::TARGET_COMP_ID, tag::MSG_SEQ_NUM,
+            tag::SENDING_TIME, tag::POSS_DUP_FLAG, tag::ORIG_SENDING_TIME,
+        ];
+        self.fields.iter().filter(|(tag, _)| !HEADER.contains(tag))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (tag, value) in &self.fields {
+            body.extend_from_slice(format!("{}={}", tag, value).as_bytes());
+            body.push(SOH);
+        }
+
+        let mut out = format!("8={}\x019={}\x01", BEGIN_STRING, body.len()).into_bytes();
+        out.extend_from_slice(&body);
+        let checksum = checksum(&out);
+        out.extend_from_slice(format!("10={:03}\x01", checksum).as_bytes());
+        out
+    }
+}
+
+/// Takes the next complete message off the front of `buf`. Returns `None`
+/// until a whole message has arrived, and an error for a garbled one, which
+/// the session ignores as FIX requires.
+pub fn take_frame(buf: &mut Vec<u8>) -> Option<Result<Message, String>> {
+    let prefix = format!("8={}\x019=", BEGIN_STRING);
+    let start = find(buf, prefix.as_bytes());
+    match start {
+        Some(0) => {}
+        Some(start) => {
+            buf.drain(..start);
+        }
+        None => {
+            // Keep a tail that may be the start of the next BeginString.
+            let keep = buf.len().min(prefix.len() - 1);
+            buf.drain(..buf.len() - keep);
+            return None;
+        }
+    }
+
+    let length_end = prefix.len() + buf[prefix.len()..].iter().position(|b| *b == SOH)?;
+    let body_length = std::str::from_utf8(&buf[prefix.len()..length_end])
+        .ok()
+        .and_then(|length| length.parse::<usize>().ok())
+        .filter(|length| *length <= MAX_BODY_LENGTH);
+    let Some(body_length) = body_length else {
+        buf.drain(..1);
+        return Some(Err("invalid BodyLength".to_string()));
+    };
+
+    let body_end = length_end + 1 + body_length;
+    let frame_end = body_end + "10=000\x01".len();
+    if buf.len() < frame_end {
+        return None;
+    }
+    let trailer = &buf[body_end..frame_end];
+    if !trailer.starts_with(b"10=") || trailer.last() != Some(&SOH) {
+        // Resynchronise on the next BeginString rather than trusting the length.
+        buf.drain(..1);
+        return Some(Err("BodyLength does not match the message".to_string()));
+    }
+    let frame: Vec<u8> = buf.drain(..frame_end).collect();
+    let trailer = &frame[body_end..];
+    let expected = std::str::from_utf8(&trailer[3..6]).ok().and_then(|sum| sum.parse::<u8>().ok());
+    if expected != Some(checksum(&frame[..body_end])) {
+        return Some(Err("invalid CheckSum".to_string()));
+    }
+
+    Some(parse_fields(&frame[length_end + 1..body_end]))
+}
+
+fn parse_fields(body: &[u8]) -> Result<Message, String> {
+    let body = std::str::from_utf8(body).map_err(|_| "message is not valid UTF-8".to_string())?;
+    let mut fields = Vec::new();
+    for field in body.split('\x01').filter(|field| !field.is_empty()) {
+        let (tag, value) = field.split_once('=').ok_or_else(|| format!("malformed field {}", field))?;
+        let tag = tag.parse::<u32>().map_err(|_| format!("malformed tag {}", tag))?;
+        fields.push((tag, value.to_string()));
+    }
+
+    if fields.first().map(|(tag, _)| *tag) != Some(tag::MSG_TYPE) {
+        return Err("MsgType must be the third field".to_string());
+    }
+    Ok(Message { fields })
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logon() -> Message {
+        Message::new(msg_type::LOGON)
+            .with(tag::SENDER_COMP_ID, "CLIENT")
+            .with(tag::TARGET_COMP_ID, "GATEWAY")
+            .with(tag::MSG_SEQ_NUM, 1)
+            .with(tag::HEART_BT_INT, 30)
+    }
+
+    #[test]
+    fn encode_adds_body_length_and_checksum() {
+        let encoded = logon().encode();
+        let text = String::from_utf8(encoded.clone()).unwrap();
+        assert!(text.starts_with("8=FIX.4.4\x019="));
+
+        let body_start = text.find("35=").unwrap();
+        let trailer_start = text.rfind("10=").unwrap();
+        let body_length: usize = text["8=FIX.4.4\x019=".len()..body_start - 1].parse().unwrap();
+        assert_eq!(body_length, trailer_start - body_start);
+
+        let expected = checksum(&encoded[..trailer_start]);
+        assert_eq!(&text[trailer_start..], format!("10={:03}\x01", expected));
+    }
+
+    #[test]
+    fn round_trips_through_take_frame() {
+        let mut buf = logon().encode();
+        let msg = take_frame(&mut buf).unwrap().unwrap();
+        assert!(buf.is_empty());
+        assert_eq!(msg.msg_type(), msg_type::LOGON);
+        assert_eq!(msg.seq_num(), Some(1));
+        assert_eq!(msg.get(tag::HEART_BT_INT), Some("30"));
+    }
+
+    #[test]
+    fn waits_for_a_complete_frame() {
+        let encoded = logon().encode();
+        let mut buf = encoded[..encoded.len() - 4].to_vec();
+        assert!(take_frame(&mut buf).is_none());
+
+        buf.extend_from_slice(&encoded[encoded.len() - 4..]);
+        assert!(take_frame(&mut buf).unwrap().is_ok());
+    }
+
+    #[test]
+    fn takes_back_to_back_frames_in_order() {
+        let mut buf = logon().encode();
+        buf.extend(Message::new(msg_type::HEARTBEAT).with(tag::MSG_SEQ_NUM, 2).encode());
+
+        assert_eq!(take_frame(&mut buf).unwrap().unwrap().seq_num(), Some(1));
+        assert_eq!(take_frame(&mut buf).unwrap().unwrap().msg_type(), msg_type::HEARTBEAT);
+        assert!(take_frame(&mut buf).is_none());
+    }
+
+    #[test]
+    fn skips_garbage_before_begin_string() {
+        let mut buf = b"noise\x01".to_vec();
+        buf.extend(logon().encode());
+        assert!(take_frame(&mut buf).unwrap().is_ok());
+
+        let mut garbage = b"no frame here".to_vec();
+        assert!(take_frame(&mut garbage).is_none());
+        assert!(garbage.len() < "8=FIX.4.4\x019=".len());
+    }
+
+    #[test]
+    fn rejects_a_bad_checksum_and_consumes_the_frame() {
+        let mut buf = logon().encode();
+        let at = buf.len() - 2;
+        buf[at] = if buf[at] == b'9' { b'0' } else { buf[at] + 1 };
+        buf.extend(Message::new(msg_type::HEARTBEAT).with(tag::MSG_SEQ_NUM, 2).encode());
+
+        assert_eq!(take_frame(&mut buf).unwrap().unwrap_err(), "invalid CheckSum");
+        assert_eq!(take_frame(&mut buf).unwrap().unwrap().seq_num(), Some(2));
+    }
+
+    #[test]
+    fn resynchronises_after_a_wrong_body_length() {
+        let encoded = logon().encode();
+        let text = String::from_utf8(encoded).unwrap();
+        let length_start = "8=FIX.4.4\x019=".len();
+        let length_end = length_start + text[length_start..].find('\x01').unwrap();
+        let length: usize = text[length_start..length_end].parse().unwrap();
+        let mut buf = format!("{}{}{}", &text[..length_start], length - 3, &text[length_end..]).into_bytes();
+        buf.extend(Message::new(msg_type::HEARTBEAT).with(tag::MSG_SEQ_NUM, 2).encode());
+
+        assert_eq!(take_frame(&mut buf).unwrap().unwrap_err(), "BodyLength does not match the message");
+        assert_eq!(take_frame(&mut buf).unwrap().unwrap().seq_num(), Some(2));
+    }
+
+    #[test]
+    fn rejects_oversized_and_non_numeric_body_lengths() {
+        let mut buf = format!("8=FIX.4.4\x019={}\x01", MAX_BODY_LENGTH + 1).into_bytes();
+        assert_eq!(take_frame(&mut buf).unwrap().unwrap_err(), "invalid BodyLength");
+
+        let mut buf = b"8=FIX.4.4\x019=abc\x01".to_vec();
+        assert_eq!(take_frame(&mut buf).unwrap().unwrap_err(), "invalid BodyLength");
+    }
+
+    #[test]
+    fn msg_type_must_come_first() {
+        assert!(parse_fields(b"34=1\x0135=0\x01").is_err());
+        assert!(parse_fields(b"35=0\x01bad\x01").is_err());
+        assert!(parse_fields(b"35=0\x01x=1\x01").is_err());
+    }
+
+    #[test]
+    fn body_skips_the_standard_header() {
+        let msg = logon().with(tag::POSS_DUP_FLAG, "Y");
+        let body: Vec<u32> = msg.body().map(|(tag, _)| *tag).collect();
+        assert_eq!(body, [tag::HEART_BT_INT]);
+        assert!(msg.flag(tag::POSS_DUP_FLAG));
+        assert!(!msg.flag(tag::GAP_FILL_FLAG));
+    }
+}
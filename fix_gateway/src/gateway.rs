@@ -0,0 +1,158 @@
+use crate::auth::ApiKeyStore;
+use crate::intake::Intake;
+use crate::session::Session;
+use common::streams::StreamReaders;
+use common::telemetry::{self, TraceContext};
+use redis::aio::ConnectionManager;
+use redis::from_redis_value;
+use redis::streams::StreamId;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::Instrument;
+
+/// State shared by every FIX connection. Sessions are keyed by the
+/// counterparty's SenderCompID and outlive their connections, so sequence
+/// numbers and unsent ExecutionReports carry over a reconnect. They are
+/// kept in memory and do not survive a gateway restart.
+pub struct Gateway {
+    pub comp_id: String,
+    pub tokens: Vec<String>,
+    pub api_keys: ApiKeyStore,
+    pub intake: Intake,
+    pub redis: ConnectionManager,
+    pub streams: StreamReaders,
+    sessions: Mutex<HashMap<String, Arc<Mutex<Session>>>>,
+    /// Open orders by `order_id`, to route status updates to their session.
+    pub orders: Mutex<HashMap<String, Arc<Mutex<Session>>>>,
+    /// Locked after `orders` when both are held.
+    unrouted: Mutex<Unrouted>,
+}
+
+/// The router can report an order before the backend's answer to
+/// SubmitOrder reaches the gateway. While a user has orders being
+/// submitted, updates for that user's unknown orders are held until the
+/// submissions finish, then handed to the session of the order they belong
+/// to or dropped.
+#[derive(Default)]
+struct Unrouted {
+    /// Submissions in flight, by user.
+    submitting: HashMap<String, usize>,
+    /// Held `(event_id, update)`s by `order_id`, with the order's user.
+    held: HashMap<String, (String, Vec<(String, serde_json::Value)>)>,
+}
+
+impl Gateway {
+    pub fn new(
+        comp_id: String,
+        tokens: Vec<String>,
+        api_keys: ApiKeyStore,
+        intake: Intake,
+        redis_client: redis::Client,
+        redis: ConnectionManager,
+    ) -> Self {
+        Self {
+            comp_id,
+            tokens,
+            api_keys,
+            intake,
+            streams: StreamReaders::new(redis_client),
+            redis,
+            sessions: Mutex::new(HashMap::new()),
+            orders: Mutex::new(HashMap::new()),
+            unrouted: Mutex::new(Unrouted::default()),
+        }
+    }
+
+    /// The counterparty's session, created on its first Logon and bound to
+    /// the user of that Logon's API key.
+    pub async fn session(&self, counterparty: &str, user_id: &str) -> Arc<Mutex<Session>> {
+        self.sessions
+            .lock()
+            .await
+            .entry(counterparty.to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(Session::new(&self.comp_id, counterparty, user_id))))
+            .clone()
+    }
+
+    /// Starts holding the user's updates for orders not routed yet. Every
+    /// call is followed by one to `submitted`.
+    pub async fn submitting(&self, user_id: &str) {
+        *self.unrouted.lock().await.submitting.entry(user_id.to_string()).or_default() += 1;
+    }
+
+    /// Ends a submission, routing the accepted order's updates to the
+    /// session. Returns the updates that arrived before it was routed.
+    pub async fn submitted(&self, user_id: &str, accepted: Option<(&str, &Arc<Mutex<Session>>)>) -> Vec<(String, serde_json::Value)> {
+        let mut orders = self.orders.lock().await;
+        let mut unrouted = self.unrouted.lock().await;
+
+        let early = match accepted {
+            Some((order_id, session)) => {
+                orders.insert(order_id.to_string(), session.clone());
+                unrouted.held.remove(order_id).map(|(_, updates)| updates).unwrap_or_default()
+            }
+            None => Vec::new(),
+        };
+        let submitting = unrouted.submitting.entry(user_id.to_string()).or_default();
+        *submitting = submitting.saturating_sub(1);
+        if *submitting == 0 {
+            unrouted.submitting.remove(user_id);
+            unrouted.held.retain(|_, (user, _)| user != user_id);
+        }
+        early
+    }
+
+    /// Follows `status_updates` and turns updates for FIX orders into
+    /// ExecutionReports on their session.
+    pub async fn listen(self: Arc<Self>) {
+        let mut reader = self.streams.reader("execution_reports", "status_updates", "$");
+
+        loop {
+            for message in reader.next().await {
+                self.report(&message).await;
+            }
+        }
+    }
+
+    async fn report(&self, message: &StreamId) {
+        let Some(data) = message.map.get("status_data").and_then(|value| from_redis_value::<String>(value).ok()) else {
+            return;
+        };
+        let Ok(update) = serde_json::from_str::<serde_json::Value>(&data) else {
+            return;
+        };
+        let Some(order_id) = update["order_id"].as_str() else {
+            return;
+        };
+
+        let session = {
+            let orders = self.orders.lock().await;
+            let session = orders.get(order_id).cloned();
+            if session.is_none() {
+                let mut unrouted = self.unrouted.lock().await;
+                let user_id = update["user_id"].as_str().unwrap_or_default();
+                if unrouted.submitting.contains_key(user_id) {
+                    let (_, held) = unrouted.held.entry(order_id.to_string()).or_insert_with(|| (user_id.to_string(), Vec::new()));
+                    held.push((message.id.clone(), update.clone()));
+                }
+            }
+            session
+        };
+        let Some(session) = session else {
+            return;
+        };
+
+        let span = tracing::info_span!("execution_report", order_id, status = update["status"].as_str(), trace_id = tracing::field::Empty);
+        let trace_context = serde_json::from_value::<TraceContext>(update["trace_context"].clone()).ok();
+        telemetry::follow(&span, trace_context.as_ref());
+        async {
+            let closed = session.lock().await.report(order_id, &message.id, &update);
+            if closed {
+                self.orders.lock().await.remove(order_id);
+            }
+        }
+        .instrument(span)
+        .await
+    }
+}
@@ -0,0 +1,87 @@
+use crate::gateway::Gateway;
+use crate::metrics;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use common::health::{probe, status_code, StreamLimits, PROBE_TIMEOUT};
+use common::streams::ReaderReport;
+use redis::aio::ConnectionManager;
+use std::sync::Arc;
+
+/// `/healthz` and `/readyz` for orchestrators, and `/metrics` for
+/// Prometheus, served on `HEALTH_PORT`.
+pub struct Health {
+    limits: StreamLimits,
+    gateway: Arc<Gateway>,
+}
+
+impl Health {
+    pub fn new(gateway: Arc<Gateway>) -> Self {
+        Self { limits: StreamLimits::from_env(), gateway }
+    }
+
+    pub async fn serve(self: Arc<Self>, port: String) {
+        let app = Router::new()
+            .route("/healthz", get(healthz))
+            .route("/readyz", get(readyz))
+            .route("/metrics", get(render_metrics))
+            .with_state(self);
+        common::health::serve(app, port).await;
+    }
+
+    /// The reader's state. The connection has no command timeout of its own,
+    /// so a lag check that hangs is dropped and the reader reported without
+    /// lag.
+    async fn streams(&self, redis_ok: bool) -> Vec<ReaderReport> {
+        let mut conn = self.gateway.redis.clone();
+        let streams = if redis_ok {
+            tokio::time::timeout(PROBE_TIMEOUT, self.gateway.streams.report(Some(&mut conn))).await.ok()
+        } else {
+            None
+        };
+        match streams {
+            Some(streams) => streams,
+            None => self.gateway.streams.report(None::<&mut ConnectionManager>).await,
+        }
+    }
+}
+
+/// Fails when the status listener has stopped reading, so no session gets
+/// its ExecutionReports. Postgres and Redis connections are reopened by
+/// themselves.
+async fn healthz(State(health): State<Arc<Health>>) -> (StatusCode, Json<serde_json::Value>) {
+    let stalled = health.limits.stalled(&health.gateway.streams);
+    let healthy = stalled.is_empty();
+
+    let body = serde_json::json!({
+        "status": if healthy { "ok" } else { "unhealthy" },
+        "stalled": stalled,
+    });
+    (status_code(healthy), Json(body))
+}
+
+/// Fails while Redis or Postgres is unreachable, or the status listener is
+/// disconnected or further behind than `MAX_STREAM_LAG_MS`.
+async fn readyz(State(health): State<Arc<Health>>) -> (StatusCode, Json<serde_json::Value>) {
+    let mut conn = health.gateway.redis.clone();
+    let redis = probe(redis::cmd("PING").query_async::<_, String>(&mut conn)).await;
+    let postgres = probe(health.gateway.api_keys.ping()).await;
+
+    let streams = health.streams(redis.ok).await;
+
+    let ready = redis.ok && postgres.ok && health.limits.caught_up(&streams);
+
+    let body = serde_json::json!({
+        "status": if ready { "ready" } else { "not_ready" },
+        "redis": redis,
+        "postgres": postgres,
+        "streams": streams,
+    });
+    (status_code(ready), Json(body))
+}
+
+async fn render_metrics(State(health): State<Arc<Health>>) -> String {
+    metrics::set_streams(&health.streams(true).await);
+    metrics::render()
+}
@@ -0,0 +1,154 @@
+use crate::orders::{ORD_REJ_DUPLICATE_ORDER, ORD_REJ_EXCHANGE_CLOSED, ORD_REJ_INCORRECT_QUANTITY, ORD_REJ_ORDER_EXCEEDS_LIMIT, ORD_REJ_OTHER, ORD_REJ_UNKNOWN_SYMBOL};
+use std::time::Duration;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Request, Status};
+
+pub mod proto {
+    tonic::include_proto!("orders.v1");
+}
+
+use proto::order_service_client::OrderServiceClient;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+/// Covers the backend's wait for the router's answer to a cancel.
+const CALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The backend's gRPC order API. FIX orders are entered and cancelled
+/// through it with the session's API key, so they get the same validation,
+/// rate limits, trading halts and ownership checks as orders over HTTP.
+#[derive(Clone)]
+pub struct Intake {
+    client: OrderServiceClient<Channel>,
+}
+
+/// Why the backend refused an order, as the ExecutionReport rejecting it
+/// carries it.
+#[derive(Debug, PartialEq)]
+pub struct Refusal {
+    /// Label of `fix_orders_rejected_total`.
+    pub metric: &'static str,
+    pub reason: u32,
+    pub text: String,
+}
+
+impl Intake {
+    /// Connects on first use, so the gateway starts while the backend is
+    /// still coming up.
+    pub fn connect_lazy(url: &str) -> Result<Self, tonic::transport::Error> {
+        let channel = Endpoint::from_shared(url.to_string())?
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(CALL_TIMEOUT)
+            .connect_lazy();
+        Ok(Self { client: OrderServiceClient::new(channel) })
+    }
+
+    pub async fn submit(&self, api_key: &str, order: proto::SubmitOrderRequest) -> Result<proto::SubmitOrderResponse, Status> {
+        let response = self.client.clone().submit_order(authorized(api_key, order)).await?;
+        Ok(response.into_inner())
+    }
+
+    /// The order's latest status update.
+    pub async fn status(&self, api_key: &str, order_id: &str) -> Result<proto::Order, Status> {
+        let request = proto::GetOrderRequest { order_id: order_id.to_string() };
+        let response = self.client.clone().get_order(authorized(api_key, request)).await?;
+        Ok(response.into_inner())
+    }
+
+    /// Succeeds once the router has cancelled the order; its `cancelled`
+    /// update follows on `status_updates`.
+    pub async fn cancel(&self, api_key: &str, order_id: &str) -> Result<(), Status> {
+        let request = proto::CancelOrderRequest { order_id: order_id.to_string() };
+        self.client.clone().cancel_order(authorized(api_key, request)).await?;
+        Ok(())
+    }
+}
+
+/// A key that is not a valid metadata value is left out, and the backend
+/// refuses the call as unauthenticated.
+fn authorized<T>(api_key: &str, message: T) -> Request<T> {
+    let mut request = Request::new(message);
+    if let Ok(api_key) = api_key.parse() {
+        request.metadata_mut().insert("x-api-key", api_key);
+    }
+    request
+}
+
+/// Maps a failed SubmitOrder onto an OrdRejReason. The backend puts the
+/// JSON body of the matching HTTP error in the status message.
+pub fn refusal(status: &Status) -> Refusal {
+    let body = serde_json::from_str::<serde_json::Value>(status.message()).unwrap_or_default();
+    let refusal = |metric, reason, text: String| Refusal { metric, reason, text };
+
+    match status.code() {
+        Code::InvalidArgument => {
+            let violations = body["violations"].as_array().cloned().unwrap_or_default();
+            let reason = match violations.first().and_then(|violation| violation["field"].as_str()) {
+                Some("amount") => ORD_REJ_INCORRECT_QUANTITY,
+                Some("token_in" | "token_out") => ORD_REJ_UNKNOWN_SYMBOL,
+                _ => ORD_REJ_OTHER,
+            };
+            let text = violations
+                .iter()
+                .filter_map(|violation| violation["message"].as_str())
+                .collect::<Vec<_>>()
+                .join("; ");
+            refusal("invalid", reason, if text.is_empty() { status.message().to_string() } else { text })
+        }
+        Code::AlreadyExists => refusal("duplicate", ORD_REJ_DUPLICATE_ORDER, "ClOrdID was used for a different order".to_string()),
+        Code::ResourceExhausted => {
+            let limit = body["limit"].as_str().unwrap_or("rate_limited");
+            refusal("throttled", ORD_REJ_ORDER_EXCEEDS_LIMIT, format!("throttled: {}", limit))
+        }
+        Code::Unavailable if body["error"] == "trading_halted" => {
+            let halt = body["halt"]["reason"].as_str().unwrap_or("trading halted");
+            refusal("halted", ORD_REJ_EXCHANGE_CLOSED, format!("trading halted: {}", halt))
+        }
+        Code::Unauthenticated => refusal("unauthenticated", ORD_REJ_OTHER, "API key is no longer valid".to_string()),
+        _ => refusal("unavailable", ORD_REJ_OTHER, "order intake unavailable".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validation_failures_name_the_first_field() {
+        let body = serde_json::json!({
+            "error": "validation_failed",
+            "violations": [
+                { "field": "amount", "code": "amount_not_positive", "message": "amount must be a number greater than 0" },
+                { "field": "max_slippage", "code": "slippage_out_of_range", "message": "max_slippage must be between 0.01 and 0.5 (exclusive)" },
+            ],
+        });
+        let refusal = refusal(&Status::invalid_argument(body.to_string()));
+        assert_eq!(refusal.metric, "invalid");
+        assert_eq!(refusal.reason, ORD_REJ_INCORRECT_QUANTITY);
+        assert_eq!(
+            refusal.text,
+            "amount must be a number greater than 0; max_slippage must be between 0.01 and 0.5 (exclusive)"
+        );
+    }
+
+    #[test]
+    fn throttles_halts_and_conflicts_have_their_own_reasons() {
+        let throttled = serde_json::json!({ "error": "rate_limited", "limit": "notional_per_minute", "retry_after_ms": 500 });
+        let refused = refusal(&Status::resource_exhausted(throttled.to_string()));
+        assert_eq!((refused.reason, refused.text.as_str()), (ORD_REJ_ORDER_EXCEEDS_LIMIT, "throttled: notional_per_minute"));
+
+        let halted = serde_json::json!({ "error": "trading_halted", "halt": { "reason": "maintenance" } });
+        let refused = refusal(&Status::unavailable(halted.to_string()));
+        assert_eq!((refused.reason, refused.text.as_str()), (ORD_REJ_EXCHANGE_CLOSED, "trading halted: maintenance"));
+
+        let conflict = serde_json::json!({ "error": "idempotency_key_reused", "client_order_id": "c-1" });
+        assert_eq!(refusal(&Status::already_exists(conflict.to_string())).reason, ORD_REJ_DUPLICATE_ORDER);
+    }
+
+    #[test]
+    fn anything_else_is_the_intake_being_unavailable() {
+        for status in [Status::unavailable("redis unavailable"), Status::cancelled("Timeout expired"), Status::internal("")] {
+            let refused = refusal(&status);
+            assert_eq!((refused.metric, refused.reason), ("unavailable", ORD_REJ_OTHER));
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use prometheus::{register_int_counter, register_int_counter_vec, register_int_gauge, IntCounter, IntCounterVec, IntGauge};
+use std::sync::LazyLock;
+
+pub use common::metrics::{render, set_streams};
+
+pub static SESSIONS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("fix_sessions", "FIX sessions logged on").unwrap()
+});
+
+pub static ORDERS_ACCEPTED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!("fix_orders_accepted_total", "NewOrderSingles accepted by the backend").unwrap()
+});
+
+pub static ORDERS_REJECTED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("fix_orders_rejected_total", "NewOrderSingles refused by the gateway or the backend", &["reason"]).unwrap()
+});
+
+pub fn order_rejected(reason: &str) {
+    ORDERS_REJECTED.with_label_values(&[reason]).inc();
+}
@@ -0,0 +1,129 @@
+use crate::fix::{tag, Message};
+use crate::intake::proto::SubmitOrderRequest;
+
+const MAX_SLIPPAGE: f64 = 0.05;
+
+pub const SIDE_BUY: &str = "1";
+pub const SIDE_SELL: &str = "2";
+const ORD_TYPE_MARKET: &str = "1";
+const ORD_TYPE_STOP: &str = "3";
+const ORD_TYPE_MARKET_IF_TOUCHED: &str = "J";
+
+pub const ORD_REJ_UNKNOWN_SYMBOL: u32 = 1;
+pub const ORD_REJ_EXCHANGE_CLOSED: u32 = 2;
+pub const ORD_REJ_ORDER_EXCEEDS_LIMIT: u32 = 3;
+pub const ORD_REJ_DUPLICATE_ORDER: u32 = 6;
+pub const ORD_REJ_UNSUPPORTED_CHARACTERISTIC: u32 = 11;
+pub const ORD_REJ_INCORRECT_QUANTITY: u32 = 13;
+pub const ORD_REJ_OTHER: u32 = 99;
+
+/// An order entered over FIX, as the session reports it back.
+#[derive(Debug, Clone)]
+pub struct FixOrder {
+    /// The backend's id for the order, set once it accepts it.
+    pub order_id: String,
+    pub cl_ord_id: String,
+    pub symbol: String,
+    pub side: String,
+    /// `token_in` amount: OrderQty of the base token for a sell,
+    /// CashOrderQty of the quote token for a buy.
+    pub qty: f64,
+    pub ord_type: String,
+    pub stop_px: Option<f64>,
+    pub ord_status: char,
+    pub acknowledged: bool,
+    /// ClOrdID of a cancel request the router has not answered yet.
+    pub pending_cancel: Option<String>,
+}
+
+impl FixOrder {
+    pub fn is_buy(&self) -> bool {
+        self.side == SIDE_BUY
+    }
+
+    pub fn is_closed(&self) -> bool {
+        matches!(self.ord_status, '2' | '4' | '8')
+    }
+}
+
+pub enum Rejection {
+    /// Session-level Reject for a required tag that is missing.
+    MissingTag(u32),
+    /// ExecutionReport rejecting the order, with an OrdRejReason.
+    Order { reason: u32, text: String },
+}
+
+/// Maps a NewOrderSingle onto the backend's SubmitOrder request, with the
+/// ClOrdID as its `client_order_id`. `Symbol` is `BASE/QUOTE`. A sell gives
+/// `OrderQty` of the base token, and a buy gives `CashOrderQty` of the quote
+/// token. Stop and market-if-touched orders become stop-loss and take-profit
+/// orders, with `StopPx` converted to the router's `token_out` per `token_in`
+/// rate.
+pub fn parse_new_order(msg: &Message, tokens: &[String]) -> Result<(FixOrder, SubmitOrderRequest), Rejection> {
+    let required = |tag: u32| msg.get(tag).filter(|value| !value.is_empty()).ok_or(Rejection::MissingTag(tag));
+    let cl_ord_id = required(tag::CL_ORD_ID)?;
+    let symbol = required(tag::SYMBOL)?;
+    let side = required(tag::SIDE)?;
+    let ord_type = required(tag::ORD_TYPE)?;
+
+    let (base, quote) = symbol
+        .split_once('/')
+        .filter(|(base, quote)| base != quote && [base, quote].iter().all(|token| tokens.iter().any(|t| t == *token)))
+        .ok_or_else(|| order_rejection(ORD_REJ_UNKNOWN_SYMBOL, format!("Symbol must be BASE/QUOTE of {}", tokens.join(", "))))?;
+
+    let (token_in, token_out, qty_tag) = match side {
+        SIDE_SELL => (base, quote, tag::ORDER_QTY),
+        SIDE_BUY => (quote, base, tag::CASH_ORDER_QTY),
+        _ => return Err(order_rejection(ORD_REJ_UNSUPPORTED_CHARACTERISTIC, "Side must be 1 (buy) or 2 (sell)")),
+    };
+    let qty = required(qty_tag)?
+        .parse::<f64>()
+        .ok()
+        .filter(|qty| qty.is_finite() && *qty > 0.0)
+        .ok_or_else(|| order_rejection(ORD_REJ_INCORRECT_QUANTITY, "quantity must be a positive number"))?;
+
+    let (order_type, stop_px) = match ord_type {
+        ORD_TYPE_MARKET => ("market", None),
+        ORD_TYPE_STOP | ORD_TYPE_MARKET_IF_TOUCHED => {
+            let stop_px = required(tag::STOP_PX)?
+                .parse::<f64>()
+                .ok()
+                .filter(|px| px.is_finite() && *px > 0.0)
+                .ok_or_else(|| order_rejection(ORD_REJ_OTHER, "StopPx must be a positive number"))?;
+            let order_type = if ord_type == ORD_TYPE_STOP { "stop_loss" } else { "take_profit" };
+            (order_type, Some(stop_px))
+        }
+        _ => return Err(order_rejection(ORD_REJ_UNSUPPORTED_CHARACTERISTIC, "OrdType must be 1 (market), 3 (stop) or J (market if touched)")),
+    };
+    // A buy's rate is base per quote, so a buy stop above the market is a
+    // stop-loss on the inverted price.
+    let trigger_price = stop_px.map(|px| if side == SIDE_BUY { 1.0 / px } else { px });
+
+    let order = FixOrder {
+        order_id: String::new(),
+        cl_ord_id: cl_ord_id.to_string(),
+        symbol: symbol.to_string(),
+        side: side.to_string(),
+        qty,
+        ord_type: ord_type.to_string(),
+        stop_px,
+        ord_status: '0',
+        acknowledged: false,
+        pending_cancel: None,
+    };
+    let request = SubmitOrderRequest {
+        token_in: token_in.to_string(),
+        token_out: token_out.to_string(),
+        amount: qty,
+        order_type: order_type.to_string(),
+        max_slippage: Some(MAX_SLIPPAGE),
+        trigger_price,
+        client_order_id: Some(cl_ord_id.to_string()),
+        ..Default::default()
+    };
+    Ok((order, request))
+}
+
+fn order_rejection(reason: u32, text: impl Into<String>) -> Rejection {
+    Rejection::Order { reason, text: text.into() }
+}
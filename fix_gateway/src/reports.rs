@@ -0,0 +1,114 @@
+use crate::fix::{msg_type, tag, Message};
+use crate::orders::FixOrder;
+
+const EXEC_TYPE_NEW: char = '0';
+const EXEC_TYPE_CANCELED: char = '4';
+const EXEC_TYPE_REJECTED: char = '8';
+const EXEC_TYPE_TRADE: char = 'F';
+const EXEC_TYPE_ORDER_STATUS: char = 'I';
+
+const ORD_STATUS_NEW: char = '0';
+const ORD_STATUS_FILLED: char = '2';
+const ORD_STATUS_CANCELED: char = '4';
+const ORD_STATUS_REJECTED: char = '8';
+
+pub const CXL_REJ_TOO_LATE: u32 = 0;
+pub const CXL_REJ_UNKNOWN_ORDER: u32 = 1;
+pub const CXL_REJ_OTHER: u32 = 99;
+
+/// Translates a `status_updates` entry into an ExecutionReport. The first
+/// update is the order's `New` acknowledgement; later intermediate ones
+/// (`routing`, `submitted`, `triggered`, ...) are order status reports with
+/// the status in Text. Prices are quoted per base token, and a buy's
+/// quantities are converted to the base token it receives.
+pub fn execution_report(order: &mut FixOrder, event_id: &str, update: &serde_json::Value) -> Message {
+    let status = update["status"].as_str().unwrap_or_default();
+    let reason = update["reason"].as_str();
+    let price = update["execution_price"].as_f64().filter(|price| *price > 0.0);
+
+    let (exec_type, ord_status) = match status {
+        "confirmed" => (EXEC_TYPE_TRADE, ORD_STATUS_FILLED),
+        "cancelled" => (EXEC_TYPE_CANCELED, ORD_STATUS_CANCELED),
+        "failed" | "rejected" => (EXEC_TYPE_REJECTED, ORD_STATUS_REJECTED),
+        _ if !order.acknowledged => (EXEC_TYPE_NEW, ORD_STATUS_NEW),
+        _ => (EXEC_TYPE_ORDER_STATUS, ORD_STATUS_NEW),
+    };
+    order.acknowledged = true;
+    order.ord_status = ord_status;
+
+    let cancel_cl_ord_id = if exec_type == EXEC_TYPE_CANCELED { order.pending_cancel.take() } else { None };
+    let report = Message::new(msg_type::EXECUTION_REPORT)
+        .with(tag::ORDER_ID, &order.order_id)
+        .with(tag::CL_ORD_ID, cancel_cl_ord_id.as_deref().unwrap_or(&order.cl_ord_id))
+        .with_opt(tag::ORIG_CL_ORD_ID, cancel_cl_ord_id.as_ref().map(|_| &order.cl_ord_id))
+        .with(tag::EXEC_ID, event_id)
+        .with(tag::EXEC_TYPE, exec_type)
+        .with(tag::ORD_STATUS, ord_status);
+    let report = order_fields(report, order);
+
+    let report = match (exec_type, price) {
+        (EXEC_TYPE_TRADE, Some(price)) => {
+            let (filled, px) = if order.is_buy() { (order.qty * price, 1.0 / price) } else { (order.qty, price) };
+            report
+                .with(tag::LAST_QTY, filled)
+                .with(tag::LAST_PX, px)
+                .with(tag::LEAVES_QTY, 0)
+                .with(tag::CUM_QTY, filled)
+                .with(tag::AVG_PX, px)
+                .with_opt(tag::SECONDARY_EXEC_ID, update["tx_hash"].as_str())
+        }
+        (EXEC_TYPE_NEW | EXEC_TYPE_ORDER_STATUS, _) => report
+            .with(tag::LEAVES_QTY, order.qty)
+            .with(tag::CUM_QTY, 0)
+            .with(tag::AVG_PX, 0)
+            .with(tag::TEXT, status),
+        _ => report
+            .with(tag::LEAVES_QTY, 0)
+            .with(tag::CUM_QTY, 0)
+            .with(tag::AVG_PX, 0)
+            .with_opt(tag::ORD_REJ_REASON, (exec_type == EXEC_TYPE_REJECTED).then_some(crate::orders::ORD_REJ_OTHER))
+            .with_opt(tag::TEXT, reason),
+    };
+    report.with(tag::TRANSACT_TIME, crate::session::utc_timestamp())
+}
+
+/// Rejects a NewOrderSingle that was never published.
+pub fn order_rejected(msg: &Message, reason: u32, text: &str) -> Message {
+    let mut report = Message::new(msg_type::EXECUTION_REPORT)
+        .with(tag::ORDER_ID, "NONE")
+        .with(tag::CL_ORD_ID, msg.get(tag::CL_ORD_ID).unwrap_or_default())
+        .with(tag::EXEC_ID, uuid::Uuid::new_v4())
+        .with(tag::EXEC_TYPE, EXEC_TYPE_REJECTED)
+        .with(tag::ORD_STATUS, ORD_STATUS_REJECTED);
+    for echoed in [tag::SYMBOL, tag::SIDE, tag::ORDER_QTY, tag::CASH_ORDER_QTY, tag::ORD_TYPE] {
+        report = report.with_opt(echoed, msg.get(echoed));
+    }
+    report
+        .with(tag::LEAVES_QTY, 0)
+        .with(tag::CUM_QTY, 0)
+        .with(tag::AVG_PX, 0)
+        .with(tag::ORD_REJ_REASON, reason)
+        .with(tag::TEXT, text)
+        .with(tag::TRANSACT_TIME, crate::session::utc_timestamp())
+}
+
+pub fn cancel_rejected(msg: &Message, order: Option<&FixOrder>, reason: u32, text: &str) -> Message {
+    Message::new(msg_type::ORDER_CANCEL_REJECT)
+        .with(tag::ORDER_ID, order.map_or("NONE", |order| order.order_id.as_str()))
+        .with(tag::CL_ORD_ID, msg.get(tag::CL_ORD_ID).unwrap_or_default())
+        .with(tag::ORIG_CL_ORD_ID, msg.get(tag::ORIG_CL_ORD_ID).unwrap_or_default())
+        .with(tag::ORD_STATUS, order.map_or(ORD_STATUS_REJECTED, |order| order.ord_status))
+        .with(tag::CXL_REJ_RESPONSE_TO, 1)
+        .with(tag::CXL_REJ_REASON, reason)
+        .with(tag::TEXT, text)
+}
+
+fn order_fields(report: Message, order: &FixOrder) -> Message {
+    let qty_tag = if order.is_buy() { tag::CASH_ORDER_QTY } else { tag::ORDER_QTY };
+    report
+        .with(tag::SYMBOL, &order.symbol)
+        .with(tag::SIDE, &order.side)
+        .with(qty_tag, order.qty)
+        .with(tag::ORD_TYPE, &order.ord_type)
+        .with_opt(tag::STOP_PX, order.stop_px)
+}
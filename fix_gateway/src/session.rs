@@ -0,0 +1,710 @@
+use crate::fix::{self, msg_type, tag, Message};
+use crate::gateway::Gateway;
+use crate::intake::{self, proto::SubmitOrderRequest};
+use crate::metrics;
+use crate::orders::{self, FixOrder, Rejection};
+use crate::reports;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::net::tcp::OwnedReadHalf;
+use tokio::sync::{mpsc, Mutex};
+use tokio::time::Instant;
+use tonic::Code;
+use tracing::Instrument;
+
+const LOGON_TIMEOUT: Duration = Duration::from_secs(10);
+/// Application messages kept for ResendRequests; older ones are gap filled.
+const SENT_MESSAGE_LIMIT: usize = 10_000;
+
+const SESSION_REJECT_REQUIRED_TAG_MISSING: u32 = 1;
+const BUSINESS_REJECT_UNSUPPORTED_MESSAGE_TYPE: u32 = 3;
+
+/// One counterparty's FIX session: its sequence numbers, the application
+/// messages sent to it, and the orders it entered.
+pub struct Session {
+    sender_comp_id: String,
+    target_comp_id: String,
+    user_id: String,
+    /// The API key of the latest Logon, which orders are entered with.
+    api_key: String,
+    next_in: u64,
+    next_out: u64,
+    sent: BTreeMap<u64, (String, Message)>,
+    /// Set while a connection is logged on. Messages sent without one are
+    /// only numbered and kept, and reach the counterparty by resend.
+    outbound: Option<mpsc::UnboundedSender<Vec<u8>>>,
+    orders: HashMap<String, FixOrder>,
+    cl_ord_ids: HashMap<String, String>,
+}
+
+#[derive(PartialEq)]
+enum Flow {
+    Continue,
+    Close,
+}
+
+/// Per-connection timers and resend state.
+struct Connection {
+    heartbeat: Duration,
+    last_received: Instant,
+    last_sent: Instant,
+    test_request: Option<Instant>,
+    /// MsgSeqNum that revealed a gap, until the resend catches up to it.
+    resend_until: Option<u64>,
+}
+
+impl Session {
+    pub fn new(sender_comp_id: &str, target_comp_id: &str, user_id: &str) -> Self {
+        Self {
+            sender_comp_id: sender_comp_id.to_string(),
+            target_comp_id: target_comp_id.to_string(),
+            user_id: user_id.to_string(),
+            api_key: String::new(),
+            next_in: 1,
+            next_out: 1,
+            sent: BTreeMap::new(),
+            outbound: None,
+            orders: HashMap::new(),
+            cl_ord_ids: HashMap::new(),
+        }
+    }
+
+    /// Numbers the message and writes it to the connection, keeping
+    /// application messages for resends.
+    pub fn send(&mut self, body: Message) {
+        let seq = self.next_out;
+        self.next_out += 1;
+        let sending_time = utc_timestamp();
+        self.write(&self.frame(&body, seq, &sending_time, None));
+
+        if !msg_type::is_admin(body.msg_type()) {
+            self.sent.insert(seq, (sending_time, body));
+            if self.sent.len() > SENT_MESSAGE_LIMIT {
+                self.sent.pop_first();
+            }
+        }
+    }
+
+    /// Reports a status update for one of the session's orders. Returns
+    /// true once the order is closed.
+    pub fn report(&mut self, order_id: &str, event_id: &str, update: &serde_json::Value) -> bool {
+        let Some(order) = self.orders.get_mut(order_id) else {
+            return true;
+        };
+        let report = reports::execution_report(order, event_id, update);
+        let closed = order.is_closed();
+        self.send(report);
+        closed
+    }
+
+    fn write(&self, msg: &Message) {
+        if let Some(outbound) = &self.outbound {
+            let _ = outbound.send(msg.encode());
+        }
+    }
+
+    fn frame(&self, body: &Message, seq: u64, sending_time: &str, orig_sending_time: Option<&str>) -> Message {
+        let mut msg = Message::new(body.msg_type())
+            .with(tag::SENDER_COMP_ID, &self.sender_comp_id)
+            .with(tag::TARGET_COMP_ID, &self.target_comp_id)
+            .with(tag::MSG_SEQ_NUM, seq)
+            .with(tag::SENDING_TIME, sending_time);
+        if let Some(orig_sending_time) = orig_sending_time {
+            msg = msg.with(tag::POSS_DUP_FLAG, "Y").with(tag::ORIG_SENDING_TIME, orig_sending_time);
+        }
+        body.body().fold(msg, |msg, (tag, value)| msg.with(*tag, value))
+    }
+
+    /// Answers a ResendRequest: kept application messages go out again as
+    /// possible duplicates, and everything else is covered by gap fills.
+    fn resend(&self, begin: u64, end: u64) {
+        let last = self.next_out - 1;
+        let end = if end == 0 || end > last { last } else { end };
+        if begin == 0 || begin > end {
+            return;
+        }
+
+        let mut next = begin;
+        for (seq, (orig_sending_time, body)) in self.sent.range(begin..=end) {
+            if *seq > next {
+                self.write(&self.gap_fill(next, *seq));
+            }
+            self.write(&self.frame(body, *seq, &utc_timestamp(), Some(orig_sending_time)));
+            next = seq + 1;
+        }
+        if next <= end {
+            self.write(&self.gap_fill(next, end + 1));
+        }
+    }
+
+    fn gap_fill(&self, seq: u64, new_seq: u64) -> Message {
+        let body = Message::new(msg_type::SEQUENCE_RESET)
+            .with(tag::GAP_FILL_FLAG, "Y")
+            .with(tag::NEW_SEQ_NO, new_seq);
+        let now = utc_timestamp();
+        self.frame(&body, seq, &now, Some(&now))
+    }
+
+    /// Starts both sides over at 1, as on a ResetSeqNumFlag Logon. Closed
+    /// orders are forgotten, so their ClOrdIDs can be used again.
+    fn reset(&mut self) {
+        self.next_in = 1;
+        self.next_out = 1;
+        self.sent.clear();
+        self.orders.retain(|_, order| !order.is_closed());
+        self.cl_ord_ids.retain(|_, order_id| self.orders.contains_key(order_id));
+    }
+}
+
+/// Serves one TCP connection: a Logon, then messages until Logout,
+/// disconnect or a missed heartbeat.
+pub async fn run(gateway: Arc<Gateway>, stream: TcpStream) {
+    let (mut reader, mut writer) = stream.into_split();
+    let mut buf = Vec::new();
+
+    let logon = match tokio::time::timeout(LOGON_TIMEOUT, read_message(&mut reader, &mut buf)).await {
+        Ok(Some(msg)) if msg.msg_type() == msg_type::LOGON => msg,
+        _ => return,
+    };
+    let counterparty = logon.get(tag::SENDER_COMP_ID).unwrap_or_default().to_string();
+
+    let (outbound, mut frames) = mpsc::unbounded_channel();
+    let (session, mut conn) = match log_on(&gateway, &logon, outbound).await {
+        Ok(logged_on) => logged_on,
+        Err(text) => {
+            tracing::info!(counterparty = %counterparty, reason = %text, "FIX logon refused");
+            let _ = writer.write_all(&refusal(&gateway.comp_id, &logon, &text).encode()).await;
+            return;
+        }
+    };
+    tracing::info!(counterparty = %counterparty, "FIX session logged on");
+    metrics::SESSIONS.inc();
+
+    let mut chunk = [0u8; 4096];
+    let mut ticker = tokio::time::interval(Duration::from_secs(1));
+    let reason = loop {
+        tokio::select! {
+            read = reader.read(&mut chunk) => {
+                match read {
+                    Ok(0) | Err(_) => break "disconnected",
+                    Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                }
+                let mut flow = Flow::Continue;
+                while let Some(frame) = fix::take_frame(&mut buf) {
+                    let msg = match frame {
+                        Ok(msg) => msg,
+                        Err(e) => {
+                            tracing::warn!(counterparty = %counterparty, error = %e, "ignoring garbled message");
+                            continue;
+                        }
+                    };
+                    conn.last_received = Instant::now();
+                    conn.test_request = None;
+                    flow = handle(&gateway, &session, &mut conn, msg).await;
+                    if flow == Flow::Close {
+                        break;
+                    }
+                }
+                if flow == Flow::Close {
+                    break "logged out";
+                }
+            }
+            Some(frame) = frames.recv() => {
+                if writer.write_all(&frame).await.is_err() {
+                    break "disconnected";
+                }
+                conn.last_sent = Instant::now();
+            }
+            _ = ticker.tick() => {
+                if let Err(reason) = conn.check_heartbeat(&session).await {
+                    break reason;
+                }
+            }
+        }
+    };
+
+    session.lock().await.outbound = None;
+    while let Ok(frame) = frames.try_recv() {
+        let _ = writer.write_all(&frame).await;
+    }
+    metrics::SESSIONS.dec();
+    tracing::info!(counterparty = %counterparty, reason, "FIX session ended");
+}
+
+async fn read_message(reader: &mut OwnedReadHalf, buf: &mut Vec<u8>) -> Option<Message> {
+    let mut chunk = [0u8; 4096];
+    loop {
+        if let Some(Ok(msg)) = fix::take_frame(buf) {
+            return Some(msg);
+        }
+        match reader.read(&mut chunk).await {
+            Ok(0) | Err(_) => return None,
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+        }
+    }
+}
+
+/// Authenticates a Logon by its Password, the user's API key, and attaches
+/// the connection to the counterparty's session.
+async fn log_on(
+    gateway: &Gateway,
+    logon: &Message,
+    outbound: mpsc::UnboundedSender<Vec<u8>>,
+) -> Result<(Arc<Mutex<Session>>, Connection), String> {
+    let counterparty = logon.get(tag::SENDER_COMP_ID).filter(|id| !id.is_empty()).ok_or("SenderCompID is required")?;
+    if logon.get(tag::TARGET_COMP_ID) != Some(gateway.comp_id.as_str()) {
+        return Err(format!("TargetCompID must be {}", gateway.comp_id));
+    }
+    let heartbeat = logon
+        .get(tag::HEART_BT_INT)
+        .and_then(|interval| interval.parse::<u64>().ok())
+        .filter(|interval| *interval > 0)
+        .ok_or("HeartBtInt must be a positive number of seconds")?;
+    let seq = logon.seq_num().ok_or("MsgSeqNum is required")?;
+    let api_key = logon.get(tag::PASSWORD).ok_or("Password must be an API key")?;
+    let user_id = gateway
+        .api_keys
+        .authenticate(api_key)
+        .await
+        .map_err(|_| "authentication unavailable".to_string())?
+        .ok_or("invalid API key")?;
+
+    let session = gateway.session(counterparty, &user_id).await;
+    let mut s = session.lock().await;
+    if s.user_id != user_id {
+        return Err("SenderCompID belongs to another user".to_string());
+    }
+    if s.outbound.is_some() {
+        return Err("session already logged on".to_string());
+    }
+    let reset = logon.flag(tag::RESET_SEQ_NUM_FLAG);
+    if reset {
+        s.reset();
+    }
+    if seq < s.next_in {
+        return Err(format!("MsgSeqNum too low, expecting {} but received {}", s.next_in, seq));
+    }
+
+    s.outbound = Some(outbound);
+    s.api_key = api_key.to_string();
+    s.send(
+        Message::new(msg_type::LOGON)
+            .with(tag::ENCRYPT_METHOD, 0)
+            .with(tag::HEART_BT_INT, heartbeat)
+            .with_opt(tag::RESET_SEQ_NUM_FLAG, reset.then_some("Y")),
+    );
+
+    let mut resend_until = None;
+    if seq > s.next_in {
+        let next_in = s.next_in;
+        s.send(resend_request(next_in));
+        resend_until = Some(seq);
+    } else {
+        s.next_in = seq + 1;
+    }
+    drop(s);
+
+    let now = Instant::now();
+    let conn = Connection {
+        heartbeat: Duration::from_secs(heartbeat),
+        last_received: now,
+        last_sent: now,
+        test_request: None,
+        resend_until,
+    };
+    Ok((session, conn))
+}
+
+async fn handle(gateway: &Arc<Gateway>, session: &Arc<Mutex<Session>>, conn: &mut Connection, msg: Message) -> Flow {
+    let mut guard = session.lock().await;
+    let s = &mut *guard;
+    let Some(seq) = msg.seq_num() else {
+        s.send(logout("MsgSeqNum is required"));
+        return Flow::Close;
+    };
+    let msg_type = msg.msg_type();
+
+    // A reset ignores sequence numbers altogether.
+    if msg_type == msg_type::SEQUENCE_RESET && !msg.flag(tag::GAP_FILL_FLAG) {
+        s.next_in = s.next_in.max(new_seq_no(&msg));
+        return Flow::Continue;
+    }
+
+    if seq > s.next_in {
+        // The resend will include this message, so it is not processed now.
+        match msg_type {
+            msg_type::RESEND_REQUEST => s.resend(seq_no(&msg, tag::BEGIN_SEQ_NO), seq_no(&msg, tag::END_SEQ_NO)),
+            msg_type::LOGOUT => {
+                s.send(Message::new(msg_type::LOGOUT));
+                return Flow::Close;
+            }
+            _ => {}
+        }
+        if conn.resend_until.is_none() {
+            let next_in = s.next_in;
+            s.send(resend_request(next_in));
+            conn.resend_until = Some(seq);
+        }
+        return Flow::Continue;
+    }
+    if seq < s.next_in {
+        if msg.flag(tag::POSS_DUP_FLAG) {
+            return Flow::Continue;
+        }
+        let text = format!("MsgSeqNum too low, expecting {} but received {}", s.next_in, seq);
+        s.send(logout(&text));
+        return Flow::Close;
+    }
+    s.next_in = seq + 1;
+
+    let mut submission = None;
+    match msg_type {
+        msg_type::HEARTBEAT | msg_type::LOGON => {}
+        msg_type::TEST_REQUEST => s.send(Message::new(msg_type::HEARTBEAT).with_opt(tag::TEST_REQ_ID, msg.get(tag::TEST_REQ_ID))),
+        msg_type::RESEND_REQUEST => s.resend(seq_no(&msg, tag::BEGIN_SEQ_NO), seq_no(&msg, tag::END_SEQ_NO)),
+        msg_type::REJECT => tracing::warn!(
+            counterparty = %s.target_comp_id,
+            ref_seq_num = msg.get(tag::REF_SEQ_NUM).unwrap_or("?"),
+            text = msg.get(tag::TEXT).unwrap_or(""),
+            "message rejected by counterparty"
+        ),
+        msg_type::SEQUENCE_RESET => s.next_in = s.next_in.max(new_seq_no(&msg)),
+        msg_type::LOGOUT => {
+            s.send(Message::new(msg_type::LOGOUT));
+            return Flow::Close;
+        }
+        msg_type::NEW_ORDER_SINGLE => submission = new_order(gateway, s, &msg, seq),
+        msg_type::ORDER_CANCEL_REQUEST => cancel_order(gateway, session, s, &msg, seq),
+        _ => s.send(
+            Message::new(msg_type::BUSINESS_MESSAGE_REJECT)
+                .with(tag::REF_SEQ_NUM, seq)
+                .with(tag::REF_MSG_TYPE, msg_type)
+                .with(tag::BUSINESS_REJECT_REASON, BUSINESS_REJECT_UNSUPPORTED_MESSAGE_TYPE)
+                .with(tag::TEXT, "Unsupported MsgType"),
+        ),
+    }
+
+    if conn.resend_until.is_some_and(|until| s.next_in > until) {
+        conn.resend_until = None;
+    }
+    // The backend is called without the session locked, so its reports and
+    // heartbeats go on meanwhile.
+    drop(guard);
+    if let Some(submission) = submission {
+        submit(gateway, session, submission).await;
+    }
+    Flow::Continue
+}
+
+/// A NewOrderSingle that passed the gateway's checks, to be entered
+/// through the backend.
+struct Submission {
+    msg: Message,
+    order: FixOrder,
+    request: SubmitOrderRequest,
+    user_id: String,
+    api_key: String,
+    span: tracing::Span,
+}
+
+fn new_order(gateway: &Gateway, s: &mut Session, msg: &Message, seq: u64) -> Option<Submission> {
+    if msg.get(tag::CL_ORD_ID).is_some_and(|cl_ord_id| s.cl_ord_ids.contains_key(cl_ord_id)) {
+        // A resent order that was already accepted is not entered twice.
+        if !msg.flag(tag::POSS_DUP_FLAG) {
+            metrics::order_rejected("duplicate");
+            s.send(reports::order_rejected(msg, orders::ORD_REJ_DUPLICATE_ORDER, "duplicate ClOrdID"));
+        }
+        return None;
+    }
+
+    let (order, request) = match orders::parse_new_order(msg, &gateway.tokens) {
+        Ok(parsed) => parsed,
+        Err(Rejection::MissingTag(missing)) => {
+            metrics::order_rejected("missing_tag");
+            s.send(required_tag_missing(msg, seq, missing));
+            return None;
+        }
+        Err(Rejection::Order { reason, text }) => {
+            metrics::order_rejected("invalid");
+            s.send(reports::order_rejected(msg, reason, &text));
+            return None;
+        }
+    };
+
+    let span = tracing::info_span!(
+        "order",
+        cl_ord_id = %order.cl_ord_id,
+        counterparty = %s.target_comp_id,
+        order_id = tracing::field::Empty,
+    );
+    Some(Submission { msg: msg.clone(), order, request, user_id: s.user_id.clone(), api_key: s.api_key.clone(), span })
+}
+
+/// Enters the order through the backend, which validates, throttles and
+/// publishes it like any other order.
+async fn submit(gateway: &Gateway, session: &Arc<Mutex<Session>>, submission: Submission) {
+    let Submission { msg, mut order, request, user_id, api_key, span } = submission;
+
+    gateway.submitting(&user_id).await;
+    let accepted = match gateway.intake.submit(&api_key, request).instrument(span.clone()).await {
+        Ok(accepted) => accepted,
+        Err(status) => {
+            gateway.submitted(&user_id, None).await;
+            let refusal = intake::refusal(&status);
+            tracing::info!(parent: &span, code = ?status.code(), reason = %refusal.text, "order refused");
+            metrics::order_rejected(refusal.metric);
+            return session.lock().await.send(reports::order_rejected(&msg, refusal.reason, &refusal.text));
+        }
+    };
+    span.record("order_id", accepted.order_id.as_str());
+    tracing::info!(parent: &span, replayed = accepted.replayed, "order accepted");
+    metrics::ORDERS_ACCEPTED.inc();
+
+    // A replayed ClOrdID is an order the backend accepted before, such as
+    // one resent after a timeout, whose updates may have been published
+    // already. It is reported with its latest one.
+    let mut latest = None;
+    if accepted.replayed {
+        match gateway.intake.status(&api_key, &accepted.order_id).await {
+            Ok(order) if !order.event_id.is_empty() => {
+                latest = Some((order.event_id, serde_json::from_str(&order.update_json).unwrap_or_default()));
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!(parent: &span, error = %e, "failed to get replayed order's status"),
+        }
+    }
+
+    // Locked before the order is routed, so the listener cannot report it
+    // before the session knows it.
+    let mut s = session.lock().await;
+    let order_id = accepted.order_id;
+    order.order_id = order_id.clone();
+    let mut updates = gateway.submitted(&user_id, Some((&order_id, session))).await;
+    s.cl_ord_ids.insert(order.cl_ord_id.clone(), order_id.clone());
+    s.orders.insert(order_id.clone(), order);
+
+    if updates.is_empty() {
+        updates.extend(latest);
+    }
+    for (event_id, update) in updates {
+        if s.report(&order_id, &event_id, &update) {
+            gateway.orders.lock().await.remove(&order_id);
+            break;
+        }
+    }
+}
+
+/// Only armed stop and market-if-touched orders can be cancelled. The
+/// router's `cancelled` update becomes the ExecutionReport confirming the
+/// cancel; a refusal becomes an OrderCancelReject.
+fn cancel_order(gateway: &Arc<Gateway>, session: &Arc<Mutex<Session>>, s: &mut Session, msg: &Message, seq: u64) {
+    for required in [tag::CL_ORD_ID, tag::ORIG_CL_ORD_ID] {
+        if msg.get(required).is_none_or(str::is_empty) {
+            return s.send(required_tag_missing(msg, seq, required));
+        }
+    }
+    let cl_ord_id = msg.get(tag::CL_ORD_ID).unwrap_or_default();
+    let orig_cl_ord_id = msg.get(tag::ORIG_CL_ORD_ID).unwrap_or_default();
+
+    let order = s.cl_ord_ids.get(orig_cl_ord_id).and_then(|order_id| s.orders.get_mut(order_id));
+    let Some(order) = order else {
+        return s.send(reports::cancel_rejected(msg, None, reports::CXL_REJ_UNKNOWN_ORDER, "unknown order"));
+    };
+    if order.is_closed() || order.pending_cancel.is_some() {
+        let reject = reports::cancel_rejected(msg, Some(order), reports::CXL_REJ_TOO_LATE, "order is closed or already being cancelled");
+        return s.send(reject);
+    }
+    order.pending_cancel = Some(cl_ord_id.to_string());
+
+    let order_id = order.order_id.clone();
+    let api_key = s.api_key.clone();
+    let (gateway, session, msg) = (gateway.clone(), session.clone(), msg.clone());
+    tokio::spawn(async move {
+        let (reason, text) = match gateway.intake.cancel(&api_key, &order_id).await {
+            Ok(()) => return,
+            Err(status) => match status.code() {
+                Code::FailedPrecondition => (reports::CXL_REJ_TOO_LATE, "only armed stop and market-if-touched orders can be cancelled"),
+                Code::DeadlineExceeded => (reports::CXL_REJ_OTHER, "cancel_timeout"),
+                Code::PermissionDenied => (reports::CXL_REJ_UNKNOWN_ORDER, "unknown order"),
+                _ => {
+                    tracing::warn!(order_id = %order_id, error = %status, "failed to cancel order");
+                    (reports::CXL_REJ_OTHER, "cancel unavailable")
+                }
+            },
+        };
+
+        let mut s = session.lock().await;
+        let order = s.orders.get_mut(&order_id);
+        let reject = order.map(|order| {
+            order.pending_cancel = None;
+            reports::cancel_rejected(&msg, Some(order), reason, text)
+        });
+        s.send(reject.unwrap_or_else(|| reports::cancel_rejected(&msg, None, reason, text)));
+    });
+}
+
+impl Connection {
+    /// Sends a Heartbeat after a quiet interval, a TestRequest when the
+    /// counterparty has been silent past its interval, and gives up when
+    /// that goes unanswered for another interval.
+    async fn check_heartbeat(&mut self, session: &Mutex<Session>) -> Result<(), &'static str> {
+        if self.last_sent.elapsed() >= self.heartbeat {
+            session.lock().await.send(Message::new(msg_type::HEARTBEAT));
+            self.last_sent = Instant::now();
+        }
+
+        match self.test_request {
+            None if self.last_received.elapsed() >= self.heartbeat + self.heartbeat / 5 => {
+                let test_req_id = format!("TEST-{}", chrono::Utc::now().timestamp_millis());
+                session.lock().await.send(Message::new(msg_type::TEST_REQUEST).with(tag::TEST_REQ_ID, test_req_id));
+                self.test_request = Some(Instant::now());
+            }
+            Some(sent) if sent.elapsed() >= self.heartbeat => return Err("no response to TestRequest"),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn resend_request(begin: u64) -> Message {
+    Message::new(msg_type::RESEND_REQUEST)
+        .with(tag::BEGIN_SEQ_NO, begin)
+        .with(tag::END_SEQ_NO, 0)
+}
+
+fn logout(text: &str) -> Message {
+    Message::new(msg_type::LOGOUT).with(tag::TEXT, text)
+}
+
+fn required_tag_missing(msg: &Message, seq: u64, missing: u32) -> Message {
+    Message::new(msg_type::REJECT)
+        .with(tag::REF_SEQ_NUM, seq)
+        .with(tag::REF_TAG_ID, missing)
+        .with(tag::REF_MSG_TYPE, msg.msg_type())
+        .with(tag::SESSION_REJECT_REASON, SESSION_REJECT_REQUIRED_TAG_MISSING)
+        .with(tag::TEXT, "Required tag missing")
+}
+
+/// A Logout for a Logon that never got a session, numbered 1.
+fn refusal(comp_id: &str, logon: &Message, text: &str) -> Message {
+    Message::new(msg_type::LOGOUT)
+        .with(tag::SENDER_COMP_ID, comp_id)
+        .with(tag::TARGET_COMP_ID, logon.get(tag::SENDER_COMP_ID).unwrap_or_default())
+        .with(tag::MSG_SEQ_NUM, 1)
+        .with(tag::SENDING_TIME, utc_timestamp())
+        .with(tag::TEXT, text)
+}
+
+fn seq_no(msg: &Message, tag: u32) -> u64 {
+    msg.get(tag).and_then(|value| value.parse().ok()).unwrap_or(0)
+}
+
+fn new_seq_no(msg: &Message) -> u64 {
+    seq_no(msg, tag::NEW_SEQ_NO)
+}
+
+/// UTCTimestamp with milliseconds, as used for SendingTime and TransactTime.
+pub fn utc_timestamp() -> String {
+    chrono::Utc::now().format("%Y%m%d-%H:%M:%S%.3f").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logged_on() -> (Session, mpsc::UnboundedReceiver<Vec<u8>>) {
+        let mut session = Session::new("GATEWAY", "CLIENT", "user-1");
+        let (tx, rx) = mpsc::unbounded_channel();
+        session.outbound = Some(tx);
+        (session, rx)
+    }
+
+    fn written(rx: &mut mpsc::UnboundedReceiver<Vec<u8>>) -> Vec<Message> {
+        let mut buf = Vec::new();
+        while let Ok(bytes) = rx.try_recv() {
+            buf.extend(bytes);
+        }
+        std::iter::from_fn(|| fix::take_frame(&mut buf)).map(Result::unwrap).collect()
+    }
+
+    fn app_message(text: &str) -> Message {
+        Message::new(msg_type::EXECUTION_REPORT).with(tag::TEXT, text)
+    }
+
+    #[test]
+    fn numbers_outgoing_messages_and_keeps_only_application_ones() {
+        let (mut s, mut rx) = logged_on();
+        s.send(Message::new(msg_type::HEARTBEAT));
+        s.send(app_message("first"));
+
+        let sent = written(&mut rx);
+        assert_eq!(sent.iter().map(|m| m.seq_num().unwrap()).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(sent[1].get(tag::SENDER_COMP_ID), Some("GATEWAY"));
+        assert_eq!(sent[1].get(tag::TARGET_COMP_ID), Some("CLIENT"));
+        assert_eq!(s.sent.keys().copied().collect::<Vec<_>>(), [2]);
+    }
+
+    #[test]
+    fn messages_sent_while_logged_off_are_numbered_and_kept() {
+        let mut s = Session::new("GATEWAY", "CLIENT", "user-1");
+        s.send(app_message("while away"));
+        assert_eq!(s.next_out, 2);
+        assert!(s.sent.contains_key(&1));
+    }
+
+    #[test]
+    fn resend_replays_kept_messages_and_gap_fills_the_rest() {
+        let (mut s, mut rx) = logged_on();
+        s.send(Message::new(msg_type::HEARTBEAT));
+        s.send(app_message("second"));
+        s.send(Message::new(msg_type::HEARTBEAT));
+        s.send(Message::new(msg_type::HEARTBEAT));
+        written(&mut rx);
+
+        s.resend(1, 0);
+        let resent = written(&mut rx);
+        assert_eq!(resent.len(), 3);
+
+        assert_eq!(resent[0].msg_type(), msg_type::SEQUENCE_RESET);
+        assert_eq!(resent[0].seq_num(), Some(1));
+        assert_eq!(resent[0].get(tag::NEW_SEQ_NO), Some("2"));
+        assert!(resent[0].flag(tag::GAP_FILL_FLAG));
+
+        assert_eq!(resent[1].msg_type(), msg_type::EXECUTION_REPORT);
+        assert_eq!(resent[1].seq_num(), Some(2));
+        assert!(resent[1].flag(tag::POSS_DUP_FLAG));
+        assert!(resent[1].get(tag::ORIG_SENDING_TIME).is_some());
+        assert_eq!(resent[1].get(tag::TEXT), Some("second"));
+
+        assert_eq!(resent[2].seq_num(), Some(3));
+        assert_eq!(resent[2].get(tag::NEW_SEQ_NO), Some("5"));
+    }
+
+    #[test]
+    fn resend_ignores_ranges_outside_what_was_sent() {
+        let (mut s, mut rx) = logged_on();
+        s.send(app_message("only"));
+        written(&mut rx);
+
+        s.resend(5, 0);
+        s.resend(0, 1);
+        assert!(written(&mut rx).is_empty());
+
+        s.resend(1, 99);
+        assert_eq!(written(&mut rx).len(), 1);
+    }
+
+    #[test]
+    fn reset_starts_both_sides_over() {
+        let (mut s, _rx) = logged_on();
+        s.send(app_message("before reset"));
+        s.next_in = 7;
+
+        s.reset();
+        assert_eq!((s.next_in, s.next_out), (1, 1));
+        assert!(s.sent.is_empty());
+    }
+}
@@ -1,78 +1,126 @@
-use crate::models::{Order, OrderStatus};
-use std::time::Duration;
+use crate::metrics;
+use crate::mev::{Protection, Sandwich, Searcher};
+use crate::models::Order;
+use crate::pools::{Pool, PoolBook, SwapQuote};
+use crate::scenario::ScenarioHandle;
+use std::time::Instant;
 use tokio::time::sleep;
 
-pub struct MockDexRouter;
+pub struct MockDexRouter {
+    scenario: ScenarioHandle,
+    pools: PoolBook,
+}
+
+#[derive(Debug, Clone)]
+pub struct Fill {
+    pub price: f64,
+    pub amount_out: f64,
+    pub sandwich: Option<Sandwich>,
+}
 
 impl MockDexRouter {
-    pub fn new() -> Self {
-        Self
+    pub fn new(scenario: ScenarioHandle) -> Self {
+        Self {
+            pools: PoolBook::new(scenario.clone()),
+            scenario,
+        }
+    }
+
+    pub fn scenario(&self) -> &ScenarioHandle {
+        &self.scenario
     }
 
-    pub async fn get_raydium_quote(&self, _amount: u64) -> f64 {
-        sleep(Duration::from_millis(200)).await;
-        
-        let base_price = 220.0;
-        base_price * (0.995 + rand::random::<f64>() * 0.01)
+    pub fn venues(&self) -> Vec<String> {
+        self.scenario.current().venues.keys().cloned().collect()
     }
 
-    pub async fn get_meteora_quote(&self, _amount: u64) -> f64 {
-        sleep(Duration::from_millis(250)).await;
-        
-        let base_price = 218.0;
-        base_price * (0.995 + rand::random::<f64>() * 0.01)
+    pub async fn get_quote(&self, venue: &str, token_in: &str, token_out: &str, amount: f64) -> Result<SwapQuote, String> {
+        let started = Instant::now();
+        let quote = self.quote_venue(venue, token_in, token_out, amount).await;
+        let outcome = if quote.is_ok() { "ok" } else { "error" };
+        metrics::QUOTE_SECONDS.with_label_values(&[venue, outcome]).observe(started.elapsed().as_secs_f64());
+        quote
     }
 
-    pub async fn execute_swap(&self, order: &Order, best_dex: &str) -> Result<(String, f64), String> {
-        let delay = (2000.0 + rand::random::<f64>() * 1000.0) as u64;
-        sleep(Duration::from_millis(delay)).await;
+    async fn quote_venue(&self, venue: &str, token_in: &str, token_out: &str, amount: f64) -> Result<SwapQuote, String> {
+        let scenario = self.scenario.current();
+        let config = scenario.venues.get(venue).ok_or_else(|| format!("Unknown DEX: {}", venue))?;
 
-        if rand::random::<f64>() < 0.1 {
-            return Err(format!("Simulated network failure on {}", best_dex));
+        sleep(config.quote_latency.sample()).await;
+
+        if config.is_down(self.scenario.since_load()) {
+            return Err(format!("{} unavailable (outage)", venue));
         }
 
-        let random_num: u64 = rand::random();
-        let tx_hash = format!("0x{:016x}", random_num);
-        
-        let base_quote = match best_dex {
-            "raydium" => self.get_raydium_quote(order.amount).await,
-            "meteora" => self.get_meteora_quote(order.amount).await,
-            _ => return Err(format!("Unknown DEX: {}", best_dex)),
-        };
+        self.pools.quote(venue, token_in, token_out, amount)
+    }
 
-        let executed_price = base_quote * (0.999 + rand::random::<f64>() * 0.002);
+    /// Quotes each of `venues` in turn, keeping the error for venues that
+    /// cannot quote the pair.
+    pub async fn quote_all(&self, venues: Vec<String>, token_in: &str, token_out: &str, amount: f64) -> Vec<(String, Result<SwapQuote, String>)> {
+        let mut quotes = Vec::new();
+        for venue in venues {
+            let quote = self.get_quote(&venue, token_in, token_out, amount).await;
+            quotes.push((venue, quote));
+        }
+        quotes
+    }
 
-        Ok((tx_hash, executed_price))
+    /// Advances every pool one price feed tick.
+    pub fn tick_market(&self) -> Vec<(String, Pool)> {
+        let config = self.scenario.current().price_feed.clone();
+        self.pools.tick(|pool| config.step(pool.price(), pool.reference_price))
     }
 
-    pub async fn execute_with_retry(&self, order: &mut Order, best_dex: &str) {
-        let max_retries = 3;
-        let retry_delays = vec![500, 1000, 2000];
-
-        for attempt in 1..=max_retries {
-            println!("Attempt {}: Executing swap on {}...", attempt, best_dex);
-            
-            match self.execute_swap(order, best_dex).await {
-                Ok((tx_hash, executed_price)) => {
-                    println!("Swap successful! TX: {}, Price: {:.4}", tx_hash, executed_price);
-                    order.update_status(OrderStatus::Confirmed { tx_hash });
-                    return;
-                }
-                Err(error) => {
-                    println!("Attempt {} failed: {}", attempt, error);
-                    
-                    if attempt < max_retries {
-                        let delay = retry_delays[attempt - 1];
-                        println!("Waiting {}ms before retry...", delay);
-                        sleep(Duration::from_millis(delay)).await;
-                    } else {
-                        println!("All {} attempts failed. Order marked as failed.", max_retries);
-                        order.update_status(OrderStatus::Failed { 
-                            reason: format!("Failed after {} attempts: {}", max_retries, error) 
-                        });
-                    }
-                }
-            }
+    pub async fn execute_swap(&self, venue: &str) -> Result<(), String> {
+        let scenario = self.scenario.current();
+        let config = scenario.venues.get(venue).ok_or_else(|| format!("Unknown DEX: {}", venue))?;
+
+        sleep(config.execution_latency.sample()).await;
+
+        if config.is_down(self.scenario.since_load()) {
+            return Err(format!("{} unavailable (outage)", venue));
+        }
+
+        match config.roll_failure() {
+            Some(kind) => Err(kind.describe(venue)),
+            None => Ok(()),
         }
     }
+
+    /// Applies a landed swap to the pool. The market first drifts from the
+    /// reference price; public orders may then be sandwiched. Fails like an
+    /// on-chain `min_out` check when the output falls short of the quote by
+    /// more than the order's slippage.
+    pub fn settle_swap(&self, quote: &SwapQuote, order: &Order) -> Result<Fill, String> {
+        let scenario = self.scenario.current();
+        let drift_pct = scenario.price_drift.sample_pct();
+        let min_out = quote.amount_out * (1.0 - order.max_slippage / 100.0);
+
+        self.pools.with_pool(&quote.venue, &order.token_in, &order.token_out, |pool| {
+            pool.set_price(pool.reference_price * (1.0 + drift_pct / 100.0));
+
+            let mut sandwich = match order.protection {
+                Protection::Public => Searcher::front_run(pool, &scenario.mev, &order.token_in, order.amount, min_out),
+                Protection::Protected => None,
+            };
+
+            let amount_out = pool.amount_out(&order.token_in, order.amount);
+            if amount_out < min_out {
+                let moved = (quote.amount_out - amount_out) / quote.amount_out * 100.0;
+                return Err(format!("Price moved {:.2}% (max allowed: {:.2}%)", moved, order.max_slippage));
+            }
+
+            pool.swap(&order.token_in, order.amount);
+            if let Some(sandwich) = sandwich.as_mut() {
+                Searcher::back_run(pool, sandwich, &order.token_out);
+            }
+
+            Ok(Fill {
+                price: amount_out / order.amount,
+                amount_out,
+                sandwich,
+            })
+        })?
+    }
 }
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5000;
+
+/// How an order bids for block space, in micro-lamports per compute unit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PriorityFeePolicy {
+    Fixed { micro_lamports: u64 },
+    Percentile { percentile: f64, max_micro_lamports: Option<u64> },
+    MaxCap { max_micro_lamports: u64 },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct FeePaid {
+    pub priority_fee: u64,
+    pub compute_units: u32,
+    pub tip_lamports: u64,
+    pub fee_lamports: u64,
+}
+
+impl Default for PriorityFeePolicy {
+    fn default() -> Self {
+        PriorityFeePolicy::Fixed { micro_lamports: 0 }
+    }
+}
+
+impl PriorityFeePolicy {
+    /// Picks the compute unit price for the next attempt from the fees that
+    /// landed in recent blocks. `MaxCap` bids what the top of recent blocks
+    /// paid, up to the cap.
+    pub fn resolve(&self, recent_fees: &[u64]) -> u64 {
+        match self {
+            PriorityFeePolicy::Fixed { micro_lamports } => *micro_lamports,
+            PriorityFeePolicy::Percentile { percentile, max_micro_lamports } => {
+                let fee = percentile_of(recent_fees, *percentile);
+                max_micro_lamports.map_or(fee, |cap| fee.min(cap))
+            }
+            PriorityFeePolicy::MaxCap { max_micro_lamports } => {
+                percentile_of(recent_fees, 90.0).min(*max_micro_lamports)
+            }
+        }
+    }
+}
+
+impl FeePaid {
+    pub fn new(priority_fee: u64, compute_units: u32, tip_lamports: u64) -> Self {
+        let priority_lamports = (priority_fee as u128 * compute_units as u128).div_ceil(1_000_000) as u64;
+        Self {
+            priority_fee,
+            compute_units,
+            tip_lamports,
+            fee_lamports: LAMPORTS_PER_SIGNATURE + priority_lamports + tip_lamports,
+        }
+    }
+}
+
+pub fn percentile_of(values: &[u64], percentile: f64) -> u64 {
+    if values.is_empty() {
+        return 0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    let rank = (percentile.clamp(0.0, 100.0) / 100.0 * (sorted.len() - 1) as f64).round() as usize;
+    sorted[rank]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECENT: [u64; 11] = [500, 0, 100, 1_000, 200, 300, 900, 400, 600, 800, 700];
+
+    #[test]
+    fn percentile_of_picks_the_nearest_rank() {
+        assert_eq!(percentile_of(&RECENT, 0.0), 0);
+        assert_eq!(percentile_of(&RECENT, 50.0), 500);
+        assert_eq!(percentile_of(&RECENT, 90.0), 900);
+        assert_eq!(percentile_of(&RECENT, 100.0), 1_000);
+        assert_eq!(percentile_of(&RECENT, 250.0), 1_000);
+        assert_eq!(percentile_of(&RECENT, -10.0), 0);
+        assert_eq!(percentile_of(&[], 50.0), 0);
+    }
+
+    #[test]
+    fn fixed_ignores_recent_fees() {
+        let policy = PriorityFeePolicy::Fixed { micro_lamports: 42 };
+        assert_eq!(policy.resolve(&RECENT), 42);
+        assert_eq!(policy.resolve(&[]), 42);
+    }
+
+    #[test]
+    fn percentile_is_capped_when_configured() {
+        let uncapped = PriorityFeePolicy::Percentile { percentile: 75.0, max_micro_lamports: None };
+        assert_eq!(uncapped.resolve(&RECENT), 800);
+
+        let capped = PriorityFeePolicy::Percentile { percentile: 75.0, max_micro_lamports: Some(650) };
+        assert_eq!(capped.resolve(&RECENT), 650);
+        assert_eq!(capped.resolve(&[]), 0);
+    }
+
+    #[test]
+    fn max_cap_bids_the_top_of_recent_blocks_up_to_the_cap() {
+        assert_eq!(PriorityFeePolicy::MaxCap { max_micro_lamports: 5_000 }.resolve(&RECENT), 900);
+        assert_eq!(PriorityFeePolicy::MaxCap { max_micro_lamports: 300 }.resolve(&RECENT), 300);
+    }
+
+    #[test]
+    fn fee_paid_adds_base_priority_and_tip() {
+        let paid = FeePaid::new(1_000, 200_000, 0);
+        assert_eq!(paid.fee_lamports, LAMPORTS_PER_SIGNATURE + 200);
+
+        let rounded_up = FeePaid::new(1, 1, 10_000);
+        assert_eq!(rounded_up.fee_lamports, LAMPORTS_PER_SIGNATURE + 1 + 10_000);
+    }
+
+    #[test]
+    fn policies_deserialize_from_tagged_json() {
+        let policy: PriorityFeePolicy = serde_json::from_str(r#"{"type":"percentile","percentile":90}"#).unwrap();
+        assert!(matches!(policy, PriorityFeePolicy::Percentile { percentile, max_micro_lamports: None } if percentile == 90.0));
+    }
+}
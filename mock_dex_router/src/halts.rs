@@ -0,0 +1,116 @@
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use serde::Deserialize;
+use std::collections::HashMap;
+use tokio::sync::watch;
+use crate::models::Order;
+use crate::redis_handler::RedisHandler;
+
+const HALTS_KEY: &str = "trading:halts";
+const CONTROL_STREAM: &str = "trading_control";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HaltMode {
+    Reject,
+    Pause,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Halt {
+    pub mode: HaltMode,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Trading halts set by the backend's admin API. The hash `trading:halts`
+/// holds the active halts, keyed `global`, `pair:{BASE}/{QUOTE}` or
+/// `venue:{name}`; each change is announced on `trading_control`.
+pub struct HaltBoard {
+    conn: ConnectionManager,
+    halts: watch::Sender<HashMap<String, Halt>>,
+}
+
+impl HaltBoard {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn, halts: watch::Sender::new(HashMap::new()) }
+    }
+
+    pub async fn refresh(&self) -> Result<(), redis::RedisError> {
+        let raw: HashMap<String, String> = self.conn.clone().hgetall(HALTS_KEY).await?;
+        let halts: HashMap<String, Halt> = raw
+            .into_iter()
+            .filter_map(|(field, value)| Some((field, serde_json::from_str(&value).ok()?)))
+            .collect();
+
+        if !halts.is_empty() {
+            tracing::info!(halts = ?halts.keys().collect::<Vec<_>>(), "halts loaded");
+        }
+        self.halts.send_replace(halts);
+        Ok(())
+    }
+
+    /// Reloads the halts whenever a control message arrives. The hash stays
+    /// the source of truth; the stream only says when to look.
+    pub async fn listen(&self, redis_handler: RedisHandler) {
+        let mut reader = redis_handler.reader("halts", CONTROL_STREAM, "$");
+        tracing::info!("halt listener started");
+
+        loop {
+            reader.next().await;
+            if let Err(e) = self.refresh().await {
+                tracing::warn!(error = %e, "halt refresh failed");
+            }
+        }
+    }
+
+    /// The global or pair halt covering the order, global first.
+    pub fn halt_for(&self, order: &Order) -> Option<Halt> {
+        self.halt_for_pair(&order.token_in, &order.token_out)
+    }
+
+    pub fn halt_for_pair(&self, token_in: &str, token_out: &str) -> Option<Halt> {
+        let halts = self.halts.borrow();
+        halts
+            .get("global")
+            .or_else(|| halts.get(&pair_field(token_in, token_out)))
+            .cloned()
+    }
+
+    /// Waits while a pausing halt covers the order. Returns the halt if the
+    /// order should be rejected instead.
+    pub async fn wait_while_paused(&self, order: &Order) -> Option<Halt> {
+        let mut halts = self.halts.subscribe();
+        let mut announced = false;
+        loop {
+            match self.halt_for(order) {
+                Some(halt) if halt.mode == HaltMode::Pause => {
+                    if !announced {
+                        tracing::info!(order_id = %order.order_id, "order paused by halt");
+                        announced = true;
+                    }
+                    if halts.changed().await.is_err() {
+                        return Some(halt);
+                    }
+                }
+                other => return other,
+            }
+        }
+    }
+
+    pub fn venue_halted(&self, venue: &str) -> bool {
+        self.halts.borrow().contains_key(&format!("venue:{}", venue))
+    }
+
+    /// Splits `venues` into those open for routing and those halted.
+    pub fn partition_venues(&self, venues: Vec<String>) -> (Vec<String>, Vec<String>) {
+        venues.into_iter().partition(|venue| !self.venue_halted(venue))
+    }
+}
+
+/// Pairs are stored with their tokens sorted, so a halt covers both
+/// directions.
+fn pair_field(token_a: &str, token_b: &str) -> String {
+    let (first, second) = if token_a <= token_b { (token_a, token_b) } else { (token_b, token_a) };
+    format!("pair:{}/{}", first, second)
+}
@@ -0,0 +1,103 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use common::health::{probe, status_code, StreamLimits, PROBE_TIMEOUT};
+use common::streams::{ReaderReport, StreamReaders};
+use redis::aio::ConnectionManager;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use crate::metrics;
+
+/// `/healthz` and `/readyz` for orchestrators, and `/metrics` for
+/// Prometheus, served on `HEALTH_PORT`.
+pub struct Health {
+    limits: StreamLimits,
+    streams: StreamReaders,
+    conn: ConnectionManager,
+    workers: Arc<Semaphore>,
+    worker_capacity: usize,
+}
+
+impl Health {
+    pub fn new(streams: StreamReaders, conn: ConnectionManager, workers: Arc<Semaphore>, worker_capacity: usize) -> Self {
+        Self {
+            limits: StreamLimits::from_env(),
+            streams,
+            conn,
+            workers,
+            worker_capacity,
+        }
+    }
+
+    pub async fn serve(self: Arc<Self>, port: String) {
+        let app = Router::new()
+            .route("/healthz", get(healthz))
+            .route("/readyz", get(readyz))
+            .route("/metrics", get(render_metrics))
+            .with_state(self);
+        common::health::serve(app, port).await;
+    }
+
+    /// Every reader's state. The shared connection has no command timeout
+    /// of its own, so a lag check that hangs is dropped and the readers
+    /// reported without lag.
+    async fn streams(&self, redis_ok: bool) -> Vec<ReaderReport> {
+        let mut conn = self.conn.clone();
+        let streams = if redis_ok {
+            tokio::time::timeout(PROBE_TIMEOUT, self.streams.report(Some(&mut conn))).await.ok()
+        } else {
+            None
+        };
+        match streams {
+            Some(streams) => streams,
+            None => self.streams.report(None::<&mut ConnectionManager>).await,
+        }
+    }
+
+    fn busy_workers(&self) -> usize {
+        self.worker_capacity - self.workers.available_permits()
+    }
+}
+
+/// Fails when a stream listener has stopped reading, such as the order
+/// loop hanging on a Redis command, which only a restart recovers from.
+async fn healthz(State(health): State<Arc<Health>>) -> (StatusCode, Json<serde_json::Value>) {
+    let stalled = health.limits.stalled(&health.streams);
+    let healthy = stalled.is_empty();
+    let body = serde_json::json!({
+        "status": if healthy { "ok" } else { "unhealthy" },
+        "stalled": stalled,
+    });
+    (status_code(healthy), Json(body))
+}
+
+/// Fails while Redis is unreachable, or a stream reader is disconnected or
+/// further behind than `MAX_STREAM_LAG_MS`. Worker saturation is reported
+/// but does not fail the check: a busy router is still making progress.
+async fn readyz(State(health): State<Arc<Health>>) -> (StatusCode, Json<serde_json::Value>) {
+    let mut conn = health.conn.clone();
+    let redis = probe(redis::cmd("PING").query_async::<_, String>(&mut conn)).await;
+    let streams = health.streams(redis.ok).await;
+    let busy = health.busy_workers();
+
+    let ready = redis.ok && health.limits.caught_up(&streams);
+
+    let body = serde_json::json!({
+        "status": if ready { "ready" } else { "not_ready" },
+        "redis": redis,
+        "streams": streams,
+        "workers": {
+            "capacity": health.worker_capacity,
+            "busy": busy,
+            "saturation": busy as f64 / health.worker_capacity as f64,
+        },
+    });
+    (status_code(ready), Json(body))
+}
+
+async fn render_metrics(State(health): State<Arc<Health>>) -> String {
+    metrics::set_streams(&health.streams(true).await);
+    metrics::set_workers(health.busy_workers(), health.worker_capacity);
+    metrics::render()
+}
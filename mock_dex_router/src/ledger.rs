@@ -0,0 +1,384 @@
+use crate::scenario::{standard_normal, LedgerConfig, ScenarioHandle};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::watch;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Commitment {
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+#[derive(Debug, Clone)]
+pub struct Blockhash {
+    pub hash: String,
+    pub slot: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LedgerError {
+    Dropped,
+    BlockhashExpired,
+    UnknownBlockhash,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Landed {
+    pub slot: u64,
+    pub commitment: Commitment,
+}
+
+#[derive(Debug, Clone)]
+enum TxState {
+    Pending { blockhash_slot: u64, priority_fee: u64, bundle: bool },
+    Landed { slot: u64 },
+    Failed { error: LedgerError, slot: u64 },
+}
+
+struct LedgerState {
+    slot: u64,
+    blockhashes: BTreeMap<u64, String>,
+    transactions: HashMap<String, TxState>,
+    recent_fees: VecDeque<Vec<u64>>,
+}
+
+/// In-process chain producing one block per slot. Submitted transactions
+/// land in a later block, get dropped, or expire with their blockhash.
+/// Blocks are filled highest priority fee first, alongside simulated
+/// background traffic, so low bids land later or not at all when congested.
+pub struct MockLedger {
+    scenario: ScenarioHandle,
+    state: Mutex<LedgerState>,
+    slot_tx: watch::Sender<u64>,
+}
+
+impl std::fmt::Display for LedgerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LedgerError::Dropped => write!(f, "transaction dropped"),
+            LedgerError::BlockhashExpired => write!(f, "blockhash expired"),
+            LedgerError::UnknownBlockhash => write!(f, "blockhash not found"),
+        }
+    }
+}
+
+impl Commitment {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "processed" => Some(Commitment::Processed),
+            "confirmed" => Some(Commitment::Confirmed),
+            "finalized" => Some(Commitment::Finalized),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Commitment::Processed => "processed",
+            Commitment::Confirmed => "confirmed",
+            Commitment::Finalized => "finalized",
+        }
+    }
+}
+
+impl MockLedger {
+    pub fn new(scenario: ScenarioHandle) -> Self {
+        let mut blockhashes = BTreeMap::new();
+        blockhashes.insert(0, Self::hash(&(0u64, "genesis")));
+        let (slot_tx, _) = watch::channel(0);
+
+        Self {
+            scenario,
+            state: Mutex::new(LedgerState {
+                slot: 0,
+                blockhashes,
+                transactions: HashMap::new(),
+                recent_fees: VecDeque::new(),
+            }),
+            slot_tx,
+        }
+    }
+
+    pub async fn run(&self) {
+        loop {
+            let slot_ms = self.scenario.current().ledger.slot_ms;
+            tokio::time::sleep(Duration::from_millis(slot_ms)).await;
+            let slot = self.produce_block();
+            self.slot_tx.send_replace(slot);
+        }
+    }
+
+    pub fn latest_blockhash(&self) -> Blockhash {
+        let state = self.state.lock().unwrap();
+        let (slot, hash) = state.blockhashes.last_key_value().expect("genesis block");
+        Blockhash { hash: hash.clone(), slot: *slot }
+    }
+
+    /// Priority fees (micro-lamports per CU) paid by transactions in recent blocks.
+    pub fn recent_prioritization_fees(&self) -> Vec<u64> {
+        let state = self.state.lock().unwrap();
+        state.recent_fees.iter().flatten().copied().collect()
+    }
+
+    /// Bundles skip the public mempool and only land when a bundle-accepting
+    /// leader produces the block (`bundle_inclusion_rate`).
+    pub fn submit(&self, order_id: &str, blockhash: &Blockhash, attempt: u32, priority_fee: u64, bundle: bool) -> Result<String, LedgerError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.blockhashes.contains_key(&blockhash.slot) {
+            return Err(LedgerError::UnknownBlockhash);
+        }
+
+        let signature = format!("0x{}{}", Self::hash(&(order_id, &blockhash.hash, attempt)), Self::hash(&(attempt, order_id)));
+        state.transactions.insert(signature.clone(), TxState::Pending { blockhash_slot: blockhash.slot, priority_fee, bundle });
+        Ok(signature)
+    }
+
+    pub async fn wait_for(&self, signature: &str, commitment: Commitment) -> Result<Landed, LedgerError> {
+        let mut slot_rx = self.slot_tx.subscribe();
+
+        loop {
+            let current = *slot_rx.borrow_and_update();
+            let tx_state = self.state.lock().unwrap().transactions.get(signature).cloned();
+
+            match tx_state {
+                Some(TxState::Landed { slot }) => {
+                    let reached = self.commitment_at(slot, current);
+                    if reached >= commitment {
+                        return Ok(Landed { slot, commitment: reached });
+                    }
+                }
+                Some(TxState::Failed { error, .. }) => return Err(error),
+                Some(TxState::Pending { .. }) => {}
+                None => return Err(LedgerError::Dropped),
+            }
+
+            if slot_rx.changed().await.is_err() {
+                return Err(LedgerError::Dropped);
+            }
+        }
+    }
+
+    fn commitment_at(&self, landed_slot: u64, current_slot: u64) -> Commitment {
+        let config = &self.scenario.current().ledger;
+        let depth = current_slot.saturating_sub(landed_slot);
+        if depth >= config.finalized_depth {
+            Commitment::Finalized
+        } else if depth >= config.confirmed_depth {
+            Commitment::Confirmed
+        } else {
+            Commitment::Processed
+        }
+    }
+
+    fn produce_block(&self) -> u64 {
+        let scenario = self.scenario.current();
+        let config = &scenario.ledger;
+        let mut state = self.state.lock().unwrap();
+
+        let slot = state.slot + 1;
+        let parent = state.blockhashes.last_key_value().map(|(_, h)| h.clone()).unwrap_or_default();
+        state.slot = slot;
+        state.blockhashes.insert(slot, Self::hash(&(slot, parent)));
+
+        let oldest_valid = slot.saturating_sub(config.blockhash_valid_slots);
+        state.blockhashes.retain(|s, _| *s >= oldest_valid);
+
+        let mut candidates: Vec<(u64, Option<&String>)> = Self::background_fees(config)
+            .into_iter()
+            .map(|fee| (fee, None))
+            .collect();
+
+        let mut failed = Vec::new();
+        for (signature, tx) in state.transactions.iter() {
+            let TxState::Pending { blockhash_slot, priority_fee, bundle } = *tx else { continue };
+            let inclusion_rate = if bundle { scenario.mev.bundle_inclusion_rate } else { config.inclusion_rate };
+
+            if blockhash_slot < oldest_valid {
+                failed.push((signature.clone(), LedgerError::BlockhashExpired));
+            } else if rand::random::<f64>() < config.drop_rate {
+                failed.push((signature.clone(), LedgerError::Dropped));
+            } else if rand::random::<f64>() < inclusion_rate {
+                candidates.push((priority_fee, Some(signature)));
+            }
+        }
+
+        candidates.sort_by_key(|(fee, _)| std::cmp::Reverse(*fee));
+        let congested = candidates.len() > config.max_txs_per_block;
+        let mut block_fees = Vec::new();
+        let mut landed = Vec::new();
+        let mut missed = Vec::new();
+
+        for (index, (fee, signature)) in candidates.into_iter().enumerate() {
+            if index < config.max_txs_per_block {
+                block_fees.push(fee);
+                if let Some(signature) = signature {
+                    landed.push(signature.clone());
+                }
+            } else if let Some(signature) = signature {
+                missed.push(signature.clone());
+            }
+        }
+
+        for signature in landed {
+            state.transactions.insert(signature, TxState::Landed { slot });
+        }
+        for (signature, error) in failed {
+            state.transactions.insert(signature, TxState::Failed { error, slot });
+        }
+        if congested {
+            for signature in missed {
+                if rand::random::<f64>() < config.fee_market.congestion_drop_rate {
+                    state.transactions.insert(signature, TxState::Failed { error: LedgerError::Dropped, slot });
+                }
+            }
+        }
+
+        state.recent_fees.push_back(block_fees);
+        while state.recent_fees.len() > config.fee_market.recent_fee_slots {
+            state.recent_fees.pop_front();
+        }
+
+        let retain_after = slot.saturating_sub(config.finalized_depth + config.blockhash_valid_slots);
+        state.transactions.retain(|_, tx| match tx {
+            TxState::Landed { slot } | TxState::Failed { slot, .. } => *slot >= retain_after,
+            TxState::Pending { .. } => true,
+        });
+
+        slot
+    }
+
+    /// Priority fees of competing transactions for one block, log-normally
+    /// distributed around the configured median.
+    fn background_fees(config: &LedgerConfig) -> Vec<u64> {
+        let market = &config.fee_market;
+        let count = (market.competing_txs_per_slot as f64 * (0.5 + rand::random::<f64>())) as usize;
+
+        (0..count)
+            .map(|_| (market.competing_fee_median as f64 * (market.competing_fee_sigma * standard_normal()).exp()) as u64)
+            .collect()
+    }
+
+    fn hash<T: Hash>(value: &T) -> String {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario::Scenario;
+
+    /// A quiet chain: every transaction is included in the next block, none
+    /// are dropped and there is no background traffic.
+    fn ledger(configure: impl FnOnce(&mut LedgerConfig)) -> MockLedger {
+        let mut scenario = Scenario::default();
+        scenario.ledger.inclusion_rate = 1.0;
+        scenario.ledger.drop_rate = 0.0;
+        scenario.ledger.confirmed_depth = 2;
+        scenario.ledger.finalized_depth = 4;
+        scenario.ledger.fee_market.competing_txs_per_slot = 0;
+        scenario.ledger.fee_market.congestion_drop_rate = 0.0;
+        configure(&mut scenario.ledger);
+        MockLedger::new(ScenarioHandle::fixed(scenario))
+    }
+
+    fn state_of(ledger: &MockLedger, signature: &str) -> Option<TxState> {
+        ledger.state.lock().unwrap().transactions.get(signature).cloned()
+    }
+
+    #[test]
+    fn produces_one_blockhash_per_slot() {
+        let ledger = ledger(|_| {});
+        assert_eq!(ledger.latest_blockhash().slot, 0);
+
+        let genesis = ledger.latest_blockhash();
+        assert_eq!(ledger.produce_block(), 1);
+        let next = ledger.latest_blockhash();
+        assert_eq!(next.slot, 1);
+        assert_ne!(next.hash, genesis.hash);
+    }
+
+    #[tokio::test]
+    async fn commitment_advances_with_depth() {
+        let ledger = ledger(|_| {});
+        let blockhash = ledger.latest_blockhash();
+        let signature = ledger.submit("order-1", &blockhash, 0, 0, false).unwrap();
+        assert!(matches!(state_of(&ledger, &signature), Some(TxState::Pending { .. })));
+
+        ledger.produce_block();
+        let landed = ledger.wait_for(&signature, Commitment::Processed).await.unwrap();
+        assert_eq!(landed.slot, 1);
+        assert_eq!(landed.commitment, Commitment::Processed);
+
+        assert_eq!(ledger.commitment_at(1, 2), Commitment::Processed);
+        assert_eq!(ledger.commitment_at(1, 3), Commitment::Confirmed);
+        assert_eq!(ledger.commitment_at(1, 5), Commitment::Finalized);
+
+        for _ in 0..4 {
+            ledger.produce_block();
+        }
+        ledger.slot_tx.send_replace(5);
+        let landed = ledger.wait_for(&signature, Commitment::Finalized).await.unwrap();
+        assert_eq!(landed.commitment, Commitment::Finalized);
+    }
+
+    #[tokio::test]
+    async fn pending_transactions_expire_with_their_blockhash() {
+        let ledger = ledger(|config| {
+            config.inclusion_rate = 0.0;
+            config.blockhash_valid_slots = 2;
+        });
+        let blockhash = ledger.latest_blockhash();
+        let signature = ledger.submit("order-1", &blockhash, 0, 0, false).unwrap();
+
+        for _ in 0..3 {
+            ledger.produce_block();
+        }
+        assert_eq!(ledger.wait_for(&signature, Commitment::Processed).await.unwrap_err(), LedgerError::BlockhashExpired);
+        assert_eq!(ledger.submit("order-2", &blockhash, 0, 0, false).unwrap_err(), LedgerError::UnknownBlockhash);
+    }
+
+    #[test]
+    fn full_blocks_take_the_highest_fees_first() {
+        let ledger = ledger(|config| config.max_txs_per_block = 1);
+        let blockhash = ledger.latest_blockhash();
+        let low = ledger.submit("low", &blockhash, 0, 10, false).unwrap();
+        let high = ledger.submit("high", &blockhash, 0, 1_000, false).unwrap();
+
+        ledger.produce_block();
+        assert!(matches!(state_of(&ledger, &high), Some(TxState::Landed { slot: 1 })));
+        assert!(matches!(state_of(&ledger, &low), Some(TxState::Pending { .. })));
+        assert_eq!(ledger.recent_prioritization_fees(), vec![1_000]);
+
+        ledger.produce_block();
+        assert!(matches!(state_of(&ledger, &low), Some(TxState::Landed { slot: 2 })));
+    }
+
+    #[test]
+    fn recent_fees_cover_only_the_configured_window() {
+        let ledger = ledger(|config| config.fee_market.recent_fee_slots = 2);
+        for fee in [1, 2, 3] {
+            let blockhash = ledger.latest_blockhash();
+            ledger.submit(&format!("order-{fee}"), &blockhash, 0, fee, false).unwrap();
+            ledger.produce_block();
+        }
+        assert_eq!(ledger.recent_prioritization_fees(), vec![2, 3]);
+    }
+
+    #[test]
+    fn retries_get_distinct_signatures() {
+        let ledger = ledger(|_| {});
+        let blockhash = ledger.latest_blockhash();
+        let first = ledger.submit("order-1", &blockhash, 0, 0, false).unwrap();
+        let second = ledger.submit("order-1", &blockhash, 1, 0, false).unwrap();
+        assert_ne!(first, second);
+    }
+}
@@ -5,94 +5,190 @@ mod redis_handler;
 mod status_manager;
 mod processor;
 mod parser;
+mod scenario;
+mod ledger;
+mod fees;
+mod pools;
+mod mev;
+mod wallets;
+mod quotes;
+mod risk;
+mod halts;
+mod parent_orders;
+mod price_feed;
+mod triggers;
+mod health;
+mod metrics;
 
 use dex_router::MockDexRouter;
 use redis_handler::RedisHandler;
 use status_manager::StatusManager;
-use processor::OrderProcessor;
-use parser::StreamParser;
+use processor::{OrderProcessor, Services};
+use parser::{Malformed, StreamParser};
+use models::{Order, OrderStatus};
+use scenario::ScenarioHandle;
+use ledger::MockLedger;
+use wallets::WalletStore;
+use quotes::QuoteBook;
+use risk::RiskEngine;
+use halts::HaltBoard;
+use parent_orders::ParentOrder;
+use price_feed::PriceFeed;
+use triggers::TriggerBook;
+use health::Health;
+use common::telemetry;
 use std::sync::Arc;
 use tokio::sync::Semaphore;
 
+/// Orders executed at once; the rest wait for a free worker.
+const ORDER_WORKERS: usize = 10;
+
 #[tokio::main]
 async fn main() {
     dotenvy::dotenv().ok();
+    let _telemetry = telemetry::init("mock_dex_router");
     
-    println!("Mock DEX Router starting...");
-    println!("Connecting to Redis...");
+    tracing::info!("Mock DEX Router starting");
     
     let redis_url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
     let redis_handler = match RedisHandler::new(&redis_url) {
         Ok(handler) => handler,
         Err(e) => {
-            eprintln!("Failed to create Redis handler: {}", e);
+            tracing::error!(error = %e, "failed to create redis handler");
             return;
         }
     };
     
-    let mut conn = match redis_handler.get_connection().await {
+    let scenario = match ScenarioHandle::from_env() {
+        Ok(scenario) => scenario,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to load scenario");
+            return;
+        }
+    };
+    tokio::spawn(scenario.clone().watch());
+
+    let ledger = Arc::new(MockLedger::new(scenario.clone()));
+    let ledger_clone = ledger.clone();
+    tokio::spawn(async move {
+        ledger_clone.run().await;
+    });
+
+    let shared_conn = match redis_handler.get_connection_manager().await {
         Ok(conn) => conn,
         Err(e) => {
-            eprintln!("Failed to connect to Redis: {}", e);
+            tracing::error!(error = %e, "failed to connect to redis");
             return;
         }
     };
-    
-    let router = Arc::new(MockDexRouter::new());
-    let semaphore = Arc::new(Semaphore::new(10));
+    let wallets = Arc::new(WalletStore::new(shared_conn.clone(), scenario.clone()));
+    let risk = Arc::new(RiskEngine::new(shared_conn.clone(), scenario.clone()));
+
+    let halts = Arc::new(HaltBoard::new(shared_conn.clone()));
+    if let Err(e) = halts.refresh().await {
+        tracing::error!(error = %e, "failed to load trading halts");
+        return;
+    }
+    let halts_clone = halts.clone();
+    let redis_handler_clone = redis_handler.clone();
+    tokio::spawn(async move {
+        halts_clone.listen(redis_handler_clone).await;
+    });
+
+    let router = Arc::new(MockDexRouter::new(scenario));
+    let price_feed = Arc::new(PriceFeed::new(router.clone(), shared_conn.clone()));
+    let price_feed_clone = price_feed.clone();
+    tokio::spawn(async move {
+        price_feed_clone.run().await;
+    });
+    let quotes = Arc::new(QuoteBook::new(shared_conn.clone(), router.clone(), halts.clone()));
+    tokio::spawn(quotes.clone().listen(redis_handler.clone()));
+    let services = Arc::new(Services { router, ledger, wallets, quotes, risk, halts });
+    let semaphore = Arc::new(Semaphore::new(ORDER_WORKERS));
     
     let (status_manager, status_rx) = StatusManager::new();
     let status_tx = status_manager.get_sender();
+
+    let health_port = std::env::var("HEALTH_PORT").unwrap_or_else(|_| "8081".to_string());
+    let health = Arc::new(Health::new(redis_handler.streams(), shared_conn.clone(), semaphore.clone(), ORDER_WORKERS));
+    tokio::spawn(health.serve(health_port));
+
+    let triggers = Arc::new(TriggerBook::new(price_feed));
+    let triggers_clone = triggers.clone();
+    let services_clone = services.clone();
+    let semaphore_clone = semaphore.clone();
+    let status_tx_clone = status_tx.clone();
+    tokio::spawn(async move {
+        triggers_clone.run(services_clone, semaphore_clone, status_tx_clone).await;
+    });
+    tokio::spawn(triggers.clone().listen_cancels(redis_handler.clone(), shared_conn, status_tx.clone()));
     
     let redis_handler_clone = redis_handler.clone();
     tokio::spawn(async move {
         StatusManager::start_redis_writer(redis_handler_clone, status_rx).await;
     });
     
-    println!("connected to redis");
+    tracing::info!("connected to redis");
     
-    let mut last_id = "$".to_string();
+    let mut orders = redis_handler.reader("orders", "order_stream", "$");
     
     loop {
-        let result = redis_handler.read_order_stream(&mut conn, &last_id).await;
-        
-        match result {
-            Ok(redis_value) => {
-                match StreamParser::parse_redis_stream_response(redis_value) {
-                    Ok(messages) => {
-                        if messages.is_empty() {
-                            println!("waiting...");
-                            continue;
-                        }
-                        
-                        for message in messages {
-                            println!("msg: {}", message.message_id);
-                            println!("queue: {}", message.order_id);
-                            println!("   {} {} -> {}", message.amount, message.token_in, message.token_out);
-                            
-                            last_id = message.message_id.clone();
-                            
-                            OrderProcessor::spawn_order_task(
-                                router.clone(),
-                                semaphore.clone(),
-                                status_tx.clone(),
-                                message.order_id,
-                                message.token_in,
-                                message.token_out,
-                                message.amount,
-                                message.max_slippage,
-                            ).await;
-                        }
-                    }
-                    Err(e) => {
-                        println!("parse error: {}", e);
-                    }
+        for entry in StreamParser::parse_entries(orders.next().await) {
+            let mut message = match entry {
+                Ok(message) => message,
+                Err(malformed) => {
+                    fail_malformed(&status_tx, malformed).await;
+                    continue;
                 }
-            }
-            Err(e) => {
-                println!("redis err: {}", e);
-                tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
+            };
+            let span = tracing::info_span!("receive_order", order_id = %message.order_id, trace_id = tracing::field::Empty);
+            telemetry::follow(&span, message.trace_context.as_ref());
+            tracing::info!(
+                parent: &span,
+                message_id = %message.message_id,
+                amount = message.amount,
+                token_in = %message.token_in,
+                token_out = %message.token_out,
+                "order received"
+            );
+            // Whatever happens to the order next is traced under this span.
+            message.trace_context = Some(telemetry::inject(&span));
+            
+            if let Some(trigger) = message.trigger {
+                let oco_order_id = message.oco_order_id.clone();
+                if let Err(e) = triggers.arm(message.into_order(), trigger, oco_order_id, &status_tx).await {
+                    tracing::warn!(parent: &span, error = %e, "failed to arm order");
+                }
+            } else if let Some(schedule) = message.schedule {
+                ParentOrder::new(message.into_order(), schedule)
+                    .spawn(services.clone(), semaphore.clone(), status_tx.clone());
+            } else {
+                OrderProcessor::spawn_order_task(
+                    services.clone(),
+                    semaphore.clone(),
+                    status_tx.clone(),
+                    message.into_order(),
+                ).await;
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Fails an order whose `order_stream` entry could not be parsed, so it
+/// does not stay `pending`. Without a readable `order_id` there is no one to
+/// tell and the entry is only logged.
+async fn fail_malformed(status_tx: &tokio::sync::mpsc::Sender<types::StatusUpdate>, malformed: Malformed) {
+    let span = tracing::info_span!("receive_order", order_id = malformed.order_id.as_deref(), trace_id = tracing::field::Empty);
+    telemetry::follow(&span, malformed.trace_context.as_ref());
+    tracing::warn!(parent: &span, message_id = %malformed.message_id, "invalid order message");
+
+    let Some(order_id) = malformed.order_id else {
+        return;
+    };
+    let mut order = Order::new(order_id, malformed.user_id, String::new(), String::new(), 0.0, 0.0);
+    order.trace = telemetry::inject(&span);
+    order.update_status(OrderStatus::Failed { reason: "invalid_order".to_string() });
+    if let Err(e) = status_tx.send(types::StatusUpdate::from_order(&order)).await {
+        tracing::warn!(parent: &span, error = %e, "failed to send status");
+    }
+}
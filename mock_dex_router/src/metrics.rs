@@ -0,0 +1,75 @@
+use prometheus::{
+    exponential_buckets, register_histogram_vec, register_int_counter_vec, register_int_gauge,
+    HistogramVec, IntCounterVec, IntGauge,
+};
+use std::sync::LazyLock;
+
+pub use common::metrics::{render, set_streams};
+
+/// Time an order spent in a status before moving on to the next one.
+pub static ORDER_STAGE_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "router_order_stage_seconds",
+        "Time an order spent in a status before the next one",
+        &["stage"],
+        exponential_buckets(0.01, 2.0, 14).unwrap()
+    )
+    .unwrap()
+});
+
+/// Time from an order reaching the router to its final status.
+pub static ORDER_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "router_order_seconds",
+        "Time from an order reaching the router to its final status",
+        &["status"],
+        exponential_buckets(0.05, 2.0, 14).unwrap()
+    )
+    .unwrap()
+});
+
+pub static ORDERS_FINISHED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("router_orders_finished_total", "Orders by final status", &["status"]).unwrap()
+});
+
+pub static QUOTE_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "router_venue_quote_seconds",
+        "Venue quote latency",
+        &["venue", "outcome"],
+        exponential_buckets(0.005, 2.0, 12).unwrap()
+    )
+    .unwrap()
+});
+
+/// Venues that quoted while an order was routed. Together with
+/// [`VENUE_WINS`] this gives each venue's win rate.
+pub static VENUE_QUOTES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("router_venue_quotes_total", "Usable venue quotes while routing orders", &["venue"]).unwrap()
+});
+
+pub static VENUE_WINS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("router_venue_wins_total", "Orders routed to each venue", &["venue"]).unwrap()
+});
+
+pub static EXECUTION_RETRIES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("router_execution_retries_total", "Swap attempts that were retried", &["venue"]).unwrap()
+});
+
+/// Orders waiting for a free worker.
+pub static QUEUED_ORDERS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("router_order_queue_depth", "Orders waiting for a free worker").unwrap()
+});
+
+static BUSY_WORKERS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("router_workers_busy", "Workers executing an order").unwrap()
+});
+
+static WORKER_CAPACITY: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("router_workers_capacity", "Orders executed at once").unwrap()
+});
+
+pub fn set_workers(busy: usize, capacity: usize) {
+    BUSY_WORKERS.set(busy as i64);
+    WORKER_CAPACITY.set(capacity as i64);
+}
@@ -0,0 +1,88 @@
+use crate::pools::Pool;
+use crate::scenario::MevConfig;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Protection {
+    #[default]
+    Public,
+    Protected,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Sandwich {
+    pub front_run_in: f64,
+    pub front_run_out: f64,
+    pub victim_loss: f64,
+    pub searcher_profit: f64,
+}
+
+pub struct Searcher;
+
+impl Protection {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "public" => Some(Protection::Public),
+            "protected" => Some(Protection::Protected),
+            _ => None,
+        }
+    }
+}
+
+impl Searcher {
+    /// Buys ahead of a public victim swap as far as the victim's `min_out`
+    /// allows. Returns `None` when the order is too small, the attack roll
+    /// fails, or there is no profitable front-run.
+    pub fn front_run(pool: &mut Pool, config: &MevConfig, token_in: &str, amount_in: f64, min_out: f64) -> Option<Sandwich> {
+        if !config.enabled
+            || pool.notional(token_in, amount_in) < config.min_victim_notional
+            || rand::random::<f64>() >= config.attack_probability
+        {
+            return None;
+        }
+
+        let clean_out = pool.amount_out(token_in, amount_in);
+        if clean_out < min_out {
+            return None;
+        }
+
+        let victim_out_after = |front_run_in: f64| {
+            let mut simulated = pool.clone();
+            simulated.swap(token_in, front_run_in);
+            simulated.amount_out(token_in, amount_in)
+        };
+
+        let (mut low, mut high) = (0.0, amount_in * config.max_front_run_multiple);
+        if victim_out_after(high) >= min_out {
+            low = high;
+        } else {
+            for _ in 0..60 {
+                let mid = (low + high) / 2.0;
+                if victim_out_after(mid) >= min_out { low = mid } else { high = mid }
+            }
+        }
+
+        let token_out = if token_in == pool.base { pool.quote.clone() } else { pool.base.clone() };
+        let mut simulated = pool.clone();
+        let front_run_out = simulated.swap(token_in, low);
+        simulated.swap(token_in, amount_in);
+        if simulated.swap(&token_out, front_run_out) <= low {
+            return None;
+        }
+
+        let front_run_out = pool.swap(token_in, low);
+        Some(Sandwich {
+            front_run_in: low,
+            front_run_out,
+            victim_loss: clean_out - pool.amount_out(token_in, amount_in),
+            searcher_profit: 0.0,
+        })
+    }
+
+    /// Sells the front-run position back into the pool after the victim.
+    pub fn back_run(pool: &mut Pool, sandwich: &mut Sandwich, token_out: &str) {
+        let proceeds = pool.swap(token_out, sandwich.front_run_out);
+        sandwich.searcher_profit = proceeds - sandwich.front_run_in;
+    }
+}
@@ -1,4 +1,10 @@
+use crate::fees::{FeePaid, PriorityFeePolicy};
+use crate::ledger::Commitment;
+use crate::metrics;
+use crate::mev::{Protection, Sandwich};
+use common::telemetry::TraceContext;
 use serde::{Deserialize, Serialize};
+use std::time::Instant;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Order {
@@ -6,33 +12,105 @@ pub struct Order {
     pub user_id: String,
     pub token_in: String,
     pub token_out: String,
-    pub amount: u64,
+    pub amount: f64,
+    pub max_slippage: f64,
+    pub commitment: Commitment,
+    pub priority_fee: PriorityFeePolicy,
+    pub compute_units: Option<u32>,
+    pub protection: Protection,
+    pub quote_id: Option<String>,
+    pub parent_order_id: Option<String>,
     pub status: OrderStatus,
+    /// The context of the span working on the order, sent along with its
+    /// status updates.
+    #[serde(default)]
+    pub trace: TraceContext,
+    #[serde(skip, default = "Instant::now")]
+    received_at: Instant,
+    #[serde(skip, default = "Instant::now")]
+    status_since: Instant,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum OrderStatus {
+    Armed,
+    Triggered { reference_price: f64 },
     Pending,
     Routing,
     Building,
-    Submitted,
-    Confirmed { tx_hash: String },
+    Submitted { tx_hash: String, slot: u64, confirmation: Option<Commitment>, fee: FeePaid },
+    Confirmed {
+        tx_hash: String,
+        execution_price: f64,
+        slot: u64,
+        confirmation: Commitment,
+        fee: FeePaid,
+        #[serde(skip)]
+        sandwich: Option<Sandwich>,
+    },
     Failed { reason: String },
+    Rejected { reason: String },
+    Cancelled { reason: String },
 }
 
 impl Order {
-    pub fn new(order_id: String, user_id: String, token_in: String, token_out: String, amount: u64) -> Self {
+    pub fn new(order_id: String, user_id: String, token_in: String, token_out: String, amount: f64, max_slippage: f64) -> Self {
         Self {
             order_id,
             user_id,
             token_in,
             token_out,
             amount,
+            max_slippage,
+            commitment: Commitment::Confirmed,
+            priority_fee: PriorityFeePolicy::default(),
+            compute_units: None,
+            protection: Protection::Public,
+            quote_id: None,
+            parent_order_id: None,
             status: OrderStatus::Pending,
+            trace: TraceContext::new(),
+            received_at: Instant::now(),
+            status_since: Instant::now(),
         }
     }
 
+    /// Moves to `status`, recording how long the order spent in the one it
+    /// leaves and, for a final status, how long it took overall.
     pub fn update_status(&mut self, status: OrderStatus) {
+        let (from, to) = (self.status.as_str(), status.as_str());
+        if from != to {
+            metrics::ORDER_STAGE_SECONDS.with_label_values(&[from]).observe(self.status_since.elapsed().as_secs_f64());
+            self.status_since = Instant::now();
+        }
+        if status.is_final() {
+            metrics::ORDERS_FINISHED.with_label_values(&[to]).inc();
+            metrics::ORDER_SECONDS.with_label_values(&[to]).observe(self.received_at.elapsed().as_secs_f64());
+        }
         self.status = status;
     }
 }
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Armed => "armed",
+            OrderStatus::Triggered { .. } => "triggered",
+            OrderStatus::Pending => "pending",
+            OrderStatus::Routing => "routing",
+            OrderStatus::Building => "building",
+            OrderStatus::Submitted { .. } => "submitted",
+            OrderStatus::Confirmed { .. } => "confirmed",
+            OrderStatus::Failed { .. } => "failed",
+            OrderStatus::Rejected { .. } => "rejected",
+            OrderStatus::Cancelled { .. } => "cancelled",
+        }
+    }
+
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::Confirmed { .. } | OrderStatus::Failed { .. } | OrderStatus::Rejected { .. } | OrderStatus::Cancelled { .. }
+        )
+    }
+}
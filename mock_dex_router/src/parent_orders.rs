@@ -0,0 +1,223 @@
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::Instrument;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::time::Instant;
+use crate::models::{Order, OrderStatus};
+use crate::processor::{OrderProcessor, Services};
+use common::telemetry;
+use crate::types::StatusUpdate;
+
+/// An iceberg gives up after this many clips in a row fail to fill.
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+const FILL_TOLERANCE: f64 = 1e-9;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Schedule {
+    /// `slices` equal children spread evenly over `duration`, the first one
+    /// straight away. A slice that fails is not retried.
+    Twap { duration: Duration, slices: u32 },
+    /// One child of at most `clip_size` at a time; the next clip is revealed
+    /// once the previous one finishes, and a failed clip is offered again.
+    Iceberg { clip_size: f64 },
+}
+
+/// Aggregated fills of a parent's children, sent with every parent update.
+#[derive(Debug, Clone, Serialize)]
+pub struct Progress {
+    pub algo: &'static str,
+    pub filled_amount: f64,
+    pub remaining_amount: f64,
+    pub amount_out: f64,
+    pub average_price: Option<f64>,
+    pub children_sent: u32,
+    pub children_filled: u32,
+    pub children_failed: u32,
+    pub last_child_id: Option<String>,
+}
+
+/// A TWAP or iceberg order. The parent never trades itself: it sends child
+/// orders `{parent_id}-{n}` through the same pipeline as market orders and
+/// reports their combined progress under its own id.
+pub struct ParentOrder {
+    order: Order,
+    schedule: Schedule,
+    progress: Progress,
+    consecutive_failures: u32,
+}
+
+impl Schedule {
+    fn algo(&self) -> &'static str {
+        match self {
+            Schedule::Twap { .. } => "twap",
+            Schedule::Iceberg { .. } => "iceberg",
+        }
+    }
+}
+
+impl ParentOrder {
+    pub fn new(order: Order, schedule: Schedule) -> Self {
+        let progress = Progress {
+            algo: schedule.algo(),
+            filled_amount: 0.0,
+            remaining_amount: order.amount,
+            amount_out: 0.0,
+            average_price: None,
+            children_sent: 0,
+            children_filled: 0,
+            children_failed: 0,
+            last_child_id: None,
+        };
+        Self { order, schedule, progress, consecutive_failures: 0 }
+    }
+
+    pub fn spawn(
+        self,
+        services: Arc<Services>,
+        semaphore: Arc<Semaphore>,
+        status_tx: Arc<mpsc::Sender<StatusUpdate>>,
+    ) {
+        let span = tracing::info_span!("parent_order", order_id = %self.order.order_id, algo = self.progress.algo, trace_id = tracing::field::Empty);
+        telemetry::follow(&span, Some(&self.order.trace));
+        tokio::spawn(
+            async move {
+                if let Err(e) = self.run(&services, &semaphore, &status_tx).await {
+                    tracing::error!(error = %e, "parent order failed");
+                }
+            }
+            .instrument(span),
+        );
+    }
+
+    async fn run(
+        mut self,
+        services: &Services,
+        semaphore: &Semaphore,
+        status_tx: &mpsc::Sender<StatusUpdate>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.order.trace = telemetry::inject(&tracing::Span::current());
+        tracing::info!(amount = self.order.amount, token_in = %self.order.token_in, token_out = %self.order.token_out, "parent order started");
+        self.publish(status_tx, "pending", None).await?;
+
+        // Each child is checked again, but a parent split into children
+        // below `max_order_notional` must not get past it.
+        match services.risk.pre_trade(&self.order, &services.wallets).await {
+            Ok(None) => {}
+            Ok(Some(violation)) => {
+                tracing::info!(violation = violation.as_str(), "parent order rejected by risk check");
+                return self.publish(status_tx, "rejected", Some(violation.as_str().to_string())).await;
+            }
+            Err(e) => {
+                let reason = format!("Risk check unavailable: {}", e);
+                tracing::warn!(reason = %reason, "parent order failed");
+                return self.publish(status_tx, "failed", Some(reason)).await;
+            }
+        }
+
+        let started = Instant::now();
+        let mut rejected = None;
+        let mut last_failure = None;
+
+        while let Some((due, amount)) = self.next_child() {
+            tokio::time::sleep_until(started + due).await;
+
+            let child = self.child(amount);
+            self.progress.children_sent += 1;
+            self.progress.last_child_id = Some(child.order_id.clone());
+
+            match OrderProcessor::run_order(services, semaphore, status_tx, child).await {
+                Some(OrderStatus::Confirmed { execution_price, .. }) => self.record_fill(amount, execution_price),
+                Some(OrderStatus::Rejected { reason }) => {
+                    // Rejections come from halts, risk limits or the wallet,
+                    // which would turn away the remaining children too.
+                    self.record_failure();
+                    rejected = Some(reason);
+                    break;
+                }
+                Some(OrderStatus::Failed { reason }) => {
+                    self.record_failure();
+                    last_failure = Some(reason);
+                }
+                _ => {
+                    self.record_failure();
+                    last_failure = Some("Child order did not complete".to_string());
+                }
+            }
+
+            tracing::info!(filled_amount = self.progress.filled_amount, amount = self.order.amount, "parent order progress");
+            if self.next_child().is_some() {
+                self.publish(status_tx, "working", None).await?;
+            }
+        }
+
+        let (status, reason) = if self.progress.remaining_amount <= self.order.amount * FILL_TOLERANCE {
+            ("confirmed", None)
+        } else if self.progress.children_filled > 0 {
+            ("partially_filled", rejected.or(last_failure))
+        } else if let Some(reason) = rejected {
+            ("rejected", Some(reason))
+        } else {
+            ("failed", Some(last_failure.unwrap_or_else(|| "No child order filled".to_string())))
+        };
+        tracing::info!(status, "parent order finished");
+        self.publish(status_tx, status, reason).await
+    }
+
+    /// When the next child is due, relative to the parent's start, and its
+    /// size. `None` once the schedule is exhausted.
+    fn next_child(&self) -> Option<(Duration, f64)> {
+        let sent = self.progress.children_sent;
+        match self.schedule {
+            Schedule::Twap { duration, slices } => {
+                if sent >= slices {
+                    return None;
+                }
+                let slice = self.order.amount / slices as f64;
+                let amount = if sent + 1 == slices { self.order.amount - slice * sent as f64 } else { slice };
+                Some((duration / slices * sent, amount))
+            }
+            Schedule::Iceberg { clip_size } => {
+                let remaining = self.progress.remaining_amount;
+                if remaining <= self.order.amount * FILL_TOLERANCE || self.consecutive_failures >= MAX_CONSECUTIVE_FAILURES {
+                    return None;
+                }
+                Some((Duration::ZERO, clip_size.min(remaining)))
+            }
+        }
+    }
+
+    fn child(&self, amount: f64) -> Order {
+        let mut child = self.order.clone();
+        child.order_id = format!("{}-{}", self.order.order_id, self.progress.children_sent + 1);
+        child.amount = amount;
+        child.parent_order_id = Some(self.order.order_id.clone());
+        child.status = OrderStatus::Pending;
+        child
+    }
+
+    fn record_fill(&mut self, amount: f64, execution_price: f64) {
+        let progress = &mut self.progress;
+        progress.children_filled += 1;
+        progress.filled_amount += amount;
+        progress.remaining_amount = (self.order.amount - progress.filled_amount).max(0.0);
+        progress.amount_out += amount * execution_price;
+        progress.average_price = Some(progress.amount_out / progress.filled_amount);
+        self.consecutive_failures = 0;
+    }
+
+    fn record_failure(&mut self) {
+        self.progress.children_failed += 1;
+        self.consecutive_failures += 1;
+    }
+
+    async fn publish(
+        &self,
+        status_tx: &mpsc::Sender<StatusUpdate>,
+        status: &str,
+        reason: Option<String>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        status_tx.send(StatusUpdate::from_parent(&self.order, status, reason, &self.progress)).await?;
+        Ok(())
+    }
+}
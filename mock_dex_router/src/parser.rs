@@ -1,95 +1,123 @@
+use crate::fees::PriorityFeePolicy;
+use crate::ledger::Commitment;
+use crate::mev::Protection;
+use crate::models::Order;
+use crate::parent_orders::Schedule;
+use common::telemetry::TraceContext;
+use crate::triggers::{Trigger, TriggerKind};
+use redis::streams::StreamId;
 use serde_json::Value;
+use std::time::Duration;
 
 pub struct StreamParser;
 
+/// An `order_stream` entry that could not be parsed, with whatever could
+/// still be read from it.
+#[derive(Debug)]
+pub struct Malformed {
+    pub message_id: String,
+    pub order_id: Option<String>,
+    pub user_id: String,
+    pub trace_context: Option<TraceContext>,
+}
+
 impl StreamParser {
-    pub fn parse_redis_stream_response(
-        value: redis::Value,
-    ) -> Result<Vec<ParsedMessage>, Box<dyn std::error::Error + Send + Sync>> {
-        let messages = Self::extract_messages(value)?;
-                            let mut parsed_messages = Vec::new();
-                            
-                            for message in messages {
-            if let Some(parsed) = Self::parse_message(message) {
-                parsed_messages.push(parsed);
-            }
-        }
-        
-        Ok(parsed_messages)
+    pub fn parse_entries(entries: Vec<StreamId>) -> Vec<Result<ParsedMessage, Malformed>> {
+        entries
+            .into_iter()
+            .map(|message| Self::parse_message(&message).ok_or_else(|| Self::malformed(&message)))
+            .collect()
     }
 
-    fn extract_messages(value: redis::Value) -> Result<Vec<redis::Value>, Box<dyn std::error::Error + Send + Sync>> {
-        let streams = match value {
-            redis::Value::Bulk(streams) if !streams.is_empty() => streams,
-            _ => return Ok(vec![]),
-        };
-
-        let stream_data = match &streams[0] {
-            redis::Value::Bulk(data) if data.len() >= 2 => &data[1],
-            _ => return Ok(vec![]),
-        };
-
-        match stream_data {
-            redis::Value::Bulk(messages) => Ok(messages.clone()),
-            _ => Ok(vec![]),
+    fn malformed(message: &StreamId) -> Malformed {
+        let order_data = message
+            .get::<String>("order_data")
+            .and_then(|json| serde_json::from_str::<Value>(&json).ok())
+            .unwrap_or_default();
+        Malformed {
+            message_id: message.id.clone(),
+            order_id: order_data.get("order_id").and_then(|v| v.as_str()).map(str::to_string),
+            user_id: order_data.get("user_id").and_then(|v| v.as_str()).unwrap_or("anonymous").to_string(),
+            trace_context: order_data.get("trace_context").and_then(|v| serde_json::from_value(v.clone()).ok()),
         }
     }
 
-    fn parse_message(message: redis::Value) -> Option<ParsedMessage> {
-        let message_data = match message {
-            redis::Value::Bulk(data) if data.len() >= 2 => data,
-            _ => return None,
+    fn parse_message(message: &StreamId) -> Option<ParsedMessage> {
+        let order_json: String = message.get("order_data")?;
+        let order_data: Value = serde_json::from_str(&order_json).ok()?;
+        let schedule = match order_data.get("order_type").and_then(|v| v.as_str()) {
+            Some("twap") => Some(Schedule::Twap {
+                duration: Duration::from_secs(order_data.get("duration_secs")?.as_u64()?),
+                slices: order_data.get("slices")?.as_u64().filter(|&slices| slices > 0)? as u32,
+            }),
+            Some("iceberg") => Some(Schedule::Iceberg { clip_size: order_data.get("clip_size")?.as_f64().filter(|&clip| clip > 0.0)? }),
+            _ => None,
         };
-
-        let message_id = Self::get_string(&message_data[0])?;
-        let fields_data = match &message_data[1] {
-            redis::Value::Bulk(data) => data,
-            _ => return None,
+        let trigger_kind = match order_data.get("order_type").and_then(|v| v.as_str()) {
+            Some("stop_loss") => Some(TriggerKind::StopLoss),
+            Some("take_profit") => Some(TriggerKind::TakeProfit),
+            _ => None,
+        };
+        let trigger = match trigger_kind {
+            Some(kind) => Some(Trigger { kind, price: order_data.get("trigger_price")?.as_f64().filter(|&price| price > 0.0)? }),
+            None => None,
         };
-
-        let order_json = Self::find_order_data(fields_data)?;
-        let order_data: Value = serde_json::from_str(&order_json).ok()?;
         
         Some(ParsedMessage {
-            message_id,
+            message_id: message.id.clone(),
             order_id: order_data.get("order_id")?.as_str()?.to_string(),
+            user_id: order_data.get("user_id").and_then(|v| v.as_str()).unwrap_or("anonymous").to_string(),
             token_in: order_data.get("token_in")?.as_str()?.to_string(),
             token_out: order_data.get("token_out")?.as_str()?.to_string(),
             amount: order_data.get("amount")?.as_f64()?,
             max_slippage: order_data.get("max_slippage").and_then(|v| v.as_f64()).unwrap_or(5.0),
+            commitment: order_data.get("commitment").and_then(|v| v.as_str()).and_then(Commitment::parse),
+            priority_fee: order_data.get("priority_fee").and_then(|v| serde_json::from_value(v.clone()).ok()),
+            compute_units: order_data.get("compute_units").and_then(|v| v.as_u64()).map(|v| v as u32),
+            protection: order_data.get("protection").and_then(|v| v.as_str()).and_then(Protection::parse),
+            quote_id: order_data.get("quote_id").and_then(|v| v.as_str()).map(str::to_string),
+            schedule,
+            trigger,
+            oco_order_id: order_data.get("oco_order_id").and_then(|v| v.as_str()).map(str::to_string),
+            trace_context: order_data.get("trace_context").and_then(|v| serde_json::from_value(v.clone()).ok()),
         })
     }
-
-    fn find_order_data(fields_data: &[redis::Value]) -> Option<String> {
-        for i in (0..fields_data.len()).step_by(2) {
-            if i + 1 < fields_data.len() {
-                if let (Some(key), Some(value)) = (
-                    Self::get_string(&fields_data[i]),
-                    Self::get_string(&fields_data[i + 1])
-                ) {
-                    if key == "order_data" {
-                        return Some(value);
-                    }
-                }
-            }
-        }
-        None
-    }
-
-    fn get_string(value: &redis::Value) -> Option<String> {
-        match value {
-            redis::Value::Data(data) => Some(String::from_utf8_lossy(data).to_string()),
-            _ => None,
-        }
-    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ParsedMessage {
     pub message_id: String,
     pub order_id: String,
+    pub user_id: String,
     pub token_in: String,
     pub token_out: String,
     pub amount: f64,
     pub max_slippage: f64,
+    pub commitment: Option<Commitment>,
+    pub priority_fee: Option<PriorityFeePolicy>,
+    pub compute_units: Option<u32>,
+    pub protection: Option<Protection>,
+    pub quote_id: Option<String>,
+    pub schedule: Option<Schedule>,
+    pub trigger: Option<Trigger>,
+    pub oco_order_id: Option<String>,
+    /// The context of the backend span that published the order.
+    pub trace_context: Option<TraceContext>,
+}
+
+impl ParsedMessage {
+    pub fn into_order(self) -> Order {
+        let mut order = Order::new(self.order_id, self.user_id, self.token_in, self.token_out, self.amount, self.max_slippage);
+        if let Some(commitment) = self.commitment {
+            order.commitment = commitment;
+        }
+        if let Some(priority_fee) = self.priority_fee {
+            order.priority_fee = priority_fee;
+        }
+        order.compute_units = self.compute_units;
+        order.protection = self.protection.unwrap_or_default();
+        order.quote_id = self.quote_id;
+        order.trace = self.trace_context.unwrap_or_default();
+        order
+    }
 }
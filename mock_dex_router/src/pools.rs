@@ -0,0 +1,187 @@
+use crate::scenario::{PoolConfig, ScenarioHandle};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Constant-product pool. Reserves are in whole tokens; the fee is taken
+/// from the input and stays in the pool.
+#[derive(Debug, Clone)]
+pub struct Pool {
+    pub base: String,
+    pub quote: String,
+    pub base_reserve: f64,
+    pub quote_reserve: f64,
+    pub fee: f64,
+    pub reference_price: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwapQuote {
+    pub venue: String,
+    pub amount_out: f64,
+    pub price: f64,
+    pub price_impact_pct: f64,
+    pub fee_amount: f64,
+}
+
+struct PoolState {
+    generation: u64,
+    pools: HashMap<String, Pool>,
+}
+
+/// Live pool reserves per venue. Swaps move the reserves; a scenario reload
+/// resets every pool to its configured state.
+pub struct PoolBook {
+    scenario: ScenarioHandle,
+    state: Mutex<PoolState>,
+}
+
+impl Pool {
+    pub fn from_config(config: &PoolConfig) -> Self {
+        Self {
+            base: config.base.clone(),
+            quote: config.quote.clone(),
+            base_reserve: config.base_reserve,
+            quote_reserve: config.base_reserve * config.reference_price,
+            fee: config.fee_bps as f64 / 10_000.0,
+            reference_price: config.reference_price,
+        }
+    }
+
+    pub fn pair(&self) -> String {
+        format!("{}/{}", self.base, self.quote)
+    }
+
+    pub fn trades(&self, token_in: &str, token_out: &str) -> bool {
+        (self.base == token_in && self.quote == token_out) || (self.quote == token_in && self.base == token_out)
+    }
+
+    /// Spot price in quote tokens per base token.
+    pub fn price(&self) -> f64 {
+        self.quote_reserve / self.base_reserve
+    }
+
+    /// Value of `amount` of `token` in quote tokens at the spot price.
+    pub fn notional(&self, token: &str, amount: f64) -> f64 {
+        if token == self.base { amount * self.price() } else { amount }
+    }
+
+    pub fn amount_out(&self, token_in: &str, amount_in: f64) -> f64 {
+        let (reserve_in, reserve_out) = self.reserves(token_in);
+        let effective_in = amount_in * (1.0 - self.fee);
+        reserve_out * effective_in / (reserve_in + effective_in)
+    }
+
+    pub fn quote(&self, venue: &str, token_in: &str, amount_in: f64) -> SwapQuote {
+        let (reserve_in, reserve_out) = self.reserves(token_in);
+        let amount_out = self.amount_out(token_in, amount_in);
+        let fee_amount = amount_in * self.fee;
+        let ideal_out = (amount_in - fee_amount) * reserve_out / reserve_in;
+
+        SwapQuote {
+            venue: venue.to_string(),
+            amount_out,
+            price: if amount_in > 0.0 { amount_out / amount_in } else { 0.0 },
+            price_impact_pct: if ideal_out > 0.0 { (ideal_out - amount_out) / ideal_out * 100.0 } else { 0.0 },
+            fee_amount,
+        }
+    }
+
+    pub fn swap(&mut self, token_in: &str, amount_in: f64) -> f64 {
+        let amount_out = self.amount_out(token_in, amount_in);
+        if token_in == self.base {
+            self.base_reserve += amount_in;
+            self.quote_reserve -= amount_out;
+        } else {
+            self.quote_reserve += amount_in;
+            self.base_reserve -= amount_out;
+        }
+        amount_out
+    }
+
+    /// Moves the pool to `price` without changing its invariant, standing in
+    /// for arbitrage against the wider market.
+    pub fn set_price(&mut self, price: f64) {
+        let k = self.base_reserve * self.quote_reserve;
+        self.base_reserve = (k / price).sqrt();
+        self.quote_reserve = (k * price).sqrt();
+    }
+
+    fn reserves(&self, token_in: &str) -> (f64, f64) {
+        if token_in == self.base {
+            (self.base_reserve, self.quote_reserve)
+        } else {
+            (self.quote_reserve, self.base_reserve)
+        }
+    }
+}
+
+impl PoolBook {
+    pub fn new(scenario: ScenarioHandle) -> Self {
+        Self {
+            scenario,
+            state: Mutex::new(PoolState { generation: u64::MAX, pools: HashMap::new() }),
+        }
+    }
+
+    pub fn quote(&self, venue: &str, token_in: &str, token_out: &str, amount_in: f64) -> Result<SwapQuote, String> {
+        self.with_pool(venue, token_in, token_out, |pool| pool.quote(venue, token_in, amount_in))
+    }
+
+    /// Runs `f` against the live pool for the pair on `venue`, holding the
+    /// book lock so a sandwich and its victim settle atomically.
+    pub fn with_pool<T>(
+        &self,
+        venue: &str,
+        token_in: &str,
+        token_out: &str,
+        f: impl FnOnce(&mut Pool) -> T,
+    ) -> Result<T, String> {
+        let mut state = self.state.lock().unwrap();
+        self.refresh(&mut state);
+
+        state
+            .pools
+            .iter_mut()
+            .find(|(key, pool)| key.starts_with(&format!("{}:", venue)) && pool.trades(token_in, token_out))
+            .map(|(_, pool)| f(pool))
+            .ok_or_else(|| format!("{} has no {}/{} pool", venue, token_in, token_out))
+    }
+
+    /// Moves every pool to the price `step` picks for it and returns a copy
+    /// of each pool afterwards, with its venue.
+    pub fn tick(&self, step: impl Fn(&Pool) -> f64) -> Vec<(String, Pool)> {
+        let mut state = self.state.lock().unwrap();
+        self.refresh(&mut state);
+
+        state
+            .pools
+            .iter_mut()
+            .map(|(key, pool)| {
+                pool.set_price(step(pool));
+                let venue = key.split_once(':').map_or(key.as_str(), |(venue, _)| venue);
+                (venue.to_string(), pool.clone())
+            })
+            .collect()
+    }
+
+    fn refresh(&self, state: &mut PoolState) {
+        let generation = self.scenario.generation();
+        if state.generation == generation {
+            return;
+        }
+
+        let scenario = self.scenario.current();
+        state.pools = scenario
+            .venues
+            .iter()
+            .flat_map(|(venue, config)| {
+                config.pools.iter().map(move |pool| {
+                    let pool = Pool::from_config(pool);
+                    (format!("{}:{}", venue, pool.pair()), pool)
+                })
+            })
+            .collect();
+        state.generation = generation;
+    }
+}
@@ -0,0 +1,128 @@
+use redis::aio::ConnectionManager;
+use redis::streams::StreamMaxlen;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+use crate::dex_router::MockDexRouter;
+use crate::pools::Pool;
+
+const MARKET_DATA_STREAM: &str = "market_data";
+const MARKET_DATA_MAXLEN: usize = 10_000;
+
+/// Mid prices keyed `BASE/QUOTE`, in quote tokens per base token.
+pub type Prices = HashMap<String, f64>;
+
+/// One pair after a tick, as published on `market_data`. Bids and asks are
+/// the marginal prices for selling and buying the base token, after the
+/// pool fee.
+#[derive(Debug, Clone, Serialize)]
+pub struct PairSnapshot {
+    pub pair: String,
+    pub mid_price: f64,
+    pub best_bid: f64,
+    pub best_ask: f64,
+    pub spread_pct: f64,
+    pub venues: Vec<VenueSnapshot>,
+    pub timestamp_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VenueSnapshot {
+    pub venue: String,
+    pub price: f64,
+    pub bid: f64,
+    pub ask: f64,
+    pub base_reserve: f64,
+    pub quote_reserve: f64,
+}
+
+/// Drives the simulated market between trades. After every tick it
+/// publishes each pair's prices and reserves to the `market_data` stream
+/// and its mid price, averaged over the venues that pool it, to in-process
+/// subscribers.
+pub struct PriceFeed {
+    router: Arc<MockDexRouter>,
+    conn: ConnectionManager,
+    prices: watch::Sender<Prices>,
+}
+
+impl PriceFeed {
+    pub fn new(router: Arc<MockDexRouter>, conn: ConnectionManager) -> Self {
+        Self { router, conn, prices: watch::Sender::new(Prices::new()) }
+    }
+
+    pub async fn run(&self) {
+        loop {
+            let interval_ms = self.router.scenario().current().price_feed.interval_ms.max(1);
+            tokio::time::sleep(Duration::from_millis(interval_ms)).await;
+
+            let snapshots = snapshots(self.router.tick_market());
+            self.prices.send_replace(snapshots.iter().map(|s| (s.pair.clone(), s.mid_price)).collect());
+
+            if let Err(e) = self.publish(&snapshots).await {
+                tracing::warn!(error = %e, "market data publish failed");
+            }
+        }
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Prices> {
+        self.prices.subscribe()
+    }
+
+    async fn publish(&self, snapshots: &[PairSnapshot]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut pipe = redis::pipe();
+        for snapshot in snapshots {
+            let data = serde_json::to_string(snapshot)?;
+            pipe.xadd_maxlen(MARKET_DATA_STREAM, StreamMaxlen::Approx(MARKET_DATA_MAXLEN), "*", &[("market_data", data)])
+                .ignore();
+        }
+        let _: () = pipe.query_async(&mut self.conn.clone()).await?;
+        Ok(())
+    }
+}
+
+/// What one `token_in` buys in `token_out` at the mid price, whichever way
+/// round the pair is pooled.
+pub fn rate(prices: &Prices, token_in: &str, token_out: &str) -> Option<f64> {
+    prices
+        .get(&format!("{}/{}", token_in, token_out))
+        .copied()
+        .or_else(|| prices.get(&format!("{}/{}", token_out, token_in)).map(|price| 1.0 / price))
+}
+
+fn snapshots(pools: Vec<(String, Pool)>) -> Vec<PairSnapshot> {
+    let timestamp_ms = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+
+    let mut pairs: BTreeMap<String, Vec<VenueSnapshot>> = BTreeMap::new();
+    for (venue, pool) in pools {
+        let price = pool.price();
+        pairs.entry(pool.pair()).or_default().push(VenueSnapshot {
+            venue,
+            price,
+            bid: price * (1.0 - pool.fee),
+            ask: price / (1.0 - pool.fee),
+            base_reserve: pool.base_reserve,
+            quote_reserve: pool.quote_reserve,
+        });
+    }
+
+    pairs
+        .into_iter()
+        .map(|(pair, venues)| {
+            let mid_price = venues.iter().map(|v| v.price).sum::<f64>() / venues.len() as f64;
+            let best_bid = venues.iter().map(|v| v.bid).fold(f64::MIN, f64::max);
+            let best_ask = venues.iter().map(|v| v.ask).fold(f64::MAX, f64::min);
+            PairSnapshot {
+                pair,
+                mid_price,
+                best_bid,
+                best_ask,
+                spread_pct: (best_ask - best_bid) / mid_price * 100.0,
+                venues,
+                timestamp_ms,
+            }
+        })
+        .collect()
+}
@@ -1,180 +1,416 @@
 use tokio::sync::mpsc;
 use std::sync::Arc;
-use crate::dex_router::MockDexRouter;
+use std::time::Duration;
+use tracing::Instrument;
+use crate::dex_router::{Fill, MockDexRouter};
+use crate::fees::FeePaid;
+use crate::halts::HaltBoard;
+use crate::ledger::{Commitment, Landed, MockLedger};
+use crate::metrics;
+use crate::mev::Protection;
+use crate::models::{Order, OrderStatus};
+use crate::pools::SwapQuote;
+use crate::quotes::{QuoteBook, Redemption};
+use crate::risk::RiskEngine;
+use common::telemetry;
 use crate::types::StatusUpdate;
+use crate::wallets::{Reservation, WalletStore};
 
 pub struct OrderProcessor;
 
+/// Shared state every order task works against.
+pub struct Services {
+    pub router: Arc<MockDexRouter>,
+    pub ledger: Arc<MockLedger>,
+    pub wallets: Arc<WalletStore>,
+    pub quotes: Arc<QuoteBook>,
+    pub risk: Arc<RiskEngine>,
+    pub halts: Arc<HaltBoard>,
+}
+
+struct Execution {
+    tx_hash: String,
+    landed: Landed,
+    fee: FeePaid,
+    fill: Fill,
+}
+
+enum Attempt {
+    Landed(Execution),
+    Retry(String),
+    Reverted(String),
+}
+
 impl OrderProcessor {
     pub async fn process_order_with_channel(
-        router: &MockDexRouter,
+        services: &Services,
         status_tx: &mpsc::Sender<StatusUpdate>,
-        order_id: &str,
-        _token_in: &str,
-        _token_out: &str,
-        amount: f64,
-        max_slippage: f64,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-                
-        tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
-        Self::send_status(status_tx, order_id, "pending", None, None, None).await?;
-        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-        println!("   pending...");
-        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
-        Self::send_status(status_tx, order_id, "routing", None, None, None).await?;
-        println!("   routing...");
-        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
-        let (best_dex, best_price) = Self::get_best_price(router, amount).await;
-        println!("   best: {} {:.4}", best_dex, best_price);
-        
-        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
-        
-        let (slippage, final_price) = Self::check_slippage(best_price, max_slippage).await;
-        if slippage > max_slippage {
-            let reason = format!("Price moved {:.2}% (max allowed: {:.2}%)", slippage, max_slippage);
-            Self::send_status(status_tx, order_id, "failed", None, Some(&reason), None).await?;
-            println!("   fail");
-            println!("   why: {}", reason);
-            return Ok(());
-        }
-        
-        Self::send_status(status_tx, order_id, "building", None, None, None).await?;
-        println!("   building...");
-        
-        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-        
-        let tx_hash = format!("0x{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
-        Self::send_status(status_tx, order_id, "submitted", Some(&tx_hash), None, None).await?;
-        println!("   submitted...");
-        
-        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
-        
-        let success = Self::execute_with_retry(status_tx, order_id, &tx_hash).await;
-        if success {
-            Self::send_status(status_tx, order_id, "confirmed", Some(&tx_hash), None, Some(final_price)).await?;
-            println!("   ok");
-            println!("   tx: {}", tx_hash);
-            println!("   final price: {:.4}", final_price);
-        } else {
-            let reason = "Execution failed after 3 retry attempts";
-            Self::send_status(status_tx, order_id, "failed", None, Some(reason), None).await?;
-            println!("   fail");
-            println!("   why: {}", reason);
-        }
-        
-        println!("   done {}\n", order_id);
-        Ok(())
+        mut order: Order,
+    ) -> Result<OrderStatus, Box<dyn std::error::Error + Send + Sync>> {
+        let Services { router, ledger, wallets, quotes, risk, halts } = services;
+        let scenario = router.scenario().current();
+        let timings = &scenario.timings;
+
+        tokio::time::sleep(Duration::from_millis(timings.pending_ms)).await;
+        Self::transition(status_tx, &mut order, OrderStatus::Pending).await?;
+        tokio::time::sleep(Duration::from_millis(timings.pending_ms / 2)).await;
+        tracing::debug!("pending");
+
+        if let Some(halt) = halts.wait_while_paused(&order).await {
+            tracing::info!(reason = halt.reason.as_deref().unwrap_or("no reason"), "order rejected: halted");
+            let reason = "halted".to_string();
+            Self::transition(status_tx, &mut order, OrderStatus::Rejected { reason }).await?;
+            return Ok(order.status);
+        }
+
+        let mut quoted = None;
+        if let Some(quote_id) = order.quote_id.clone() {
+            let reason = match quotes.redeem(&quote_id, &order).await {
+                Ok(Redemption::Honoured(route)) => {
+                    quoted = Some(route);
+                    None
+                }
+                Ok(Redemption::Expired) => Some(OrderStatus::Rejected { reason: "quote_expired".to_string() }),
+                Ok(Redemption::Mismatch) => Some(OrderStatus::Rejected { reason: "quote_mismatch".to_string() }),
+                Err(e) => Some(OrderStatus::Failed { reason: format!("Quote unavailable: {}", e) }),
+            };
+            if let Some(status) = reason {
+                tracing::info!(quote_id = %quote_id, "quote not honoured");
+                Self::transition(status_tx, &mut order, status).await?;
+                return Ok(order.status);
+            }
+        }
+
+        match risk.pre_trade(&order, wallets).await {
+            Ok(None) => {}
+            Ok(Some(violation)) => {
+                tracing::info!(violation = violation.as_str(), "order rejected by risk check");
+                let reason = violation.as_str().to_string();
+                Self::transition(status_tx, &mut order, OrderStatus::Rejected { reason }).await?;
+                return Ok(order.status);
+            }
+            Err(e) => {
+                let reason = format!("Risk check unavailable: {}", e);
+                tracing::warn!(reason = %reason, "order failed");
+                Self::transition(status_tx, &mut order, OrderStatus::Failed { reason }).await?;
+                return Ok(order.status);
+            }
+        }
+
+        let held = wallets.enabled();
+        if held {
+            match wallets.reserve(&order.user_id, &order.token_in, order.amount).await {
+                Ok(Reservation::Held) => {}
+                Ok(Reservation::Insufficient { available }) => {
+                    tracing::info!(available, needed = order.amount, token = %order.token_in, "order rejected: insufficient balance");
+                    let reason = "insufficient_balance".to_string();
+                    Self::transition(status_tx, &mut order, OrderStatus::Rejected { reason }).await?;
+                    return Ok(order.status);
+                }
+                Err(e) => {
+                    let reason = format!("Wallet unavailable: {}", e);
+                    tracing::warn!(reason = %reason, "order failed");
+                    Self::transition(status_tx, &mut order, OrderStatus::Failed { reason }).await?;
+                    return Ok(order.status);
+                }
+            }
+        }
+
+        tokio::time::sleep(Duration::from_millis(timings.routing_ms)).await;
+        Self::transition(status_tx, &mut order, OrderStatus::Routing).await?;
+        tracing::debug!("routing");
+        tokio::time::sleep(Duration::from_millis(timings.routing_ms)).await;
+
+        let route = match quoted {
+            Some(route) => {
+                tracing::info!(venue = %route.venue, "using quoted route");
+                Some(route)
+            }
+            None => Self::get_best_quote(router, halts, &order).await,
+        };
+        let quote = match route {
+            Some(quote) => quote,
+            None => {
+                let reason = "No venue available for routing".to_string();
+                if held {
+                    Self::release_funds(wallets, &order).await;
+                }
+                tracing::warn!(reason = %reason, "order failed");
+                Self::transition(status_tx, &mut order, OrderStatus::Failed { reason }).await?;
+                return Ok(order.status);
+            }
+        };
+        tracing::info!(venue = %quote.venue, price = quote.price, price_impact_pct = quote.price_impact_pct, fee = quote.fee_amount, "route selected");
+
+        if halts.venue_halted(&quote.venue) {
+            tracing::info!(venue = %quote.venue, "order rejected: venue halted");
+            if held {
+                Self::release_funds(wallets, &order).await;
+            }
+            let reason = "halted".to_string();
+            Self::transition(status_tx, &mut order, OrderStatus::Rejected { reason }).await?;
+            return Ok(order.status);
+        }
+
+        if let Some(violation) = risk.price_band(&order, &quote) {
+            tracing::info!(violation = violation.as_str(), price = quote.price, "order rejected by risk check");
+            if held {
+                Self::release_funds(wallets, &order).await;
+            }
+            let reason = violation.as_str().to_string();
+            Self::transition(status_tx, &mut order, OrderStatus::Rejected { reason }).await?;
+            return Ok(order.status);
+        }
+
+        tokio::time::sleep(Duration::from_millis(timings.routing_ms)).await;
+
+        Self::transition(status_tx, &mut order, OrderStatus::Building).await?;
+        tracing::debug!("building");
+
+        tokio::time::sleep(Duration::from_millis(timings.building_ms)).await;
+
+        match Self::execute_with_retry(router, ledger, status_tx, &mut order, &quote).await? {
+            Ok(execution) => {
+                let Execution { tx_hash, landed, fee, fill } = execution;
+                tracing::info!(
+                    tx_hash = %tx_hash,
+                    slot = landed.slot,
+                    commitment = landed.commitment.as_str(),
+                    fee_lamports = fee.fee_lamports,
+                    execution_price = fill.price,
+                    "order confirmed"
+                );
+                if let Some(sandwich) = &fill.sandwich {
+                    tracing::info!(victim_loss = sandwich.victim_loss, searcher_profit = sandwich.searcher_profit, "order sandwiched");
+                }
+                if held
+                    && let Err(e) = wallets.settle(&order.user_id, &order.token_in, order.amount, &order.token_out, fill.amount_out).await
+                {
+                    tracing::error!(error = %e, "wallet settle failed");
+                }
+                if let Err(e) = risk.record_fill(&order, &quote.venue, fill.amount_out).await {
+                    tracing::error!(error = %e, "risk fill record failed");
+                }
+                Self::transition(status_tx, &mut order, OrderStatus::Confirmed {
+                    tx_hash,
+                    execution_price: fill.price,
+                    slot: landed.slot,
+                    confirmation: landed.commitment,
+                    fee,
+                    sandwich: fill.sandwich,
+                }).await?;
+            }
+            Err(reason) => {
+                if held {
+                    Self::release_funds(wallets, &order).await;
+                }
+                tracing::warn!(reason = %reason, "order failed");
+                Self::transition(status_tx, &mut order, OrderStatus::Failed { reason }).await?;
+            }
+        }
+
+        Ok(order.status)
     }
 
-    async fn send_status(
+    async fn transition(
         status_tx: &mpsc::Sender<StatusUpdate>,
-        order_id: &str,
-        status: &str,
-        tx_hash: Option<&str>,
-        reason: Option<&str>,
-        execution_price: Option<f64>,
+        order: &mut Order,
+        status: OrderStatus,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let status_update = StatusUpdate {
-            order_id: order_id.to_string(),
-            status: status.to_string(),
-            tx_hash: tx_hash.map(|s| s.to_string()),
-            reason: reason.map(|s| s.to_string()),
-            execution_price,
-        };
-        status_tx.send(status_update).await?;
+        order.update_status(status);
+        status_tx.send(StatusUpdate::from_order(order)).await?;
         Ok(())
     }
 
-    async fn get_best_price(router: &MockDexRouter, amount: f64) -> (&'static str, f64) {
-        let raydium_price = router.get_raydium_quote(amount as u64).await;
-        let meteora_price = router.get_meteora_quote(amount as u64).await;
-        
-        println!("   ray: {:.4}", raydium_price);
-        println!("   met: {:.4}", meteora_price);
-        
-        if raydium_price > meteora_price {
-            ("raydium", raydium_price)
-        } else {
-            ("meteora", meteora_price)
+    async fn release_funds(wallets: &WalletStore, order: &Order) {
+        if let Err(e) = wallets.release(&order.user_id, &order.token_in, order.amount).await {
+            tracing::error!(error = %e, "wallet release failed");
         }
     }
 
-    async fn check_slippage(best_price: f64, max_slippage: f64) -> (f64, f64) {
-        let price_movement = Self::simulate_price_movement();
-        let slippage = price_movement.abs();
-        
-        let final_price = best_price * (1.0 + price_movement / 100.0);
-        
-        println!("   price moved: {:.2}%", price_movement);
-        println!("   max slippage: {:.2}%", max_slippage);
-        println!("   final price: {:.4}", final_price);
-        
-        (slippage, final_price)
+    async fn get_best_quote(router: &MockDexRouter, halts: &HaltBoard, order: &Order) -> Option<SwapQuote> {
+        let mut best: Option<SwapQuote> = None;
+
+        let (open, halted) = halts.partition_venues(router.venues());
+        for venue in halted {
+            tracing::debug!(venue = %venue, "venue halted");
+        }
+        for (venue, quote) in router.quote_all(open, &order.token_in, &order.token_out, order.amount).await {
+            match quote {
+                Ok(quote) => {
+                    tracing::debug!(venue = %venue, price = quote.price, "venue quoted");
+                    metrics::VENUE_QUOTES.with_label_values(&[&venue]).inc();
+                    if best.as_ref().is_none_or(|b| quote.amount_out > b.amount_out) {
+                        best = Some(quote);
+                    }
+                }
+                Err(e) => tracing::debug!(venue = %venue, error = %e, "venue quote failed"),
+            }
+        }
+
+        if let Some(best) = &best {
+            metrics::VENUE_WINS.with_label_values(&[&best.venue]).inc();
+        }
+        best
     }
 
+    /// Sends the swap and waits for the order's commitment level, re-signing
+    /// with a fresh blockhash when the venue rejects the send or the ledger
+    /// drops or expires the transaction. A swap that lands but misses its
+    /// `min_out` reverts and is not retried.
     async fn execute_with_retry(
+        router: &MockDexRouter,
+        ledger: &MockLedger,
         status_tx: &mpsc::Sender<StatusUpdate>,
-        order_id: &str,
-        tx_hash: &str,
-    ) -> bool {
-        const MAX_RETRIES: u32 = 3;
-        const BASE_DELAY_MS: u64 = 1000;
-
-        for attempt in 1..=MAX_RETRIES {
-            println!("   attempt {}/{}", attempt, MAX_RETRIES);
-            
-            let success = Self::simulate_execution().await;
-            if success {
-                println!("   success on attempt {}", attempt);
-                return true;
+        order: &mut Order,
+        quote: &SwapQuote,
+    ) -> Result<Result<Execution, String>, Box<dyn std::error::Error + Send + Sync>> {
+        let timings = router.scenario().current().timings.clone();
+        let mut last_error = String::new();
+
+        for attempt in 1..=timings.max_retries {
+            tracing::debug!(attempt, max_retries = timings.max_retries, "sending swap");
+
+            match Self::send_transaction(router, ledger, status_tx, order, quote, attempt).await? {
+                Attempt::Landed(execution) => {
+                    return Ok(Ok(execution));
+                }
+                Attempt::Reverted(reason) => return Ok(Err(reason)),
+                Attempt::Retry(e) => {
+                    tracing::info!(attempt, error = %e, "swap attempt failed");
+                    metrics::EXECUTION_RETRIES.with_label_values(&[&quote.venue]).inc();
+                    last_error = e;
+                }
             }
-            
-            if attempt < MAX_RETRIES {
-                let delay_ms = BASE_DELAY_MS * (2_u64.pow(attempt - 1));
-                println!("   retry in {}ms", delay_ms);
-                tokio::time::sleep(tokio::time::Duration::from_millis(delay_ms)).await;
+
+            if attempt < timings.max_retries {
+                let delay_ms = timings.retry_base_delay_ms * (2_u64.pow(attempt - 1));
+                tracing::debug!(delay_ms, "retrying swap");
+                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
             }
         }
-        
-        false
-    }
 
-    async fn simulate_execution() -> bool {
-        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
-        rand::random::<f32>() > 0.3
+        Ok(Err(format!("Execution failed after {} retry attempts: {}", timings.max_retries, last_error)))
     }
 
-    fn simulate_price_movement() -> f64 {
-        let movement = (rand::random::<f64>() - 0.5) * 4.0;
-        movement
+    async fn send_transaction(
+        router: &MockDexRouter,
+        ledger: &MockLedger,
+        status_tx: &mpsc::Sender<StatusUpdate>,
+        order: &mut Order,
+        quote: &SwapQuote,
+        attempt: u32,
+    ) -> Result<Attempt, Box<dyn std::error::Error + Send + Sync>> {
+        let scenario = router.scenario().current();
+        let bundle = order.protection == Protection::Protected;
+        let blockhash = ledger.latest_blockhash();
+        let compute_units = order.compute_units.unwrap_or(scenario.ledger.fee_market.compute_units);
+        let tip = if bundle { scenario.mev.bundle_tip_lamports } else { 0 };
+        let fee = FeePaid::new(order.priority_fee.resolve(&ledger.recent_prioritization_fees()), compute_units, tip);
+
+        if let Err(e) = router.execute_swap(&quote.venue).await {
+            return Ok(Attempt::Retry(e));
+        }
+
+        let tx_hash = match ledger.submit(&order.order_id, &blockhash, attempt, fee.priority_fee, bundle) {
+            Ok(tx_hash) => tx_hash,
+            Err(e) => return Ok(Attempt::Retry(e.to_string())),
+        };
+        Self::transition(status_tx, order, OrderStatus::Submitted {
+            tx_hash: tx_hash.clone(),
+            slot: blockhash.slot,
+            confirmation: None,
+            fee,
+        }).await?;
+        tracing::info!(tx_hash = %tx_hash, blockhash_slot = blockhash.slot, priority_fee = fee.priority_fee, "transaction submitted");
+
+        let processed = match ledger.wait_for(&tx_hash, Commitment::Processed).await {
+            Ok(landed) => landed,
+            Err(e) => return Ok(Attempt::Retry(e.to_string())),
+        };
+
+        let fill = match router.settle_swap(quote, order) {
+            Ok(fill) => fill,
+            Err(reason) => return Ok(Attempt::Reverted(reason)),
+        };
+
+        if order.commitment == Commitment::Processed {
+            return Ok(Attempt::Landed(Execution { tx_hash, landed: processed, fee, fill }));
+        }
+
+        Self::transition(status_tx, order, OrderStatus::Submitted {
+            tx_hash: tx_hash.clone(),
+            slot: processed.slot,
+            confirmation: Some(processed.commitment),
+            fee,
+        }).await?;
+        tracing::info!(slot = processed.slot, "transaction processed");
+
+        match ledger.wait_for(&tx_hash, order.commitment).await {
+            Ok(landed) => Ok(Attempt::Landed(Execution { tx_hash, landed, fee, fill })),
+            Err(e) => Ok(Attempt::Reverted(format!("{} after landing in slot {}", e, processed.slot))),
+        }
     }
 
     pub async fn spawn_order_task(
-        router: Arc<MockDexRouter>,
+        services: Arc<Services>,
         semaphore: Arc<tokio::sync::Semaphore>,
         status_tx: Arc<mpsc::Sender<StatusUpdate>>,
-        order_id: String,
-        token_in: String,
-        token_out: String,
-        amount: f64,
-        max_slippage: f64,
+        order: Order,
     ) {
         tokio::spawn(async move {
-            let _permit = match semaphore.acquire().await {
+            Self::run_order(&services, &semaphore, &status_tx, order).await;
+        });
+    }
+
+    /// Processes the order once a slot is free and returns its final status,
+    /// or `None` if it could not be processed. The work is traced in an
+    /// `execute_order` span under the order's trace, which the status
+    /// updates it sends continue.
+    pub async fn run_order(
+        services: &Services,
+        semaphore: &tokio::sync::Semaphore,
+        status_tx: &mpsc::Sender<StatusUpdate>,
+        mut order: Order,
+    ) -> Option<OrderStatus> {
+        let span = tracing::info_span!(
+            "execute_order",
+            order_id = %order.order_id,
+            parent_order_id = order.parent_order_id.as_deref(),
+            trace_id = tracing::field::Empty,
+        );
+        telemetry::follow(&span, Some(&order.trace));
+        order.trace = telemetry::inject(&span);
+
+        async move {
+            // Paused orders wait here rather than holding one of the
+            // processing slots.
+            services.halts.wait_while_paused(&order).await;
+
+            metrics::QUEUED_ORDERS.inc();
+            let permit = semaphore.acquire().await;
+            metrics::QUEUED_ORDERS.dec();
+            let _permit = match permit {
                 Ok(permit) => permit,
                 Err(e) => {
-                    println!("semaphore fail {}: {}", order_id, e);
-                    return;
+                    tracing::error!(error = %e, "no worker available");
+                    return None;
                 }
             };
-            
-            println!("start: {}", order_id);
-            
-            if let Err(e) = Self::process_order_with_channel(&router, &status_tx, &order_id, &token_in, &token_out, amount, max_slippage).await {
-                println!("proc err {}: {}", order_id, e);
+
+            tracing::info!("order started");
+            match Self::process_order_with_channel(services, status_tx, order).await {
+                Ok(status) => {
+                    tracing::info!(status = status.as_str(), "order finished");
+                    Some(status)
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, "order processing failed");
+                    None
+                }
             }
-            
-            println!("done: {}", order_id);
-        });
+        }
+        .instrument(span)
+        .await
     }
 }
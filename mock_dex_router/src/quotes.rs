@@ -0,0 +1,172 @@
+use redis::aio::ConnectionManager;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::dex_router::MockDexRouter;
+use crate::fees::LAMPORTS_PER_SIGNATURE;
+use crate::halts::HaltBoard;
+use crate::models::Order;
+use crate::pools::SwapQuote;
+use crate::redis_handler::RedisHandler;
+
+const REPLY_TTL_MS: i64 = 30_000;
+
+#[derive(Debug, Deserialize)]
+struct QuoteRequest {
+    quote_id: String,
+    user_id: String,
+    token_in: String,
+    token_out: String,
+    amount: f64,
+}
+
+/// A priced route kept at `quote:{quote_id}` until it expires or an order
+/// redeems it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Quote {
+    pub quote_id: String,
+    pub user_id: String,
+    pub token_in: String,
+    pub token_out: String,
+    pub amount: f64,
+    pub route: SwapQuote,
+    pub venues: Vec<SwapQuote>,
+    pub unavailable: BTreeMap<String, String>,
+    pub network_fee_lamports: u64,
+    pub expires_at_ms: u64,
+}
+
+pub enum Redemption {
+    Honoured(SwapQuote),
+    Expired,
+    Mismatch,
+}
+
+/// Answers quote requests from the backend with the same venue quotes used
+/// for routing, without executing anything.
+pub struct QuoteBook {
+    conn: ConnectionManager,
+    router: Arc<MockDexRouter>,
+    halts: Arc<HaltBoard>,
+}
+
+impl QuoteBook {
+    pub fn new(conn: ConnectionManager, router: Arc<MockDexRouter>, halts: Arc<HaltBoard>) -> Self {
+        Self { conn, router, halts }
+    }
+
+    /// Reads `quote_requests` and replies on `quote_reply:{quote_id}`. Each
+    /// request is priced in its own task since venue quotes take a while.
+    pub async fn listen(self: Arc<Self>, redis_handler: RedisHandler) {
+        let mut reader = redis_handler.reader("quotes", "quote_requests", "$");
+        tracing::info!("quote listener started");
+
+        loop {
+            for message in reader.next().await {
+                let Some(request) = message
+                    .get::<String>("quote_data")
+                    .and_then(|data| serde_json::from_str::<QuoteRequest>(&data).ok())
+                else {
+                    tracing::warn!(message_id = %message.id, "bad quote request");
+                    continue;
+                };
+
+                let book = self.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = book.answer(request).await {
+                        tracing::warn!(error = %e, "quote reply failed");
+                    }
+                });
+            }
+        }
+    }
+
+    async fn answer(&self, request: QuoteRequest) -> Result<(), redis::RedisError> {
+        tracing::info!(quote_id = %request.quote_id, amount = request.amount, token_in = %request.token_in, token_out = %request.token_out, "quote requested");
+        let reply_key = format!("quote_reply:{}", request.quote_id);
+        let validity_ms = self.router.scenario().current().quotes.validity_ms;
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+
+        match self.price(request, validity_ms).await {
+            Ok(quote) => {
+                let data = serde_json::to_string(&quote).unwrap_or_default();
+                pipe.cmd("SET").arg(Self::quote_key(&quote.quote_id)).arg(&data).arg("PX").arg(validity_ms).ignore();
+                pipe.rpush(&reply_key, &data).ignore();
+            }
+            Err(reply) => {
+                pipe.rpush(&reply_key, reply.to_string()).ignore();
+            }
+        }
+
+        pipe.pexpire(&reply_key, REPLY_TTL_MS).ignore();
+        pipe.query_async(&mut self.conn.clone()).await
+    }
+
+    async fn price(&self, request: QuoteRequest, validity_ms: u64) -> Result<Quote, serde_json::Value> {
+        if self.halts.halt_for_pair(&request.token_in, &request.token_out).is_some() {
+            return Err(serde_json::json!({ "quote_id": request.quote_id, "error": "halted" }));
+        }
+
+        let (open, halted) = self.halts.partition_venues(self.router.venues());
+        let mut venues = Vec::new();
+        let mut unavailable: BTreeMap<String, String> = halted.into_iter().map(|venue| (venue, "halted".to_string())).collect();
+        for (venue, quote) in self.router.quote_all(open, &request.token_in, &request.token_out, request.amount).await {
+            match quote {
+                Ok(quote) => venues.push(quote),
+                Err(e) => {
+                    unavailable.insert(venue, e);
+                }
+            }
+        }
+
+        let Some(route) = venues.iter().max_by(|a, b| a.amount_out.total_cmp(&b.amount_out)).cloned() else {
+            return Err(serde_json::json!({
+                "quote_id": request.quote_id,
+                "error": "no_route",
+                "unavailable": unavailable,
+            }));
+        };
+
+        Ok(Quote {
+            quote_id: request.quote_id,
+            user_id: request.user_id,
+            token_in: request.token_in,
+            token_out: request.token_out,
+            amount: request.amount,
+            route,
+            venues,
+            unavailable,
+            network_fee_lamports: LAMPORTS_PER_SIGNATURE,
+            expires_at_ms: now_ms() + validity_ms,
+        })
+    }
+
+    /// Takes the order's quote so it cannot be used twice. The quote only
+    /// applies to the same user, pair and amount it was priced for.
+    pub async fn redeem(&self, quote_id: &str, order: &Order) -> Result<Redemption, redis::RedisError> {
+        let data: Option<String> = redis::cmd("GETDEL")
+            .arg(Self::quote_key(quote_id))
+            .query_async(&mut self.conn.clone())
+            .await?;
+
+        let Some(quote) = data.and_then(|data| serde_json::from_str::<Quote>(&data).ok()) else {
+            return Ok(Redemption::Expired);
+        };
+
+        let matches = quote.user_id == order.user_id
+            && quote.token_in == order.token_in
+            && quote.token_out == order.token_out
+            && quote.amount == order.amount;
+        Ok(if matches { Redemption::Honoured(quote.route) } else { Redemption::Mismatch })
+    }
+
+    fn quote_key(quote_id: &str) -> String {
+        format!("quote:{}", quote_id)
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
@@ -0,0 +1,151 @@
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::models::Order;
+use crate::pools::SwapQuote;
+use crate::scenario::{Scenario, ScenarioHandle};
+use crate::wallets::WalletStore;
+
+const LOSS_TTL_SECS: i64 = 2 * 86_400;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RiskViolation {
+    PairNotAllowed,
+    MaxOrderNotional,
+    MaxPosition,
+    DailyLossLimit,
+    PriceBand,
+}
+
+/// Pre-trade controls from the scenario's `[risk]` section. Daily losses are
+/// kept in Redis at `risk:{user}:loss:{day}` so they survive restarts.
+pub struct RiskEngine {
+    conn: ConnectionManager,
+    scenario: ScenarioHandle,
+}
+
+impl RiskViolation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RiskViolation::PairNotAllowed => "pair_not_allowed",
+            RiskViolation::MaxOrderNotional => "max_order_notional",
+            RiskViolation::MaxPosition => "max_position",
+            RiskViolation::DailyLossLimit => "daily_loss_limit",
+            RiskViolation::PriceBand => "price_band",
+        }
+    }
+}
+
+impl RiskEngine {
+    pub fn new(conn: ConnectionManager, scenario: ScenarioHandle) -> Self {
+        Self { conn, scenario }
+    }
+
+    /// Checks run before funds are held: allowed pairs, order notional,
+    /// resulting position in `token_out` and the day's realised loss.
+    pub async fn pre_trade(&self, order: &Order, wallets: &WalletStore) -> Result<Option<RiskViolation>, redis::RedisError> {
+        let scenario = self.scenario.current();
+        let config = &scenario.risk;
+        if !config.enabled {
+            return Ok(None);
+        }
+        let user = config.users.get(&order.user_id);
+
+        let allowed_pairs = user.and_then(|u| u.allowed_pairs.as_ref()).unwrap_or(&config.allowed_pairs);
+        if !allowed_pairs.is_empty()
+            && !allowed_pairs.iter().any(|pair| pair_matches(pair, &order.token_in, &order.token_out))
+        {
+            return Ok(Some(RiskViolation::PairNotAllowed));
+        }
+
+        // Without a pool for the pair there is nothing to value the order
+        // against, and routing fails it anyway.
+        let Some((rate, notional)) = reference(&scenario, None, &order.token_in, &order.token_out, order.amount) else {
+            return Ok(None);
+        };
+
+        let max_notional = user.and_then(|u| u.max_order_notional).unwrap_or(config.max_order_notional);
+        if notional > max_notional {
+            return Ok(Some(RiskViolation::MaxOrderNotional));
+        }
+
+        if wallets.enabled()
+            && let Some(max_position) = config.max_position.get(&order.token_out)
+        {
+            let position = wallets.position(&order.user_id, &order.token_out).await?;
+            if position + order.amount * rate > *max_position {
+                return Ok(Some(RiskViolation::MaxPosition));
+            }
+        }
+
+        let loss_limit = user.and_then(|u| u.daily_loss_limit).unwrap_or(config.daily_loss_limit);
+        let loss: Option<f64> = self.conn.clone().get(Self::loss_key(&order.user_id)).await?;
+        if loss.unwrap_or(0.0) >= loss_limit {
+            return Ok(Some(RiskViolation::DailyLossLimit));
+        }
+
+        Ok(None)
+    }
+
+    /// Fat-finger check on the routed quote: its price may not deviate from
+    /// the venue's reference price by more than `price_band_pct`.
+    pub fn price_band(&self, order: &Order, quote: &SwapQuote) -> Option<RiskViolation> {
+        let scenario = self.scenario.current();
+        if !scenario.risk.enabled {
+            return None;
+        }
+
+        let (rate, _) = reference(&scenario, Some(&quote.venue), &order.token_in, &order.token_out, order.amount)?;
+        let deviation_pct = (quote.price - rate).abs() / rate * 100.0;
+        (deviation_pct > scenario.risk.price_band_pct).then_some(RiskViolation::PriceBand)
+    }
+
+    /// Adds the fill's shortfall against the venue's reference price to the
+    /// user's loss for the day. Fills better than the reference reduce it.
+    pub async fn record_fill(&self, order: &Order, venue: &str, amount_out: f64) -> Result<(), redis::RedisError> {
+        let scenario = self.scenario.current();
+        let Some((rate, notional)) = reference(&scenario, Some(venue), &order.token_in, &order.token_out, order.amount) else {
+            return Ok(());
+        };
+
+        let loss = notional * (1.0 - amount_out / (order.amount * rate));
+        let key = Self::loss_key(&order.user_id);
+        redis::pipe()
+            .atomic()
+            .cmd("INCRBYFLOAT").arg(&key).arg(loss).ignore()
+            .expire(&key, LOSS_TTL_SECS).ignore()
+            .query_async(&mut self.conn.clone())
+            .await
+    }
+
+    fn loss_key(user_id: &str) -> String {
+        let day = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() / 86_400).unwrap_or(0);
+        format!("risk:{}:loss:{}", user_id, day)
+    }
+}
+
+/// Pairs are written `BASE/QUOTE` and allow trading in either direction.
+fn pair_matches(pair: &str, token_in: &str, token_out: &str) -> bool {
+    match pair.split_once('/') {
+        Some((a, b)) => (a == token_in && b == token_out) || (a == token_out && b == token_in),
+        None => false,
+    }
+}
+
+/// Reference rate in `token_out` per `token_in`, and the order's notional in
+/// the pool's quote token, from the first configured pool for the pair.
+fn reference(scenario: &Scenario, venue: Option<&str>, token_in: &str, token_out: &str, amount: f64) -> Option<(f64, f64)> {
+    scenario
+        .venues
+        .iter()
+        .filter(|(name, _)| venue.is_none_or(|venue| venue == name.as_str()))
+        .flat_map(|(_, config)| &config.pools)
+        .find(|pool| (pool.base == token_in && pool.quote == token_out) || (pool.base == token_out && pool.quote == token_in))
+        .map(|pool| {
+            if pool.base == token_in {
+                (pool.reference_price, amount * pool.reference_price)
+            } else {
+                (1.0 / pool.reference_price, amount)
+            }
+        })
+}
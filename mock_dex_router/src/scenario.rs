@@ -0,0 +1,677 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    pub name: String,
+    pub timings: Timings,
+    pub price_drift: PriceDrift,
+    pub price_feed: PriceFeedConfig,
+    pub ledger: LedgerConfig,
+    pub mev: MevConfig,
+    pub wallets: WalletConfig,
+    pub quotes: QuoteConfig,
+    pub risk: RiskConfig,
+    pub venues: BTreeMap<String, VenueConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Timings {
+    pub pending_ms: u64,
+    pub routing_ms: u64,
+    pub building_ms: u64,
+    pub retry_base_delay_ms: u64,
+    pub max_retries: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LedgerConfig {
+    pub slot_ms: u64,
+    pub blockhash_valid_slots: u64,
+    pub confirmed_depth: u64,
+    pub finalized_depth: u64,
+    pub inclusion_rate: f64,
+    pub drop_rate: f64,
+    pub max_txs_per_block: usize,
+    pub fee_market: FeeMarketConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FeeMarketConfig {
+    pub compute_units: u32,
+    pub competing_txs_per_slot: usize,
+    pub competing_fee_median: u64,
+    pub competing_fee_sigma: f64,
+    pub congestion_drop_rate: f64,
+    pub recent_fee_slots: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct VenueConfig {
+    pub pools: Vec<PoolConfig>,
+    pub quote_latency: Latency,
+    pub execution_latency: Latency,
+    pub failures: BTreeMap<FailureKind, f64>,
+    pub outages: Vec<OutageWindow>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PoolConfig {
+    pub base: String,
+    pub quote: String,
+    pub reference_price: f64,
+    pub base_reserve: f64,
+    #[serde(default = "default_fee_bps")]
+    pub fee_bps: u32,
+}
+
+/// Simulated searcher sandwiching public orders, and the cost of avoiding it
+/// with private bundle submission.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MevConfig {
+    pub enabled: bool,
+    pub attack_probability: f64,
+    pub min_victim_notional: f64,
+    pub max_front_run_multiple: f64,
+    pub bundle_tip_lamports: u64,
+    pub bundle_inclusion_rate: f64,
+}
+
+/// Balances enforced before routing. Wallets that do not exist yet are
+/// created with `initial_balances` on first use.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WalletConfig {
+    pub enabled: bool,
+    pub initial_balances: BTreeMap<String, f64>,
+}
+
+/// Market movement between trades. Every `interval_ms` each pool is pulled
+/// `mean_reversion` of the way back to its reference price and then moved by
+/// a normal step of `volatility_pct`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PriceFeedConfig {
+    pub interval_ms: u64,
+    pub volatility_pct: f64,
+    pub mean_reversion: f64,
+}
+
+/// How long a quote from `POST /api/quotes` can be redeemed by an order.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct QuoteConfig {
+    pub validity_ms: u64,
+}
+
+/// Pre-trade controls. Notional and losses are in the pool's quote token at
+/// its reference price. `users` overrides the defaults per `user_id`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RiskConfig {
+    pub enabled: bool,
+    pub max_order_notional: f64,
+    pub max_position: BTreeMap<String, f64>,
+    pub allowed_pairs: Vec<String>,
+    pub daily_loss_limit: f64,
+    pub price_band_pct: f64,
+    pub users: BTreeMap<String, UserRiskConfig>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct UserRiskConfig {
+    pub max_order_notional: Option<f64>,
+    pub allowed_pairs: Option<Vec<String>>,
+    pub daily_loss_limit: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    NetworkFailure,
+    RpcTimeout,
+    SimulationFailed,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "distribution", rename_all = "snake_case")]
+pub enum Latency {
+    Fixed { ms: u64 },
+    Uniform { min_ms: u64, max_ms: u64 },
+    Normal { mean_ms: f64, stddev_ms: f64 },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "model", rename_all = "snake_case")]
+pub enum PriceDrift {
+    Uniform { max_move_pct: f64 },
+    Normal { volatility_pct: f64 },
+    Jump { volatility_pct: f64, jump_probability: f64, jump_pct: f64 },
+}
+
+/// Window relative to the moment the scenario was (re)loaded, so editing the
+/// file triggers the outage "from now".
+#[derive(Debug, Clone, Deserialize)]
+pub struct OutageWindow {
+    #[serde(default)]
+    pub start_after_secs: u64,
+    pub duration_secs: u64,
+}
+
+impl FailureKind {
+    pub fn describe(&self, venue: &str) -> String {
+        match self {
+            FailureKind::NetworkFailure => format!("Simulated network failure on {}", venue),
+            FailureKind::RpcTimeout => format!("RPC timeout on {}", venue),
+            FailureKind::SimulationFailed => format!("Transaction simulation failed on {}", venue),
+        }
+    }
+}
+
+impl Scenario {
+    /// Rejects values that would make the simulation panic or behave
+    /// nonsensically: rates outside [0, 1], negative latencies, empty pool
+    /// lists and zero-sized slots or blocks.
+    pub fn validate(&self) -> Result<(), String> {
+        let t = &self.timings;
+        if t.max_retries == 0 {
+            return Err("timings.max_retries must be at least 1".into());
+        }
+
+        let l = &self.ledger;
+        if l.slot_ms == 0 {
+            return Err("ledger.slot_ms must be positive".into());
+        }
+        if l.max_txs_per_block == 0 {
+            return Err("ledger.max_txs_per_block must be positive".into());
+        }
+        if l.blockhash_valid_slots == 0 {
+            return Err("ledger.blockhash_valid_slots must be positive".into());
+        }
+        if l.finalized_depth < l.confirmed_depth {
+            return Err("ledger.finalized_depth must not be below confirmed_depth".into());
+        }
+        probability("ledger.inclusion_rate", l.inclusion_rate)?;
+        probability("ledger.drop_rate", l.drop_rate)?;
+
+        let f = &l.fee_market;
+        if f.recent_fee_slots == 0 {
+            return Err("ledger.fee_market.recent_fee_slots must be positive".into());
+        }
+        non_negative("ledger.fee_market.competing_fee_sigma", f.competing_fee_sigma)?;
+        probability("ledger.fee_market.congestion_drop_rate", f.congestion_drop_rate)?;
+
+        self.price_drift.validate()?;
+        if self.price_feed.interval_ms == 0 {
+            return Err("price_feed.interval_ms must be positive".into());
+        }
+        non_negative("price_feed.volatility_pct", self.price_feed.volatility_pct)?;
+        probability("price_feed.mean_reversion", self.price_feed.mean_reversion)?;
+
+        let m = &self.mev;
+        probability("mev.attack_probability", m.attack_probability)?;
+        probability("mev.bundle_inclusion_rate", m.bundle_inclusion_rate)?;
+        non_negative("mev.min_victim_notional", m.min_victim_notional)?;
+        non_negative("mev.max_front_run_multiple", m.max_front_run_multiple)?;
+
+        for (token, balance) in &self.wallets.initial_balances {
+            non_negative(&format!("wallets.initial_balances.{token}"), *balance)?;
+        }
+        non_negative("risk.price_band_pct", self.risk.price_band_pct)?;
+
+        if self.venues.is_empty() {
+            return Err("at least one venue is required".into());
+        }
+        for (name, venue) in &self.venues {
+            venue.validate(name)?;
+        }
+        Ok(())
+    }
+}
+
+impl VenueConfig {
+    fn validate(&self, name: &str) -> Result<(), String> {
+        if self.pools.is_empty() {
+            return Err(format!("venues.{name}.pools must not be empty"));
+        }
+        for pool in &self.pools {
+            let pair = format!("venues.{name} {}/{}", pool.base, pool.quote);
+            positive(&format!("{pair}: reference_price"), pool.reference_price)?;
+            positive(&format!("{pair}: base_reserve"), pool.base_reserve)?;
+            if pool.fee_bps >= 10_000 {
+                return Err(format!("{pair}: fee_bps must be below 10000"));
+            }
+        }
+        self.quote_latency.validate(&format!("venues.{name}.quote_latency"))?;
+        self.execution_latency.validate(&format!("venues.{name}.execution_latency"))?;
+        for (kind, rate) in &self.failures {
+            probability(&format!("venues.{name}.failures.{kind:?}"), *rate)?;
+        }
+        Ok(())
+    }
+}
+
+impl Latency {
+    fn validate(&self, field: &str) -> Result<(), String> {
+        match self {
+            Latency::Fixed { .. } => Ok(()),
+            Latency::Uniform { min_ms, max_ms } if min_ms > max_ms => {
+                Err(format!("{field}: min_ms must not exceed max_ms"))
+            }
+            Latency::Uniform { .. } => Ok(()),
+            Latency::Normal { mean_ms, stddev_ms } => {
+                non_negative(&format!("{field}.mean_ms"), *mean_ms)?;
+                non_negative(&format!("{field}.stddev_ms"), *stddev_ms)
+            }
+        }
+    }
+
+    pub fn sample(&self) -> Duration {
+        let ms = match self {
+            Latency::Fixed { ms } => *ms as f64,
+            Latency::Uniform { min_ms, max_ms } => {
+                *min_ms as f64 + rand::random::<f64>() * max_ms.saturating_sub(*min_ms) as f64
+            }
+            Latency::Normal { mean_ms, stddev_ms } => mean_ms + stddev_ms * standard_normal(),
+        };
+        Duration::from_millis(ms.max(0.0) as u64)
+    }
+}
+
+impl PriceDrift {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            PriceDrift::Uniform { max_move_pct } => non_negative("price_drift.max_move_pct", *max_move_pct),
+            PriceDrift::Normal { volatility_pct } => non_negative("price_drift.volatility_pct", *volatility_pct),
+            PriceDrift::Jump { volatility_pct, jump_probability, jump_pct } => {
+                non_negative("price_drift.volatility_pct", *volatility_pct)?;
+                probability("price_drift.jump_probability", *jump_probability)?;
+                non_negative("price_drift.jump_pct", *jump_pct)
+            }
+        }
+    }
+
+    /// Price movement in percent between quote and execution.
+    pub fn sample_pct(&self) -> f64 {
+        match self {
+            PriceDrift::Uniform { max_move_pct } => (rand::random::<f64>() - 0.5) * 2.0 * max_move_pct,
+            PriceDrift::Normal { volatility_pct } => volatility_pct * standard_normal(),
+            PriceDrift::Jump { volatility_pct, jump_probability, jump_pct } => {
+                let mut movement = volatility_pct * standard_normal();
+                if rand::random::<f64>() < *jump_probability {
+                    let direction = if rand::random::<bool>() { 1.0 } else { -1.0 };
+                    movement += direction * jump_pct;
+                }
+                movement
+            }
+        }
+    }
+}
+
+impl VenueConfig {
+    pub fn is_down(&self, since_load: Duration) -> bool {
+        let elapsed = since_load.as_secs();
+        self.outages
+            .iter()
+            .any(|w| elapsed >= w.start_after_secs && elapsed < w.start_after_secs + w.duration_secs)
+    }
+
+    pub fn roll_failure(&self) -> Option<FailureKind> {
+        self.failures
+            .iter()
+            .find(|(_, rate)| rand::random::<f64>() < **rate)
+            .map(|(kind, _)| *kind)
+    }
+}
+
+fn probability(field: &str, value: f64) -> Result<(), String> {
+    if (0.0..=1.0).contains(&value) {
+        Ok(())
+    } else {
+        Err(format!("{field} must be between 0 and 1, got {value}"))
+    }
+}
+
+fn positive(field: &str, value: f64) -> Result<(), String> {
+    if value > 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(format!("{field} must be positive, got {value}"))
+    }
+}
+
+fn non_negative(field: &str, value: f64) -> Result<(), String> {
+    if value >= 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(format!("{field} must not be negative, got {value}"))
+    }
+}
+
+pub fn standard_normal() -> f64 {
+    let u1 = rand::random::<f64>().max(f64::MIN_POSITIVE);
+    let u2 = rand::random::<f64>();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        let mut venues = BTreeMap::new();
+        venues.insert("raydium".to_string(), VenueConfig {
+            pools: vec![PoolConfig::sol_usdc(220.0, 25)],
+            quote_latency: Latency::Fixed { ms: 200 },
+            ..VenueConfig::default()
+        });
+        venues.insert("meteora".to_string(), VenueConfig {
+            pools: vec![PoolConfig::sol_usdc(218.0, 20)],
+            quote_latency: Latency::Fixed { ms: 250 },
+            ..VenueConfig::default()
+        });
+
+        Self {
+            name: "default".to_string(),
+            timings: Timings::default(),
+            price_drift: PriceDrift::default(),
+            price_feed: PriceFeedConfig::default(),
+            ledger: LedgerConfig::default(),
+            mev: MevConfig::default(),
+            wallets: WalletConfig::default(),
+            quotes: QuoteConfig::default(),
+            risk: RiskConfig::default(),
+            venues,
+        }
+    }
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Self {
+            pending_ms: 1000,
+            routing_ms: 200,
+            building_ms: 500,
+            retry_base_delay_ms: 1000,
+            max_retries: 3,
+        }
+    }
+}
+
+impl Default for LedgerConfig {
+    fn default() -> Self {
+        Self {
+            slot_ms: 400,
+            blockhash_valid_slots: 150,
+            confirmed_depth: 2,
+            finalized_depth: 32,
+            inclusion_rate: 0.8,
+            drop_rate: 0.02,
+            max_txs_per_block: 64,
+            fee_market: FeeMarketConfig::default(),
+        }
+    }
+}
+
+impl Default for FeeMarketConfig {
+    fn default() -> Self {
+        Self {
+            compute_units: 200_000,
+            competing_txs_per_slot: 32,
+            competing_fee_median: 5_000,
+            competing_fee_sigma: 1.0,
+            congestion_drop_rate: 0.05,
+            recent_fee_slots: 150,
+        }
+    }
+}
+
+impl Default for VenueConfig {
+    fn default() -> Self {
+        let mut failures = BTreeMap::new();
+        failures.insert(FailureKind::NetworkFailure, 0.3);
+
+        Self {
+            pools: vec![PoolConfig::sol_usdc(220.0, 25)],
+            quote_latency: Latency::Fixed { ms: 200 },
+            execution_latency: Latency::Fixed { ms: 200 },
+            failures,
+            outages: Vec::new(),
+        }
+    }
+}
+
+impl PoolConfig {
+    fn sol_usdc(reference_price: f64, fee_bps: u32) -> Self {
+        Self {
+            base: "SOL".to_string(),
+            quote: "USDC".to_string(),
+            reference_price,
+            base_reserve: 50_000.0,
+            fee_bps,
+        }
+    }
+}
+
+fn default_fee_bps() -> u32 {
+    25
+}
+
+impl Default for MevConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            attack_probability: 0.8,
+            min_victim_notional: 2_000.0,
+            max_front_run_multiple: 20.0,
+            bundle_tip_lamports: 10_000,
+            bundle_inclusion_rate: 0.6,
+        }
+    }
+}
+
+impl Default for WalletConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            initial_balances: BTreeMap::from([
+                ("SOL".to_string(), 1_000.0),
+                ("USDC".to_string(), 250_000.0),
+            ]),
+        }
+    }
+}
+
+impl PriceFeedConfig {
+    /// The pool's next price, one tick after `price`.
+    pub fn step(&self, price: f64, reference_price: f64) -> f64 {
+        let pulled = price + (reference_price - price) * self.mean_reversion;
+        pulled * (1.0 + self.volatility_pct / 100.0 * standard_normal())
+    }
+}
+
+impl Default for PriceFeedConfig {
+    fn default() -> Self {
+        Self { interval_ms: 1000, volatility_pct: 0.1, mean_reversion: 0.05 }
+    }
+}
+
+impl Default for QuoteConfig {
+    fn default() -> Self {
+        Self { validity_ms: 10_000 }
+    }
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_order_notional: 250_000.0,
+            max_position: BTreeMap::from([
+                ("SOL".to_string(), 10_000.0),
+                ("USDC".to_string(), 2_500_000.0),
+            ]),
+            allowed_pairs: Vec::new(),
+            daily_loss_limit: 25_000.0,
+            price_band_pct: 5.0,
+            users: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for PriceDrift {
+    fn default() -> Self {
+        PriceDrift::Uniform { max_move_pct: 2.0 }
+    }
+}
+
+struct Loaded {
+    scenario: Arc<Scenario>,
+    generation: u64,
+    loaded_at: Instant,
+    modified: Option<SystemTime>,
+}
+
+#[derive(Clone)]
+pub struct ScenarioHandle {
+    path: Option<PathBuf>,
+    inner: Arc<RwLock<Loaded>>,
+}
+
+impl ScenarioHandle {
+    pub fn from_env() -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let path = std::env::var("SCENARIO_PATH").ok().map(PathBuf::from);
+        let (scenario, modified) = match &path {
+            Some(path) => (Self::read(path)?, Self::modified(path)),
+            None => (Scenario::default(), None),
+        };
+        tracing::info!(scenario = %scenario.name, "scenario loaded");
+
+        Ok(Self {
+            path,
+            inner: Arc::new(RwLock::new(Loaded {
+                scenario: Arc::new(scenario),
+                generation: 0,
+                loaded_at: Instant::now(),
+                modified,
+            })),
+        })
+    }
+
+    /// A handle that never reloads, for tests.
+    #[cfg(test)]
+    pub fn fixed(scenario: Scenario) -> Self {
+        Self {
+            path: None,
+            inner: Arc::new(RwLock::new(Loaded {
+                scenario: Arc::new(scenario),
+                generation: 0,
+                loaded_at: Instant::now(),
+                modified: None,
+            })),
+        }
+    }
+
+    pub fn current(&self) -> Arc<Scenario> {
+        self.inner.read().unwrap().scenario.clone()
+    }
+
+    /// Increments on every successful reload.
+    pub fn generation(&self) -> u64 {
+        self.inner.read().unwrap().generation
+    }
+
+    pub fn since_load(&self) -> Duration {
+        self.inner.read().unwrap().loaded_at.elapsed()
+    }
+
+    /// Polls the scenario file and swaps it in when it changes. A file that
+    /// fails to parse or validate is reported and the previous scenario stays
+    /// active.
+    pub async fn watch(self) {
+        let Some(path) = self.path.clone() else { return };
+        let interval = std::env::var("SCENARIO_RELOAD_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1000);
+
+        loop {
+            tokio::time::sleep(Duration::from_millis(interval)).await;
+
+            let modified = Self::modified(&path);
+            if modified == self.inner.read().unwrap().modified {
+                continue;
+            }
+
+            match Self::read(&path) {
+                Ok(scenario) => {
+                    tracing::info!(scenario = %scenario.name, "scenario reloaded");
+                    let mut loaded = self.inner.write().unwrap();
+                    *loaded = Loaded {
+                        scenario: Arc::new(scenario),
+                        generation: loaded.generation + 1,
+                        loaded_at: Instant::now(),
+                        modified,
+                    };
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, "scenario reload failed");
+                    self.inner.write().unwrap().modified = modified;
+                }
+            }
+        }
+    }
+
+    fn read(path: &Path) -> Result<Scenario, Box<dyn std::error::Error + Send + Sync>> {
+        let contents = std::fs::read_to_string(path)?;
+        let scenario = match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml") | Some("yml") => serde_yaml::from_str(&contents)?,
+            _ => toml::from_str(&contents)?,
+        };
+        Scenario::validate(&scenario)?;
+        Ok(scenario)
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_scenarios_validate() {
+        for entry in std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios")).unwrap() {
+            let path = entry.unwrap().path();
+            if let Err(e) = ScenarioHandle::read(&path) {
+                panic!("{}: {e}", path.display());
+            }
+        }
+        Scenario::default().validate().unwrap();
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        let mut scenario = Scenario::default();
+        scenario.ledger.drop_rate = 1.5;
+        assert!(scenario.validate().unwrap_err().contains("ledger.drop_rate"));
+
+        let mut scenario = Scenario::default();
+        scenario.ledger.max_txs_per_block = 0;
+        assert!(scenario.validate().is_err());
+
+        let mut scenario = Scenario::default();
+        let venue = scenario.venues.values_mut().next().unwrap();
+        venue.execution_latency = Latency::Normal { mean_ms: -5.0, stddev_ms: 1.0 };
+        assert!(scenario.validate().unwrap_err().contains("execution_latency.mean_ms"));
+    }
+}
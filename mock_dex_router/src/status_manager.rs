@@ -24,20 +24,13 @@ impl StatusManager {
         redis_handler: RedisHandler,
         mut status_rx: mpsc::Receiver<StatusUpdate>,
     ) {
-        let mut writer_conn = match redis_handler.get_connection().await {
-            Ok(conn) => conn,
-            Err(e) => {
-                println!("redis writer conn failed: {}", e);
-                return;
-            }
-        };
-        
-        println!("redis writer started");
+        let mut writer = redis_handler.writer("status_updates", "status_data");
+        tracing::info!("status writer started");
         
+        // Updates queue up in the channel while Redis is down and are
+        // written in order once it is back.
         while let Some(status_update) = status_rx.recv().await {
-            if let Err(e) = RedisHandler::write_status_to_redis(&mut writer_conn, &status_update).await {
-                println!("redis write failed: {}", e);
-            }
+            writer.add(&status_update.to_redis_data().to_string()).await;
         }
     }
 }
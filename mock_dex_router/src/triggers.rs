@@ -0,0 +1,210 @@
+use redis::aio::ConnectionManager;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex, Semaphore};
+use crate::models::{Order, OrderStatus};
+use crate::price_feed::{rate, PriceFeed};
+use crate::processor::{OrderProcessor, Services};
+use crate::redis_handler::RedisHandler;
+use crate::types::StatusUpdate;
+
+const CANCEL_REPLY_TTL_MS: i64 = 30_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerKind {
+    StopLoss,
+    TakeProfit,
+}
+
+/// `price` is a rate in `token_out` per `token_in`, the same way round as
+/// the order trades.
+#[derive(Debug, Clone, Copy)]
+pub struct Trigger {
+    pub kind: TriggerKind,
+    pub price: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct CancelRequest {
+    cancel_id: String,
+    order_id: String,
+    user_id: String,
+}
+
+struct Armed {
+    order: Order,
+    trigger: Trigger,
+    oco_order_id: Option<String>,
+}
+
+/// Stop-loss and take-profit orders waiting for the price feed to cross
+/// their trigger. A fired order continues as a market order; its OCO
+/// partner, if any, is cancelled in the same step.
+pub struct TriggerBook {
+    feed: Arc<PriceFeed>,
+    armed: Mutex<HashMap<String, Armed>>,
+}
+
+impl Trigger {
+    /// A stop-loss fires once the order's rate falls to the trigger, a
+    /// take-profit once it rises to it.
+    fn crossed(&self, rate: f64) -> bool {
+        match self.kind {
+            TriggerKind::StopLoss => rate <= self.price,
+            TriggerKind::TakeProfit => rate >= self.price,
+        }
+    }
+}
+
+impl TriggerBook {
+    pub fn new(feed: Arc<PriceFeed>) -> Self {
+        Self { feed, armed: Mutex::new(HashMap::new()) }
+    }
+
+    /// Arms the order, linking it to `oco_order_id` when given. An order
+    /// whose partner has already fired or been cancelled is cancelled
+    /// straight away.
+    pub async fn arm(
+        &self,
+        mut order: Order,
+        trigger: Trigger,
+        oco_order_id: Option<String>,
+        status_tx: &mpsc::Sender<StatusUpdate>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut armed = self.armed.lock().await;
+
+        if let Some(partner_id) = &oco_order_id {
+            match armed.get_mut(partner_id) {
+                Some(partner) if partner.order.user_id == order.user_id && partner.oco_order_id.is_none() => {
+                    partner.oco_order_id = Some(order.order_id.clone());
+                }
+                _ => {
+                    tracing::info!(order_id = %order.order_id, partner_id = %partner_id, "oco partner already closed");
+                    order.update_status(OrderStatus::Cancelled { reason: "oco_partner_closed".to_string() });
+                    status_tx.send(StatusUpdate::from_order(&order)).await?;
+                    return Ok(());
+                }
+            }
+        }
+
+        tracing::info!(order_id = %order.order_id, kind = ?trigger.kind, price = trigger.price, "order armed");
+        order.update_status(OrderStatus::Armed);
+        status_tx.send(StatusUpdate::from_order(&order)).await?;
+        armed.insert(order.order_id.clone(), Armed { order, trigger, oco_order_id });
+        Ok(())
+    }
+
+    /// Checks every armed order against each price feed update.
+    pub async fn run(
+        &self,
+        services: Arc<Services>,
+        semaphore: Arc<Semaphore>,
+        status_tx: Arc<mpsc::Sender<StatusUpdate>>,
+    ) {
+        let mut prices = self.feed.subscribe();
+
+        while prices.changed().await.is_ok() {
+            let prices = prices.borrow_and_update().clone();
+            let mut armed = self.armed.lock().await;
+
+            let fired: Vec<(String, f64)> = armed
+                .iter()
+                .filter_map(|(order_id, armed)| {
+                    let rate = rate(&prices, &armed.order.token_in, &armed.order.token_out)?;
+                    armed.trigger.crossed(rate).then(|| (order_id.clone(), rate))
+                })
+                .collect();
+
+            for (order_id, reference_price) in fired {
+                // Gone if its OCO partner fired earlier in this tick.
+                let Some(Armed { mut order, oco_order_id, .. }) = armed.remove(&order_id) else {
+                    continue;
+                };
+                tracing::info!(order_id = %order_id, reference_price, "order triggered");
+                order.update_status(OrderStatus::Triggered { reference_price });
+                if let Err(e) = status_tx.send(StatusUpdate::from_order(&order)).await {
+                    tracing::warn!(order_id = %order_id, error = %e, "failed to send trigger status");
+                }
+
+                if let Some(partner_id) = oco_order_id
+                    && let Some(Armed { order: mut partner, .. }) = armed.remove(&partner_id)
+                {
+                    tracing::info!(order_id = %partner_id, "oco partner cancelled");
+                    partner.update_status(OrderStatus::Cancelled { reason: "oco".to_string() });
+                    if let Err(e) = status_tx.send(StatusUpdate::from_order(&partner)).await {
+                        tracing::warn!(order_id = %partner_id, error = %e, "failed to send trigger status");
+                    }
+                }
+
+                OrderProcessor::spawn_order_task(services.clone(), semaphore.clone(), status_tx.clone(), order).await;
+            }
+        }
+    }
+
+    /// Cancels the user's armed order, reporting `cancelled` with reason
+    /// `user`. Its OCO partner stays armed on its own. Returns `false` when
+    /// the order is not armed, having fired, been cancelled or never been a
+    /// trigger order.
+    pub async fn cancel(&self, order_id: &str, user_id: &str, status_tx: &mpsc::Sender<StatusUpdate>) -> bool {
+        let mut armed = self.armed.lock().await;
+        if armed.get(order_id).is_none_or(|armed| armed.order.user_id != user_id) {
+            return false;
+        }
+        let Some(Armed { mut order, oco_order_id, .. }) = armed.remove(order_id) else {
+            return false;
+        };
+
+        if let Some(partner) = oco_order_id.and_then(|partner_id| armed.get_mut(&partner_id)) {
+            partner.oco_order_id = None;
+        }
+
+        tracing::info!(order_id = %order_id, "armed order cancelled");
+        order.update_status(OrderStatus::Cancelled { reason: "user".to_string() });
+        if let Err(e) = status_tx.send(StatusUpdate::from_order(&order)).await {
+            tracing::warn!(order_id = %order_id, error = %e, "failed to send trigger status");
+        }
+        true
+    }
+
+    /// Reads `order_cancels` from the backend and replies on
+    /// `cancel_reply:{cancel_id}`.
+    pub async fn listen_cancels(
+        self: Arc<Self>,
+        redis_handler: RedisHandler,
+        reply_conn: ConnectionManager,
+        status_tx: Arc<mpsc::Sender<StatusUpdate>>,
+    ) {
+        let mut reader = redis_handler.reader("cancels", "order_cancels", "$");
+        tracing::info!("cancel listener started");
+
+        loop {
+            for message in reader.next().await {
+                let Some(request) = message
+                    .get::<String>("cancel_data")
+                    .and_then(|data| serde_json::from_str::<CancelRequest>(&data).ok())
+                else {
+                    tracing::warn!(message_id = %message.id, "bad cancel request");
+                    continue;
+                };
+
+                let cancelled = self.cancel(&request.order_id, &request.user_id, &status_tx).await;
+                let answer = serde_json::json!({
+                    "order_id": request.order_id,
+                    "cancelled": cancelled,
+                    "reason": (!cancelled).then_some("not_cancellable"),
+                });
+                let reply_key = format!("cancel_reply:{}", request.cancel_id);
+                let result: Result<(), _> = redis::pipe()
+                    .atomic()
+                    .rpush(&reply_key, answer.to_string()).ignore()
+                    .pexpire(&reply_key, CANCEL_REPLY_TTL_MS).ignore()
+                    .query_async(&mut reply_conn.clone())
+                    .await;
+                if let Err(e) = result {
+                    tracing::warn!(error = %e, "cancel reply failed");
+                }
+            }
+        }
+    }
+}
@@ -1,22 +1,115 @@
+use crate::fees::FeePaid;
+use crate::ledger::Commitment;
+use crate::mev::Sandwich;
+use crate::models::{Order, OrderStatus};
+use crate::parent_orders::Progress;
+use common::telemetry::TraceContext;
 use serde_json::json;
 
 #[derive(Debug, Clone)]
 pub struct StatusUpdate {
     pub order_id: String,
+    pub user_id: String,
     pub status: String,
     pub tx_hash: Option<String>,
     pub reason: Option<String>,
     pub execution_price: Option<f64>,
+    pub reference_price: Option<f64>,
+    pub slot: Option<u64>,
+    pub confirmation: Option<Commitment>,
+    pub fee: Option<FeePaid>,
+    pub sandwich: Option<Sandwich>,
+    pub parent_order_id: Option<String>,
+    pub progress: Option<Progress>,
+    pub trace: TraceContext,
 }
 
 impl StatusUpdate {
+    pub fn from_order(order: &Order) -> Self {
+        let mut update = Self {
+            order_id: order.order_id.clone(),
+            user_id: order.user_id.clone(),
+            status: order.status.as_str().to_string(),
+            tx_hash: None,
+            reason: None,
+            execution_price: None,
+            reference_price: None,
+            slot: None,
+            confirmation: None,
+            fee: None,
+            sandwich: None,
+            parent_order_id: order.parent_order_id.clone(),
+            progress: None,
+            trace: order.trace.clone(),
+        };
+
+        match &order.status {
+            OrderStatus::Submitted { tx_hash, slot, confirmation, fee } => {
+                update.tx_hash = Some(tx_hash.clone());
+                update.slot = Some(*slot);
+                update.confirmation = *confirmation;
+                update.fee = Some(*fee);
+            }
+            OrderStatus::Confirmed { tx_hash, execution_price, slot, confirmation, fee, sandwich } => {
+                update.tx_hash = Some(tx_hash.clone());
+                update.execution_price = Some(*execution_price);
+                update.slot = Some(*slot);
+                update.confirmation = Some(*confirmation);
+                update.fee = Some(*fee);
+                update.sandwich = *sandwich;
+            }
+            OrderStatus::Triggered { reference_price } => update.reference_price = Some(*reference_price),
+            OrderStatus::Failed { reason } | OrderStatus::Rejected { reason } | OrderStatus::Cancelled { reason } => {
+                update.reason = Some(reason.clone())
+            }
+            _ => {}
+        }
+
+        update
+    }
+
+    /// A parent order's aggregated update. Final updates carry the average
+    /// price over all filled children.
+    pub fn from_parent(order: &Order, status: &str, reason: Option<String>, progress: &Progress) -> Self {
+        let done = matches!(status, "confirmed" | "partially_filled");
+        Self {
+            order_id: order.order_id.clone(),
+            user_id: order.user_id.clone(),
+            status: status.to_string(),
+            tx_hash: None,
+            reason,
+            execution_price: progress.average_price.filter(|_| done),
+            reference_price: None,
+            slot: None,
+            confirmation: None,
+            fee: None,
+            sandwich: None,
+            parent_order_id: None,
+            progress: Some(progress.clone()),
+            trace: order.trace.clone(),
+        }
+    }
+
     pub fn to_redis_data(&self) -> serde_json::Value {
         json!({
             "order_id": self.order_id,
+            "user_id": self.user_id,
             "status": self.status,
             "tx_hash": self.tx_hash,
             "reason": self.reason,
-            "execution_price": self.execution_price
+            "execution_price": self.execution_price,
+            "reference_price": self.reference_price,
+            "slot": self.slot,
+            "confirmation": self.confirmation.map(|c| c.as_str()),
+            "priority_fee": self.fee.map(|f| f.priority_fee),
+            "compute_units": self.fee.map(|f| f.compute_units),
+            "fee_lamports": self.fee.map(|f| f.fee_lamports),
+            "tip_lamports": self.fee.map(|f| f.tip_lamports),
+            "sandwiched": self.sandwich.is_some(),
+            "mev_loss": self.sandwich.map(|s| s.victim_loss),
+            "parent_order_id": self.parent_order_id,
+            "progress": self.progress,
+            "trace_context": self.trace
         })
     }
 }
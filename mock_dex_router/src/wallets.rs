@@ -0,0 +1,126 @@
+use redis::aio::ConnectionManager;
+use redis::Script;
+use crate::scenario::ScenarioHandle;
+
+// Returns `{ok, available}`. ARGV[3..] are the starting balances used when
+// the wallet does not exist yet.
+const RESERVE_SCRIPT: &str = r#"
+if redis.call('EXISTS', KEYS[1]) == 0 then
+    for i = 3, #ARGV, 2 do
+        redis.call('HSET', KEYS[1], ARGV[i], ARGV[i + 1])
+    end
+end
+local available = tonumber(redis.call('HGET', KEYS[1], ARGV[1]) or '0')
+if available < tonumber(ARGV[2]) then
+    return {0, tostring(available)}
+end
+redis.call('HINCRBYFLOAT', KEYS[1], ARGV[1], -tonumber(ARGV[2]))
+redis.call('HINCRBYFLOAT', KEYS[2], ARGV[1], ARGV[2])
+return {1, tostring(available)}
+"#;
+
+const SETTLE_SCRIPT: &str = r#"
+redis.call('HINCRBYFLOAT', KEYS[2], ARGV[1], -tonumber(ARGV[2]))
+redis.call('HINCRBYFLOAT', KEYS[1], ARGV[3], ARGV[4])
+return 1
+"#;
+
+const RELEASE_SCRIPT: &str = r#"
+redis.call('HINCRBYFLOAT', KEYS[2], ARGV[1], -tonumber(ARGV[2]))
+redis.call('HINCRBYFLOAT', KEYS[1], ARGV[1], ARGV[2])
+return 1
+"#;
+
+/// Simulated per-user token balances in Redis. `wallet:{user}` holds
+/// available balances, `wallet:{user}:held` funds reserved by open orders.
+#[derive(Clone)]
+pub struct WalletStore {
+    conn: ConnectionManager,
+    scenario: ScenarioHandle,
+}
+
+pub enum Reservation {
+    Held,
+    Insufficient { available: f64 },
+}
+
+impl WalletStore {
+    pub fn new(conn: ConnectionManager, scenario: ScenarioHandle) -> Self {
+        Self { conn, scenario }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.scenario.current().wallets.enabled
+    }
+
+    /// Holds `amount` of `token` for an order, seeding the wallet first if
+    /// the user has never traded.
+    pub async fn reserve(&self, user_id: &str, token: &str, amount: f64) -> Result<Reservation, redis::RedisError> {
+        let script = Script::new(RESERVE_SCRIPT);
+        let mut invocation = script.prepare_invoke();
+        invocation
+            .key(Self::wallet_key(user_id))
+            .key(Self::held_key(user_id))
+            .arg(token)
+            .arg(amount);
+        for (token, balance) in &self.scenario.current().wallets.initial_balances {
+            invocation.arg(token).arg(balance);
+        }
+
+        let (ok, available): (i32, String) = invocation.invoke_async(&mut self.conn.clone()).await?;
+        if ok == 1 {
+            Ok(Reservation::Held)
+        } else {
+            Ok(Reservation::Insufficient { available: available.parse().unwrap_or(0.0) })
+        }
+    }
+
+    /// Debits the held input and credits the output in one step.
+    pub async fn settle(&self, user_id: &str, token_in: &str, amount_in: f64, token_out: &str, amount_out: f64) -> Result<(), redis::RedisError> {
+        let _: i32 = Script::new(SETTLE_SCRIPT)
+            .key(Self::wallet_key(user_id))
+            .key(Self::held_key(user_id))
+            .arg(token_in)
+            .arg(amount_in)
+            .arg(token_out)
+            .arg(amount_out)
+            .invoke_async(&mut self.conn.clone())
+            .await?;
+        Ok(())
+    }
+
+    pub async fn release(&self, user_id: &str, token: &str, amount: f64) -> Result<(), redis::RedisError> {
+        let _: i32 = Script::new(RELEASE_SCRIPT)
+            .key(Self::wallet_key(user_id))
+            .key(Self::held_key(user_id))
+            .arg(token)
+            .arg(amount)
+            .invoke_async(&mut self.conn.clone())
+            .await?;
+        Ok(())
+    }
+
+    /// Available plus held balance of `token`. A wallet that does not exist
+    /// yet counts at its starting balance.
+    pub async fn position(&self, user_id: &str, token: &str) -> Result<f64, redis::RedisError> {
+        let (exists, available, held): (bool, Option<f64>, Option<f64>) = redis::pipe()
+            .exists(Self::wallet_key(user_id))
+            .hget(Self::wallet_key(user_id), token)
+            .hget(Self::held_key(user_id), token)
+            .query_async(&mut self.conn.clone())
+            .await?;
+
+        if !exists {
+            return Ok(self.scenario.current().wallets.initial_balances.get(token).copied().unwrap_or(0.0));
+        }
+        Ok(available.unwrap_or(0.0) + held.unwrap_or(0.0))
+    }
+
+    fn wallet_key(user_id: &str) -> String {
+        format!("wallet:{}", user_id)
+    }
+
+    fn held_key(user_id: &str) -> String {
+        format!("wallet:{}:held", user_id)
+    }
+}
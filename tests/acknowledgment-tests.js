@@ -1,8 +1,8 @@
 import WebSocket from 'ws';
 import axios from 'axios';
+import { useSuiteApiKey, wsUrl } from './auth.js';
 
 const API_URL = 'http://localhost:3000';
-const WS_URL = 'ws://localhost:3000/api/orders/execute';
 
 class AcknowledgmentTester {
   constructor() {
@@ -13,7 +13,7 @@ class AcknowledgmentTester {
     return new Promise((resolve, reject) => {
       console.log(`Upgrading to WebSocket for order: ${orderId}`);
       
-      const ws = new WebSocket(WS_URL);
+      const ws = new WebSocket(wsUrl());
       
       ws.on('open', () => {
         console.log(`WebSocket connected for order: ${orderId}`);
@@ -86,12 +86,12 @@ class AcknowledgmentTester {
                 this.stats.acked++;
                 ws.close();
                 resolve({ orderId, status: 'confirmed', totalTime });
-              } else if (status === 'failed') {
-                console.log('  -> Failed');
+              } else if (status === 'failed' || status === 'rejected') {
+                console.log(status === 'failed' ? '  -> Failed' : '  -> Rejected');
                 console.log(`  -> Error: ${message.reason}`);
                 this.stats.failed++;
                 ws.close();
-                resolve({ orderId, status: 'failed', reason: message.reason });
+                resolve({ orderId, status, reason: message.reason });
               }
               this.stats.times.push(Date.now() - startTime);
             }
@@ -143,7 +143,7 @@ class AcknowledgmentTester {
 
   async testSingleOrder(order, orderNumber) {
     return new Promise((resolve, reject) => {
-      const ws = new WebSocket(WS_URL);
+      const ws = new WebSocket(wsUrl());
       let orderId = null;
       const startTime = Date.now();
 
@@ -175,7 +175,7 @@ class AcknowledgmentTester {
               this.stats.acked++;
               ws.close();
               resolve({ orderNumber, orderId, status: message.status, responseTime });
-            } else if (message.status === 'failed') {
+            } else if (message.status === 'failed' || message.status === 'rejected') {
               this.stats.failed++;
               ws.close();
               resolve({ orderNumber, orderId, status: message.status, responseTime });
@@ -202,7 +202,7 @@ class AcknowledgmentTester {
     this.stats.total += 1;
     
     return new Promise((resolve) => {
-      const ws = new WebSocket(WS_URL);
+      const ws = new WebSocket(wsUrl());
 
       ws.on('open', async () => {
         try {
@@ -242,7 +242,7 @@ class AcknowledgmentTester {
             this.stats.acked++;
             ws.close();
             resolve({ status: 'completed', message: 'Order completed successfully' });
-          } else if (message.status === 'failed') {
+          } else if (message.status === 'failed' || message.status === 'rejected') {
             console.log('Order failed before timeout');
             this.stats.failed++;
             ws.close();
@@ -283,6 +283,7 @@ async function runAcknowledgmentTests() {
   
   try {
     console.log('Starting tests...');
+    await useSuiteApiKey();
     
     await tester.testOrderWithFullAcknowledgment();
     await tester.testConcurrentOrdersWithAcknowledgment(3);
@@ -0,0 +1,37 @@
+import axios from 'axios';
+
+const API_URL = 'http://localhost:3000';
+const WS_URL = 'ws://localhost:3000';
+const ADMIN_TOKEN = process.env.ADMIN_TOKEN || 'dev-admin-token';
+
+let suiteApiKey = process.env.API_KEY || null;
+
+async function adminRequest(method, path, data = undefined, params = undefined) {
+  const response = await axios.request({
+    method, url: `${API_URL}${path}`, data, params,
+    headers: { 'Content-Type': 'application/json', 'x-admin-token': ADMIN_TOKEN }, timeout: 5000
+  });
+  return response.data;
+}
+
+async function createUser(name) {
+  return adminRequest('post', '/api/admin/users', { name });
+}
+
+// Uses API_KEY if set, otherwise creates a test user once, and sends the
+// key with every axios request.
+async function useSuiteApiKey() {
+  if (!suiteApiKey) {
+    suiteApiKey = (await createUser('test-suite')).api_key;
+    console.log(`Created test user with key ${suiteApiKey.slice(0, 11)}...`);
+  }
+  axios.defaults.headers.common['x-api-key'] = suiteApiKey;
+  return suiteApiKey;
+}
+
+function wsUrl(apiKey = suiteApiKey, path = '/api/orders/execute', params = {}) {
+  const query = new URLSearchParams({ ...params, api_key: apiKey });
+  return `${WS_URL}${path}?${query}`;
+}
+
+export { adminRequest, createUser, useSuiteApiKey, wsUrl };
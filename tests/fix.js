@@ -0,0 +1,72 @@
+import net from 'net';
+
+const FIX_HOST = process.env.FIX_HOST || 'localhost';
+const FIX_PORT = Number(process.env.FIX_PORT || 9878);
+const FIX_COMP_ID = process.env.FIX_COMP_ID || 'DEXGW';
+const SOH = '\x01';
+
+// A minimal FIX 4.4 initiator: numbers and frames outgoing messages and
+// parses incoming ones into { tag: value } objects.
+class FixSession {
+  constructor(senderCompId) {
+    this.senderCompId = senderCompId;
+    this.seq = 1;
+    this.buffer = '';
+    this.messages = [];
+    this.waiting = [];
+  }
+
+  connect() {
+    return new Promise((resolve, reject) => {
+      this.socket = net.createConnection({ host: FIX_HOST, port: FIX_PORT }, resolve);
+      this.socket.on('error', reject);
+      this.socket.on('data', (data) => this.receive(data.toString('latin1')));
+    });
+  }
+
+  send(msgType, fields) {
+    const header = [[35, msgType], [49, this.senderCompId], [56, FIX_COMP_ID], [34, this.seq++], [52, timestamp()]];
+    const body = [...header, ...fields].map(([tag, value]) => `${tag}=${value}${SOH}`).join('');
+    const message = `8=FIX.4.4${SOH}9=${Buffer.byteLength(body, 'latin1')}${SOH}${body}`;
+    const checksum = [...Buffer.from(message, 'latin1')].reduce((sum, byte) => sum + byte, 0) % 256;
+    this.socket.write(`${message}10=${String(checksum).padStart(3, '0')}${SOH}`, 'latin1');
+  }
+
+  // Resolves with the first message, already received or not, that matches.
+  waitFor(predicate, description, timeoutMs = 30000) {
+    const index = this.messages.findIndex(predicate);
+    if (index >= 0) return Promise.resolve(this.messages.splice(index, 1)[0]);
+    return new Promise((resolve, reject) => {
+      const timer = setTimeout(() => reject(new Error(`No FIX ${description} within ${timeoutMs}ms`)), timeoutMs);
+      this.waiting.push({ predicate, resolve: (message) => { clearTimeout(timer); resolve(message); } });
+    });
+  }
+
+  receive(data) {
+    this.buffer += data;
+    let end;
+    while ((end = this.buffer.indexOf(`${SOH}10=`)) >= 0 && this.buffer.length >= end + 8) {
+      const raw = this.buffer.slice(0, end + 8);
+      this.buffer = this.buffer.slice(end + 8);
+      const message = Object.fromEntries(raw.split(SOH).filter(Boolean).map(field => {
+        const at = field.indexOf('=');
+        return [field.slice(0, at), field.slice(at + 1)];
+      }));
+
+      if (message['35'] === '1') this.send('0', [[112, message['112']]]);
+      const waiter = this.waiting.findIndex(({ predicate }) => predicate(message));
+      if (waiter >= 0) this.waiting.splice(waiter, 1)[0].resolve(message);
+      else this.messages.push(message);
+    }
+  }
+
+  close() {
+    this.socket.end();
+  }
+}
+
+function timestamp() {
+  return new Date().toISOString().replace(/-/g, '').replace('T', '-').replace('Z', '');
+}
+
+export { FixSession };
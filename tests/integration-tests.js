@@ -1,19 +1,30 @@
 import WebSocket from 'ws';
 import axios from 'axios';
+import crypto from 'crypto';
+import http from 'http';
+import { adminRequest, createUser, useSuiteApiKey, wsUrl } from './auth.js';
+import { FixSession } from './fix.js';
 
 const API_URL = 'http://localhost:3000';
-const WS_URL = 'ws://localhost:3000/api/orders/execute';
+const WEBHOOK_RECEIVER_PORT = Number(process.env.WEBHOOK_RECEIVER_PORT || 4010);
+const WEBHOOK_RECEIVER_HOST = process.env.WEBHOOK_RECEIVER_HOST || 'host.docker.internal';
+const HEALTH_URLS = {
+  backend: API_URL,
+  router: process.env.ROUTER_HEALTH_URL || 'http://localhost:8081',
+  db_service: process.env.DB_SERVICE_HEALTH_URL || 'http://localhost:8082',
+  fix_gateway: process.env.FIX_GATEWAY_HEALTH_URL || 'http://localhost:8083',
+};
 
 class IntegrationTester {
   constructor() {
     this.results = { total: 0, passed: 0, failed: 0, orders: { total: 0, success: 0, failed: 0 } };
   }
 
-  async createWebSocketUpgrade(orderId) {
+  async createWebSocketUpgrade(orderId, apiKey = undefined) {
     return new Promise((resolve, reject) => {
       console.log(`  → Initiating HTTP GET upgrade request for order: ${orderId}`);
       
-      const ws = new WebSocket(WS_URL);
+      const ws = new WebSocket(wsUrl(apiKey));
       
       ws.on('open', () => {
         console.log(`  → WebSocket connection established for order: ${orderId}`);
@@ -37,9 +48,26 @@ class IntegrationTester {
     console.log('Starting integration tests...');
     
     try {
+      await useSuiteApiKey();
+      await this.testHealthChecks();
+      await this.testAuthentication();
       await this.testCompleteOrderLifecycle();
       await this.testConcurrentOrderFlows();
       await this.testErrorHandling();
+      await this.testWalletBalances();
+      await this.testIdempotentRetry();
+      await this.testBatchOrders();
+      await this.testQuotedExecution();
+      await this.testRiskLimits();
+      await this.testTradingHalt();
+      await this.testParentOrders();
+      await this.testTriggerOrders();
+      await this.testMarketData();
+      await this.testStatusEvents();
+      await this.testTraceContext();
+      await this.testWebhooks();
+      await this.testFixGateway();
+      await this.testMetrics();
       
       console.log('\nAll integration tests passed!');
       
@@ -50,6 +78,105 @@ class IntegrationTester {
     }
   }
 
+  async testHealthChecks() {
+    console.log('\n=== Health Check Test ===');
+    this.results.total++;
+
+    try {
+      for (const [service, url] of Object.entries(HEALTH_URLS)) {
+        const live = await fetch(`${url}/healthz`);
+        const liveBody = await live.json();
+        if (live.status !== 200 || liveBody.status !== 'ok') {
+          throw new Error(`${service} is not live: ${live.status} ${JSON.stringify(liveBody)}`);
+        }
+
+        const ready = await fetch(`${url}/readyz`);
+        const readyBody = await ready.json();
+        if (ready.status !== 200 || readyBody.status !== 'ready' || !readyBody.redis?.ok) {
+          throw new Error(`${service} is not ready: ${ready.status} ${JSON.stringify(readyBody)}`);
+        }
+        if (!Array.isArray(readyBody.streams) || readyBody.streams.some(stream => !stream.connected)) {
+          throw new Error(`${service} has a disconnected stream reader: ${JSON.stringify(readyBody.streams)}`);
+        }
+        console.log(`  ${service} is live and ready with ${readyBody.streams.length} stream reader(s)`);
+      }
+
+      console.log('Health check test passed');
+      this.results.passed++;
+
+    } catch (error) {
+      console.error('Health check test failed:', error.message);
+      this.results.failed++;
+      throw error;
+    }
+  }
+
+  async testAuthentication() {
+    console.log('\n=== Authentication Test ===');
+    this.results.total++;
+
+    try {
+      await this.testMissingApiKey();
+      await this.testForeignOrderSubscription();
+
+      console.log('Authentication test passed');
+      this.results.passed++;
+
+    } catch (error) {
+      console.error('Authentication test failed:', error.message);
+      this.results.failed++;
+      throw error;
+    }
+  }
+
+  async testMissingApiKey() {
+    console.log('  Testing requests without a valid API key...');
+
+    const order = { token_in: 'SOL', token_out: 'USDC', amount: 1, order_type: 'market', max_slippage: 0.03 };
+    const missing = await fetch(`${API_URL}/api/orders/execute`, {
+      method: 'POST', headers: { 'Content-Type': 'application/json' }, body: JSON.stringify(order)
+    });
+    if (missing.status !== 401) {
+      throw new Error(`Expected 401 without API key, got ${missing.status}`);
+    }
+
+    try {
+      await axios.post(`${API_URL}/api/orders/execute`, order, {
+        headers: { 'Content-Type': 'application/json', 'x-api-key': 'ak_invalid' }, timeout: 5000
+      });
+      throw new Error('Accepted an invalid API key');
+    } catch (error) {
+      if (!error.response || error.response.status !== 401) throw error;
+    }
+
+    console.log('    Correctly rejected missing and invalid keys');
+  }
+
+  async testForeignOrderSubscription() {
+    console.log('  Testing subscription to another user\'s order...');
+
+    const response = await axios.post(`${API_URL}/api/orders/execute`, {
+      token_in: 'SOL', token_out: 'USDC', amount: 1, order_type: 'market', max_slippage: 0.03
+    }, { headers: { 'Content-Type': 'application/json' }, timeout: 5000 });
+    const orderId = response.data.order_id;
+
+    const intruder = await createUser('intruder');
+    const ws = await this.createWebSocketUpgrade(orderId, intruder.api_key);
+
+    const message = await new Promise((resolve, reject) => {
+      ws.on('message', (data) => resolve(JSON.parse(data.toString())));
+      ws.on('error', reject);
+      setTimeout(() => reject(new Error('No reply to foreign subscription')), 5000);
+    });
+    ws.close();
+
+    if (message.error !== 'forbidden' || message.status) {
+      throw new Error(`Foreign subscription was not refused: ${JSON.stringify(message)}`);
+    }
+
+    console.log('    Correctly refused foreign subscription');
+  }
+
   async testCompleteOrderLifecycle() {
     console.log('\n=== Order Lifecycle Test ===');
     this.results.total++;
@@ -132,6 +259,9 @@ class IntegrationTester {
       await this.testInvalidSlippage();
       await this.testInvalidTokens();
       await this.testZeroAmount();
+      await this.testAllViolationsReported();
+      await this.testMalformedBody();
+      await this.testNotionalThrottle();
       
       console.log('Error handling test passed');
       this.results.passed++;
@@ -143,74 +273,778 @@ class IntegrationTester {
     }
   }
 
-  async testInvalidSlippage() {
-    console.log('  Testing invalid slippage...');
-    
-    const invalidOrders = [
-      { token_in: 'SOL', token_out: 'USDC', amount: 10, order_type: 'market', max_slippage: 0.6 },
-      { token_in: 'SOL', token_out: 'USDC', amount: 10, order_type: 'market', max_slippage: 0.005 }
-    ];
+  async testIdempotentRetry() {
+    console.log('\n=== Idempotent Retry Test ===');
+    this.results.total++;
+
+    try {
+      const key = `it-retry-${Date.now()}`;
+      const order = { token_in: 'SOL', token_out: 'USDC', amount: 1.5, order_type: 'market', max_slippage: 0.03 };
+      const submit = (body) => axios.post(`${API_URL}/api/orders/execute`, body, {
+        headers: { 'Content-Type': 'application/json', 'Idempotency-Key': key }, timeout: 5000
+      });
+
+      const first = await submit(order);
+      const retry = await submit(order);
+      if (retry.data.order_id !== first.data.order_id || retry.headers['idempotent-replayed'] !== 'true') {
+        throw new Error(`Retry created a new order: ${first.data.order_id} vs ${retry.data.order_id}`);
+      }
 
-    for (const order of invalidOrders) {
       try {
-        const response = await axios.post(`${API_URL}/api/orders/execute`, order, {
+        await submit({ ...order, amount: 2.5 });
+        throw new Error('Key reused with different parameters was accepted');
+      } catch (error) {
+        if (!error.response || error.response.status !== 422) throw error;
+      }
+
+      console.log(`Idempotent retry test passed (order ${first.data.order_id})`);
+      this.results.passed++;
+
+    } catch (error) {
+      console.error('Idempotent retry test failed:', error.message);
+      this.results.failed++;
+      throw error;
+    }
+  }
+
+  async testBatchOrders() {
+    console.log('\n=== Batch Orders Test ===');
+    this.results.total++;
+
+    try {
+      const key = `it-batch-${Date.now()}`;
+      const order = { token_in: 'SOL', token_out: 'USDC', amount: 0.5, order_type: 'market', max_slippage: 0.03 };
+      const submit = (body) => axios.post(`${API_URL}/api/orders/batch`, body, {
+        headers: { 'Content-Type': 'application/json' }, timeout: 5000, validateStatus: () => true
+      });
+
+      const atomic = await submit({ all_or_nothing: true, orders: [order, { ...order, token_out: 'SOL' }] });
+      const held = atomic.data.orders || [];
+      if (atomic.status !== 422 || held[0]?.status !== 'not_submitted' || held[1]?.error !== 'validation_failed') {
+        throw new Error(`All-or-nothing batch was not held back: ${atomic.status} ${JSON.stringify(atomic.data)}`);
+      }
+
+      const batch = [{ ...order, client_order_id: key }, { token_in: 'SOL' }, { ...order, client_order_id: key }];
+      const first = await submit({ orders: batch });
+      const [accepted, malformed, duplicate] = first.data.orders || [];
+      if (first.status !== 200 || accepted?.status !== 'accepted' || malformed?.status !== 'rejected'
+          || duplicate?.violations?.[0]?.code !== 'duplicate_client_order_id') {
+        throw new Error(`Unexpected batch results: ${first.status} ${JSON.stringify(first.data)}`);
+      }
+
+      const retry = await submit({ orders: [batch[0]] });
+      if (retry.data.orders?.[0]?.status !== 'replayed' || retry.data.orders[0].order_id !== accepted.order_id) {
+        throw new Error(`Batch retry created a new order: ${JSON.stringify(retry.data)}`);
+      }
+
+      console.log(`Batch orders test passed (order ${accepted.order_id})`);
+      this.results.passed++;
+
+    } catch (error) {
+      console.error('Batch orders test failed:', error.message);
+      this.results.failed++;
+      throw error;
+    }
+  }
+
+  async testQuotedExecution() {
+    console.log('\n=== Quoted Execution Test ===');
+    this.results.total++;
+
+    try {
+      const trade = { token_in: 'SOL', token_out: 'USDC', amount: 2.0 };
+      const { data: quote } = await axios.post(`${API_URL}/api/quotes`, trade, {
+        headers: { 'Content-Type': 'application/json' }, timeout: 10000
+      });
+      if (!quote.quote_id || !quote.route || !(quote.route.amount_out > 0) || !Array.isArray(quote.venues)) {
+        throw new Error(`Malformed quote: ${JSON.stringify(quote)}`);
+      }
+      console.log(`  Quote ${quote.quote_id}: ${quote.route.amount_out} USDC via ${quote.route.venue}`);
+
+      const order = { ...trade, order_type: 'market', max_slippage: 0.03, quote_id: quote.quote_id };
+      const result = await this.executeOrder(order);
+      if (result.finalStatus === 'rejected') {
+        throw new Error(`Quoted order was rejected: ${result.reason}`);
+      }
+
+      const reused = await this.executeOrder(order);
+      if (reused.finalStatus !== 'rejected' || reused.reason !== 'quote_expired') {
+        throw new Error(`Expected reused quote to be rejected, got ${reused.finalStatus}/${reused.reason}`);
+      }
+
+      console.log(`Quoted execution test passed (order ${result.orderId} ${result.finalStatus})`);
+      this.results.passed++;
+
+    } catch (error) {
+      console.error('Quoted execution test failed:', error.message);
+      this.results.failed++;
+      throw error;
+    }
+  }
+
+  async testRiskLimits() {
+    console.log('\n=== Pre-trade Risk Test ===');
+    this.results.total++;
+
+    try {
+      const user = await createUser('it-risk');
+      const result = await this.executeOrder({
+        token_in: 'SOL', token_out: 'USDC', amount: 1200,
+        order_type: 'market', max_slippage: 0.03
+      }, null, user.api_key);
+
+      if (result.finalStatus !== 'rejected' || result.reason !== 'max_order_notional') {
+        throw new Error(`Expected rejected/max_order_notional, got ${result.finalStatus}/${result.reason}`);
+      }
+      if (result.statusSequence.includes('routing')) {
+        throw new Error('Order over the notional limit was routed');
+      }
+
+      console.log('Pre-trade risk test passed');
+      this.results.passed++;
+
+    } catch (error) {
+      console.error('Pre-trade risk test failed:', error.message);
+      this.results.failed++;
+      throw error;
+    }
+  }
+
+  async testTradingHalt() {
+    console.log('\n=== Trading Halt Test ===');
+    this.results.total++;
+
+    const halt = { scope: 'pair', target: 'SOL/USDC' };
+    const order = { token_in: 'USDC', token_out: 'SOL', amount: 100, order_type: 'market', max_slippage: 0.03 };
+
+    try {
+      await adminRequest('post', '/api/admin/halts', { ...halt, mode: 'reject', reason: 'integration test' });
+      try {
+        await axios.post(`${API_URL}/api/orders/execute`, order, {
           headers: { 'Content-Type': 'application/json' }, timeout: 5000
         });
-        
-        if (response.status === 200) {
-          console.log(`    Accepted invalid slippage: ${order.max_slippage}`);
+        throw new Error('Order accepted while its pair was halted');
+      } catch (error) {
+        if (!error.response || error.response.status !== 503 || error.response.data.error !== 'trading_halted') throw error;
+      }
+      console.log('  Order refused while the pair was halted');
+
+      await adminRequest('delete', '/api/admin/halts', undefined, halt);
+      const { halts } = await adminRequest('get', '/api/admin/halts');
+      if (halts.some(h => h.scope === 'pair' && h.target === 'SOL/USDC')) {
+        throw new Error(`Halt still active after resume: ${JSON.stringify(halts)}`);
+      }
+
+      const result = await this.executeOrder(order);
+      if (result.reason === 'halted') {
+        throw new Error('Order rejected as halted after resume');
+      }
+
+      console.log(`Trading halt test passed (order after resume ${result.finalStatus})`);
+      this.results.passed++;
+
+    } catch (error) {
+      await adminRequest('delete', '/api/admin/halts', undefined, halt).catch(() => {});
+      console.error('Trading halt test failed:', error.message);
+      this.results.failed++;
+      throw error;
+    }
+  }
+
+  async testParentOrders() {
+    console.log('\n=== Parent Orders Test ===');
+    this.results.total++;
+
+    try {
+      const twap = { token_in: 'SOL', token_out: 'USDC', amount: 3.0, order_type: 'twap', duration_secs: 4, slices: 2, max_slippage: 0.03 };
+      const result = await this.executeOrder(twap);
+      const { progress } = result;
+
+      if (!progress || progress.algo !== 'twap' || progress.children_sent !== 2) {
+        throw new Error(`Expected 2 TWAP children, got ${JSON.stringify(progress)}`);
+      }
+      if (!result.statusSequence.includes('working')) {
+        throw new Error(`No working update between slices: ${result.statusSequence.join(' -> ')}`);
+      }
+      if (Math.abs(progress.filled_amount + progress.remaining_amount - twap.amount) > 1e-9) {
+        throw new Error(`Filled and remaining do not add up: ${JSON.stringify(progress)}`);
+      }
+      console.log(`  TWAP ${result.finalStatus}: ${progress.filled_amount} SOL filled at ${progress.average_price}`);
+
+      await this.expectViolations({ ...twap, slices: 1 }, ['slices_out_of_range']);
+      await this.expectViolations(
+        { token_in: 'SOL', token_out: 'USDC', amount: 3.0, order_type: 'iceberg', clip_size: 5.0, duration_secs: 60 },
+        ['clip_size_out_of_range', 'unexpected_field']
+      );
+      console.log('  Invalid TWAP and iceberg parameters correctly rejected');
+
+      console.log(`Parent orders test passed (order ${result.orderId})`);
+      this.results.passed++;
+
+    } catch (error) {
+      console.error('Parent orders test failed:', error.message);
+      this.results.failed++;
+      throw error;
+    }
+  }
+
+  async testTriggerOrders() {
+    console.log('\n=== Trigger Orders Test ===');
+    this.results.total++;
+
+    try {
+      // SOL trades far above 1 USDC, so the take-profit fires on the next
+      // price tick and the stop-loss never would on its own.
+      const leg = { token_in: 'SOL', token_out: 'USDC', amount: 1.0, max_slippage: 0.03, trigger_price: 1.0 };
+      const { data: stop } = await axios.post(`${API_URL}/api/orders/execute`, { ...leg, order_type: 'stop_loss' }, {
+        headers: { 'Content-Type': 'application/json' }, timeout: 5000
+      });
+      const stopWs = await this.createWebSocketUpgrade(stop.order_id);
+      const stopFinal = new Promise((resolve, reject) => {
+        stopWs.on('message', (data) => {
+          const message = JSON.parse(data.toString());
+          if (message.status === 'cancelled' || message.status === 'triggered') {
+            stopWs.close();
+            resolve(message);
+          }
+        });
+        setTimeout(() => reject(new Error('Stop-loss leg never closed')), 30000);
+      });
+
+      const result = await this.executeOrder({ ...leg, order_type: 'take_profit', oco_order_id: stop.order_id });
+      const [armed, triggered] = ['armed', 'triggered'].map(s => result.statusSequence.indexOf(s));
+      if (armed < 0 || triggered < armed || !result.statusSequence.includes('pending')) {
+        throw new Error(`Take-profit did not arm, trigger and execute: ${result.statusSequence.join(' -> ')}`);
+      }
+
+      const stopMessage = await stopFinal;
+      if (stopMessage.status !== 'cancelled' || stopMessage.reason !== 'oco') {
+        throw new Error(`Expected the OCO stop-loss to be cancelled, got ${JSON.stringify(stopMessage)}`);
+      }
+      console.log(`  Take-profit ${result.finalStatus}, stop-loss cancelled`);
+
+      await this.expectViolations({ ...leg, order_type: 'stop_loss', trigger_price: 0 }, ['invalid_trigger_price']);
+
+      console.log('Trigger orders test passed');
+      this.results.passed++;
+
+    } catch (error) {
+      console.error('Trigger orders test failed:', error.message);
+      this.results.failed++;
+      throw error;
+    }
+  }
+
+  async testMarketData() {
+    console.log('\n=== Market Data Test ===');
+    this.results.total++;
+
+    try {
+      const { data } = await axios.get(`${API_URL}/api/market-data`, { timeout: 5000 });
+      const snapshot = data.pairs.find(p => p.pair === 'SOL/USDC');
+      if (!snapshot || !(snapshot.mid_price > 0) || !(snapshot.best_ask >= snapshot.best_bid) || snapshot.venues.length === 0) {
+        throw new Error(`Missing or malformed SOL/USDC snapshot: ${JSON.stringify(data)}`);
+      }
+      console.log(`  SOL/USDC mid ${snapshot.mid_price.toFixed(4)}, spread ${snapshot.spread_pct.toFixed(3)}%`);
+
+      // The first message is the cached snapshot, the second a live tick.
+      const ticks = await new Promise((resolve, reject) => {
+        const ws = new WebSocket(wsUrl(undefined, '/api/market-data/stream', { pairs: 'USDC/SOL' }));
+        const received = [];
+        ws.on('message', (raw) => {
+          received.push(JSON.parse(raw.toString()));
+          if (received.length === 2) {
+            ws.close();
+            resolve(received);
+          }
+        });
+        ws.on('error', reject);
+        setTimeout(() => { ws.close(); reject(new Error('No live market data tick')); }, 10000);
+      });
+      if (ticks.some(tick => tick.pair !== 'SOL/USDC') || !(ticks[1].timestamp_ms >= ticks[0].timestamp_ms)) {
+        throw new Error(`Unexpected market data ticks: ${JSON.stringify(ticks)}`);
+      }
+
+      try {
+        await axios.get(`${API_URL}/api/market-data`, { params: { pairs: 'DOGE/USDC' }, timeout: 5000 });
+        throw new Error('Unknown pair returned a snapshot');
+      } catch (error) {
+        if (!error.response || error.response.status !== 404) throw error;
+      }
+
+      console.log('Market data test passed');
+      this.results.passed++;
+
+    } catch (error) {
+      console.error('Market data test failed:', error.message);
+      this.results.failed++;
+      throw error;
+    }
+  }
+
+  async testStatusEvents() {
+    console.log('\n=== Status Events Test ===');
+    this.results.total++;
+
+    try {
+      const order = { token_in: 'SOL', token_out: 'USDC', amount: 0.5, order_type: 'market', max_slippage: 0.05 };
+      const { data } = await axios.post(`${API_URL}/api/orders/execute`, order, {
+        headers: { 'Content-Type': 'application/json' }, timeout: 5000
+      });
+
+      const events = await this.readStatusEvents(data.order_id);
+      const statuses = events.map(event => event.data.status);
+      console.log(`  SSE flow: ${statuses.join(' -> ')}`);
+      if (!['confirmed', 'failed', 'rejected'].includes(statuses[statuses.length - 1])) {
+        throw new Error(`SSE stream ended without a final status: ${statuses.join(', ')}`);
+      }
+
+      // Resuming after the first event replays the rest from the Redis stream.
+      const resumed = await this.readStatusEvents(data.order_id, events[0].id);
+      if (resumed.map(event => event.id).join() !== events.slice(1).map(event => event.id).join()) {
+        throw new Error(`Resume from ${events[0].id} returned ${resumed.map(event => event.id).join(', ')}`);
+      }
+
+      const foreign = await createUser('sse-foreign');
+      const forbidden = await fetch(`${API_URL}/api/orders/${data.order_id}/events`, {
+        headers: { 'x-api-key': foreign.api_key }
+      });
+      if (forbidden.status !== 403) {
+        throw new Error(`Expected 403 for another user's order events, got ${forbidden.status}`);
+      }
+
+      console.log('Status events test passed');
+      this.results.passed++;
+
+    } catch (error) {
+      console.error('Status events test failed:', error.message);
+      this.results.failed++;
+      throw error;
+    }
+  }
+
+  async testTraceContext() {
+    console.log('\n=== Trace Context Test ===');
+    this.results.total++;
+
+    try {
+      const order = { token_in: 'SOL', token_out: 'USDC', amount: 0.5, order_type: 'market', max_slippage: 0.05 };
+      const { data } = await axios.post(`${API_URL}/api/orders/execute`, order, {
+        headers: { 'Content-Type': 'application/json' }, timeout: 5000
+      });
+
+      // traceparent is 00-{trace id}-{span id}-{flags}; every update of the
+      // order belongs to the trace the backend started at intake.
+      const events = await this.readStatusEvents(data.order_id);
+      const traceIds = events.map(event => event.data.trace_context?.traceparent?.split('-')[1]);
+      if (traceIds.length === 0 || traceIds.some(traceId => !/^[0-9a-f]{32}$/.test(traceId || ''))) {
+        throw new Error(`Status updates without a trace context: ${JSON.stringify(events.map(event => event.data.trace_context))}`);
+      }
+      if (new Set(traceIds).size !== 1) {
+        throw new Error(`Status updates span several traces: ${[...new Set(traceIds)].join(', ')}`);
+      }
+
+      console.log(`  ${events.length} updates in trace ${traceIds[0]}`);
+      console.log('Trace context test passed');
+      this.results.passed++;
+
+    } catch (error) {
+      console.error('Trace context test failed:', error.message);
+      this.results.failed++;
+      throw error;
+    }
+  }
+
+  // Reads an order's SSE stream until the server ends it after the final status.
+  async readStatusEvents(orderId, lastEventId = undefined) {
+    const headers = { 'x-api-key': axios.defaults.headers.common['x-api-key'] };
+    if (lastEventId) headers['Last-Event-ID'] = lastEventId;
+    const response = await fetch(`${API_URL}/api/orders/${orderId}/events`, {
+      headers, signal: AbortSignal.timeout(30000)
+    });
+    if (response.status !== 200) {
+      throw new Error(`SSE request failed with ${response.status}`);
+    }
+
+    const events = [];
+    const decoder = new TextDecoder();
+    let buffered = '';
+    for await (const chunk of response.body) {
+      buffered += decoder.decode(chunk, { stream: true });
+      let end;
+      while ((end = buffered.indexOf('\n\n')) !== -1) {
+        const fields = Object.fromEntries(buffered.slice(0, end).split('\n')
+          .filter(line => line && !line.startsWith(':'))
+          .map(line => [line.slice(0, line.indexOf(':')), line.slice(line.indexOf(':') + 1).trimStart()]));
+        buffered = buffered.slice(end + 2);
+        if (fields.event === 'status') {
+          events.push({ id: fields.id, data: JSON.parse(fields.data) });
         }
+      }
+    }
+    return events;
+  }
+
+  async testWebhooks() {
+    console.log('\n=== Webhooks Test ===');
+    this.results.total++;
+
+    // The receiver fails the first request, so the delivery has to be retried.
+    const received = [];
+    let waiting = null;
+    const receiver = http.createServer((req, res) => {
+      let body = '';
+      req.on('data', chunk => { body += chunk; });
+      req.on('end', () => {
+        received.push({ headers: req.headers, body });
+        res.writeHead(received.length === 1 ? 500 : 200).end();
+        if (waiting && received.length >= waiting.count) waiting.resolve();
+      });
+    });
+    const receivedCount = (count) => new Promise((resolve, reject) => {
+      if (received.length >= count) return resolve();
+      waiting = { count, resolve };
+      setTimeout(() => reject(new Error(`Webhook receiver got ${received.length} of ${count} requests`)), 30000);
+    });
+
+    try {
+      await new Promise(resolve => receiver.listen(WEBHOOK_RECEIVER_PORT, resolve));
+
+      try {
+        await axios.post(`${API_URL}/api/webhooks`, { url: 'ftp://example.com' }, { timeout: 5000 });
+        throw new Error('Accepted a non-http webhook url');
       } catch (error) {
-        if (error.response && error.response.status === 400) {
-          console.log(`    Correctly rejected slippage: ${order.max_slippage}`);
+        if (!error.response || error.response.data.violations?.[0]?.code !== 'invalid_webhook_url') throw error;
+      }
+
+      const { data: webhook } = await axios.post(`${API_URL}/api/webhooks`, {
+        url: `http://${WEBHOOK_RECEIVER_HOST}:${WEBHOOK_RECEIVER_PORT}/hooks`,
+        statuses: ['confirmed', 'failed', 'rejected']
+      }, { timeout: 5000 });
+      console.log(`  Registered webhook ${webhook.webhook_id}`);
+
+      const order = { token_in: 'SOL', token_out: 'USDC', amount: 0.5, order_type: 'market', max_slippage: 0.05 };
+      const result = await this.executeOrder(order);
+      await receivedCount(2);
+
+      for (const { headers, body } of received) {
+        const expected = crypto.createHmac('sha256', webhook.secret)
+          .update(`${headers['x-webhook-timestamp']}.${body}`).digest('hex');
+        if (headers['x-webhook-signature'] !== `v1=${expected}`) {
+          throw new Error(`Bad webhook signature: ${headers['x-webhook-signature']}`);
         }
       }
+      const payload = JSON.parse(received[1].body);
+      if (payload.order_id !== result.orderId || payload.status !== result.finalStatus || payload.update.status !== result.finalStatus) {
+        throw new Error(`Unexpected webhook payload: ${received[1].body}`);
+      }
+      console.log(`  Delivered ${payload.status} for ${payload.order_id} after a failed attempt`);
+
+      const deliveryUrl = `${API_URL}/api/webhooks/${webhook.webhook_id}/deliveries/${payload.delivery_id}`;
+      const { data: delivery } = await axios.get(deliveryUrl, { timeout: 5000 });
+      if (delivery.state !== 'delivered' || delivery.history.map(a => a.response_status).join() !== '500,200') {
+        throw new Error(`Unexpected delivery record: ${JSON.stringify(delivery)}`);
+      }
+
+      await axios.post(`${deliveryUrl}/redeliver`, null, { timeout: 5000 });
+      await receivedCount(3);
+      if (JSON.parse(received[2].body).delivery_id !== payload.delivery_id) {
+        throw new Error(`Redelivery sent the wrong delivery: ${received[2].body}`);
+      }
+
+      await axios.delete(`${API_URL}/api/webhooks/${webhook.webhook_id}`, { timeout: 5000 });
+
+      console.log('Webhooks test passed');
+      this.results.passed++;
+
+    } catch (error) {
+      console.error('Webhooks test failed:', error.message);
+      this.results.failed++;
+      throw error;
+    } finally {
+      receiver.close();
     }
   }
 
-  async testInvalidTokens() {
-    console.log('  Testing invalid tokens...');
-    
-    const invalidOrder = {
-      token_in: '', token_out: 'USDC', amount: 10,
-      order_type: 'market', max_slippage: 0.03
+  async testFixGateway() {
+    console.log('\n=== FIX Gateway Test ===');
+    this.results.total++;
+
+    const run = Date.now();
+    const session = new FixSession(`TEST${run}`);
+    // The backend remembers ClOrdIDs as idempotency keys, so each run uses its own.
+    const [market, stop, cancel] = ['fix-market', 'fix-stop', 'fix-cancel'].map(id => `${id}-${run}`);
+    const report = (clOrdId, statuses) => (m) => m['35'] === '8' && m['11'] === clOrdId && statuses.includes(m['39']);
+
+    try {
+      await session.connect();
+      session.send('A', [[98, 0], [108, 5], [141, 'Y'], [554, await useSuiteApiKey()]]);
+      await session.waitFor(m => m['35'] === 'A', 'Logon', 5000);
+
+      session.send('D', [[11, market], [55, 'SOL/USDC'], [54, 2], [38, 0.5], [40, 1], [60, '20250101-00:00:00']]);
+      const ack = await session.waitFor(report(market, ['0']), 'New report');
+      const final = await session.waitFor(report(market, ['2', '8']), 'final report');
+      if (ack['37'] !== final['37'] || (final['39'] === '2' && !(Number(final['31']) > 0))) {
+        throw new Error(`Unexpected execution reports: ${JSON.stringify([ack, final])}`);
+      }
+      console.log(`  Market order ${final['37']} ended with OrdStatus ${final['39']}`);
+
+      // SOL never falls to 1 USDC, so the stop stays armed until cancelled.
+      session.send('D', [[11, stop], [55, 'SOL/USDC'], [54, 2], [38, 0.5], [40, 3], [99, 1]]);
+      await session.waitFor(report(stop, ['0']), 'stop New report');
+      session.send('F', [[11, cancel], [41, stop], [55, 'SOL/USDC'], [54, 2]]);
+      const cancelled = await session.waitFor(report(cancel, ['4']), 'cancel report');
+      if (cancelled['41'] !== stop) {
+        throw new Error(`Cancel report did not reference the stop order: ${JSON.stringify(cancelled)}`);
+      }
+
+      session.send('D', [[11, market], [55, 'SOL/USDC'], [54, 2], [38, 0.5], [40, 1]]);
+      const duplicate = await session.waitFor(report(market, ['8']), 'duplicate rejection', 5000);
+      if (duplicate['103'] !== '6') {
+        throw new Error(`Expected OrdRejReason 6 for a reused ClOrdID, got ${duplicate['103']}`);
+      }
+
+      session.send('5', []);
+      await session.waitFor(m => m['35'] === '5', 'Logout', 5000);
+
+      console.log('FIX gateway test passed');
+      this.results.passed++;
+
+    } catch (error) {
+      console.error('FIX gateway test failed:', error.message);
+      this.results.failed++;
+      throw error;
+    } finally {
+      session.close();
+    }
+  }
+
+  async testMetrics() {
+    console.log('\n=== Metrics Test ===');
+    this.results.total++;
+
+    // Every earlier test has sent orders through each of these services.
+    const expected = {
+      backend: ['backend_orders_accepted_total{api="http"}', 'backend_orders_rejected_total', 'stream_lag_ms'],
+      router: ['router_order_stage_seconds_bucket{stage="routing"', 'router_venue_quote_seconds_bucket', 'router_orders_finished_total'],
+      db_service: ['db_write_seconds_bucket{result="inserted"', 'db_orders_stored_total'],
+      fix_gateway: ['fix_orders_accepted_total', 'fix_sessions'],
     };
 
     try {
-      const response = await axios.post(`${API_URL}/api/orders/execute`, invalidOrder, {
-        headers: { 'Content-Type': 'application/json' }, timeout: 5000
-      });
-      
-      if (response.status === 200) {
-        console.log('    Accepted invalid token pair');
+      for (const [service, names] of Object.entries(expected)) {
+        const response = await fetch(`${HEALTH_URLS[service]}/metrics`);
+        const text = await response.text();
+        if (response.status !== 200) {
+          throw new Error(`${service} /metrics answered ${response.status}`);
+        }
+        const missing = names.filter(name => !text.includes(name));
+        if (missing.length > 0) {
+          throw new Error(`${service} /metrics is missing ${missing.join(', ')}`);
+        }
+        console.log(`  ${service} exports ${names.length} expected series`);
+      }
+
+      console.log('Metrics test passed');
+      this.results.passed++;
+
+    } catch (error) {
+      console.error('Metrics test failed:', error.message);
+      this.results.failed++;
+      throw error;
+    }
+  }
+
+  async testWalletBalances() {
+    console.log('\n=== Wallet Balance Test ===');
+    this.results.total++;
+
+    try {
+      await this.testInsufficientBalance();
+      await this.testFundedOrderSettles();
+
+      console.log('Wallet balance test passed');
+      this.results.passed++;
+
+    } catch (error) {
+      console.error('Wallet balance test failed:', error.message);
+      this.results.failed++;
+      throw error;
+    }
+  }
+
+  async seedWallet(user, balances) {
+    return adminRequest('put', `/api/admin/wallets/${user.user_id}`, { balances });
+  }
+
+  async getWallet(user) {
+    const response = await axios.get(`${API_URL}/api/wallets/${user.user_id}`, {
+      headers: { 'x-api-key': user.api_key }, timeout: 5000
+    });
+    return response.data;
+  }
+
+  async testInsufficientBalance() {
+    console.log('  Testing insufficient balance...');
+
+    const user = await createUser('it-poor');
+    const empty = await this.seedWallet(user, {});
+    if (empty.balances.SOL !== 0 || empty.balances.USDC !== 0) {
+      throw new Error(`Empty seed did not zero the wallet: ${JSON.stringify(empty)}`);
+    }
+    await this.seedWallet(user, { SOL: 1.0 });
+
+    const result = await this.executeOrder({
+      token_in: 'SOL', token_out: 'USDC', amount: 5.0,
+      order_type: 'market', max_slippage: 0.03
+    }, null, user.api_key);
+
+    if (result.finalStatus !== 'rejected' || result.reason !== 'insufficient_balance') {
+      throw new Error(`Expected rejected/insufficient_balance, got ${result.finalStatus}/${result.reason}`);
+    }
+    if (result.statusSequence.includes('routing')) {
+      throw new Error('Rejected order was routed');
+    }
+
+    const wallet = await this.getWallet(user);
+    if (wallet.balances.SOL !== 1.0 || (wallet.held.SOL || 0) !== 0) {
+      throw new Error(`Balances changed after rejection: ${JSON.stringify(wallet)}`);
+    }
+
+    console.log('    Correctly rejected order exceeding balance');
+  }
+
+  async testFundedOrderSettles() {
+    console.log('  Testing funded order settlement...');
+
+    const user = await createUser('it-funded');
+    await this.seedWallet(user, { SOL: 10.0, USDC: 0.0 });
+
+    const result = await this.executeOrder({
+      token_in: 'SOL', token_out: 'USDC', amount: 4.0,
+      order_type: 'market', max_slippage: 0.03
+    }, null, user.api_key);
+
+    const wallet = await this.getWallet(user);
+    if ((wallet.held.SOL || 0) !== 0) {
+      throw new Error(`Funds still held after ${result.finalStatus}: ${JSON.stringify(wallet)}`);
+    }
+
+    if (result.finalStatus === 'confirmed') {
+      if (wallet.balances.SOL !== 6.0 || !(wallet.balances.USDC > 0)) {
+        throw new Error(`Fill not settled: ${JSON.stringify(wallet)}`);
+      }
+      console.log(`    Settled fill: ${JSON.stringify(wallet.balances)}`);
+    } else {
+      if (wallet.balances.SOL !== 10.0) {
+        throw new Error(`Funds not released after failure: ${JSON.stringify(wallet)}`);
       }
+      console.log(`    Order ${result.finalStatus}, funds released`);
+    }
+  }
+
+  async expectViolations(body, expectedCodes, headers = { 'Content-Type': 'application/json' }) {
+    try {
+      await axios.post(`${API_URL}/api/orders/execute`, body, { headers, timeout: 5000 });
     } catch (error) {
-      console.log('    Correctly handled invalid token pair');
+      if (!error.response || error.response.status !== 400) throw error;
+      const codes = error.response.data.violations.map(v => v.code);
+      const missing = expectedCodes.filter(code => !codes.includes(code));
+      if (error.response.data.error !== 'validation_failed' || missing.length > 0) {
+        throw new Error(`Expected ${expectedCodes.join(', ')}, got ${JSON.stringify(error.response.data)}`);
+      }
+      return codes;
+    }
+    throw new Error(`Invalid order was accepted: ${JSON.stringify(body)}`);
+  }
+
+  async testInvalidSlippage() {
+    console.log('  Testing invalid slippage...');
+    
+    const invalidOrders = [
+      { token_in: 'SOL', token_out: 'USDC', amount: 10, order_type: 'market', max_slippage: 0.6 },
+      { token_in: 'SOL', token_out: 'USDC', amount: 10, order_type: 'market', max_slippage: 0.005 }
+    ];
+
+    for (const order of invalidOrders) {
+      await this.expectViolations(order, ['slippage_out_of_range']);
+      console.log(`    Correctly rejected slippage: ${order.max_slippage}`);
     }
   }
 
+  async testInvalidTokens() {
+    console.log('  Testing invalid tokens...');
+
+    await this.expectViolations(
+      { token_in: '', token_out: 'USDC', amount: 10, order_type: 'market', max_slippage: 0.03 },
+      ['token_required']
+    );
+    await this.expectViolations(
+      { token_in: 'SOL', token_out: 'SOL', amount: 10, order_type: 'market', max_slippage: 0.03 },
+      ['same_token_pair']
+    );
+    await this.expectViolations(
+      { token_in: 'DOGE', token_out: 'USDC', amount: 10, order_type: 'market', max_slippage: 0.03 },
+      ['unsupported_token']
+    );
+    console.log('    Correctly rejected invalid token pairs');
+  }
+
   async testZeroAmount() {
     console.log('  Testing zero amount...');
-    
-    const zeroOrder = {
-      token_in: 'SOL', token_out: 'USDC', amount: 0,
+
+    for (const amount of [0, -5]) {
+      await this.expectViolations(
+        { token_in: 'SOL', token_out: 'USDC', amount, order_type: 'market', max_slippage: 0.03 },
+        ['amount_not_positive']
+      );
+    }
+    console.log('    Zero and negative amounts correctly rejected');
+  }
+
+  async testAllViolationsReported() {
+    console.log('  Testing multiple violations...');
+
+    const codes = await this.expectViolations(
+      { token_in: 'SOL', token_out: 'USDC', amount: 0, order_type: 'limit', max_slippage: 0.9 },
+      ['amount_not_positive', 'unsupported_order_type', 'slippage_out_of_range']
+    );
+    console.log(`    Reported ${codes.length} violations in one response`);
+  }
+
+  async testMalformedBody() {
+    console.log('  Testing malformed body...');
+
+    await this.expectViolations('{"token_in": "SOL",', ['malformed_json']);
+    await this.expectViolations(
+      { token_in: 'SOL', token_out: 'USDC', amount: 'ten', order_type: 'market' },
+      ['invalid_field']
+    );
+    console.log('    Malformed bodies correctly rejected');
+  }
+
+  async testNotionalThrottle() {
+    console.log('  Testing notional throttle...');
+
+    const hugeOrder = {
+      token_in: 'SOL', token_out: 'USDC', amount: 10000000,
       order_type: 'market', max_slippage: 0.03
     };
 
     try {
-      const response = await axios.post(`${API_URL}/api/orders/execute`, zeroOrder, {
+      await axios.post(`${API_URL}/api/orders/execute`, hugeOrder, {
         headers: { 'Content-Type': 'application/json' }, timeout: 5000
       });
-      
-      if (response.status === 200) {
-        console.log('    Zero amount order accepted');
-      }
+      throw new Error('Order above the notional limit was accepted');
     } catch (error) {
-      console.log('    Zero amount order correctly rejected');
+      if (!error.response || error.response.status !== 429) throw error;
+      if (!error.response.headers['retry-after'] || error.response.data.limit !== 'notional_per_minute') {
+        throw new Error(`Unexpected throttle response: ${JSON.stringify(error.response.data)}`);
+      }
     }
-  }
 
-  
+    console.log('    Correctly throttled order above notional limit');
+  }
 
   async testRapidConnections() {
     console.log('  Testing rapid connections...');
@@ -276,7 +1110,7 @@ class IntegrationTester {
     }
   }
 
-  async executeOrder(order, orderNumber = null) {
+  async executeOrder(order, orderNumber = null, apiKey = undefined) {
     return new Promise(async (resolve, reject) => {
       let orderId = null;
       let statusSequence = [];
@@ -287,8 +1121,10 @@ class IntegrationTester {
 
       try {
         console.log(`  → Submitting order via HTTP POST...`);
+        const headers = { 'Content-Type': 'application/json' };
+        if (apiKey) headers['x-api-key'] = apiKey;
         const response = await axios.post(`${API_URL}/api/orders/execute`, order, {
-          headers: { 'Content-Type': 'application/json' }, timeout: 10000
+          headers, timeout: 10000
         });
 
         orderId = response.data.order_id;
@@ -296,7 +1132,7 @@ class IntegrationTester {
         console.log(`  → HTTP Response: ${response.status} ${response.statusText}`);
 
         console.log(`  → Now upgrading to WebSocket via HTTP GET...`);
-        const ws = await this.createWebSocketUpgrade(orderId);
+        const ws = await this.createWebSocketUpgrade(orderId, apiKey);
         console.log(`  → WebSocket upgrade completed for order: ${orderId}`);
 
         ws.on('message', (data) => {
@@ -319,7 +1155,7 @@ class IntegrationTester {
                 console.log(`  → Execution price: $${executionPrice}`);
               }
 
-              if (status === 'confirmed' || status === 'failed') {
+              if (['confirmed', 'partially_filled', 'failed', 'rejected', 'cancelled'].includes(status)) {
                 finalStatus = status;
                 completionTime = Date.now() - startTime;
                 console.log(`  → Order ${orderId} completed with status: ${finalStatus}`);
@@ -328,7 +1164,7 @@ class IntegrationTester {
                 
                 resolve({
                   orderId, statusSequence, finalStatus,
-                  executionPrice, completionTime, reason: message.reason
+                  executionPrice, completionTime, reason: message.reason, progress: message.progress
                 });
               }
             }